@@ -5,6 +5,9 @@
 #ifndef RESVG_H
 #define RESVG_H
 
+#include <stdbool.h>
+#include <stdint.h>
+
 #ifdef RESVG_CAIRO_BACKEND
 #include <cairo.h>
 #endif
@@ -13,6 +16,18 @@
 struct resvg_document;
 typedef struct resvg_document resvg_document;
 
+/**
+ * @brief A pixel format of a caller-provided buffer.
+ *
+ * All formats use 8 bits per channel and are stored in the specified byte order.
+ */
+typedef enum resvg_pixel_format {
+    RESVG_FORMAT_RGBA8,
+    RESVG_FORMAT_RGBA8_PREMULTIPLIED,
+    RESVG_FORMAT_BGRA8,
+    RESVG_FORMAT_BGRA8_PREMULTIPLIED,
+} resvg_pixel_format;
+
 void resvg_init_log();
 
 /**
@@ -68,4 +83,30 @@ void resvg_qt_render_to_canvas(void *painter,
                                resvg_document *doc);
 #endif
 
+#if defined(RESVG_CAIRO_BACKEND) || defined(RESVG_QT_BACKEND)
+/**
+ * @brief Renders <b>resvg_document</b> to a caller-provided buffer.
+ *
+ * The image is scaled to fit the buffer, preserving the aspect ratio.
+ * Uses the cairo backend when both backends are enabled.
+ *
+ * @param doc Render tree. Will panic on NULL value.
+ * @param buffer Pixel buffer with at least stride * height bytes, aligned to 4 bytes.
+ *               Will panic on NULL value.
+ * @param width Image width in pixels.
+ * @param height Image height in pixels.
+ * @param stride Number of bytes per buffer row. Must be a multiple of 4.
+ * @param format Buffer pixel format.
+ * @param error The error string if false is returned. Should be destroyed via resvg_error_msg_destroy.
+ * @return false on error.
+ */
+bool resvg_render_to_buffer(resvg_document *doc,
+                            uint8_t *buffer,
+                            uint32_t width,
+                            uint32_t height,
+                            uint32_t stride,
+                            resvg_pixel_format format,
+                            char **error);
+#endif
+
 #endif // RESVG_H
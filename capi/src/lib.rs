@@ -18,6 +18,7 @@ use std::ffi::{
 };
 use std::os::raw::{
     c_char,
+    c_int,
 };
 
 #[cfg(feature = "qt-backend")]
@@ -196,6 +197,55 @@ pub extern fn resvg_cairo_render_to_canvas(
     resvg::render_cairo::render_to_canvas(&cr, rect, &doc.0);
 }
 
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+#[no_mangle]
+pub extern fn resvg_render_to_buffer(
+    doc: *mut resvg_document,
+    buffer: *mut u8,
+    width: u32,
+    height: u32,
+    stride: u32,
+    format: c_int,
+    error: *mut *mut c_char,
+) -> bool {
+    let doc = unsafe {
+        assert!(!doc.is_null());
+        &mut *doc
+    };
+
+    // Must be in sync with 'resvg_pixel_format'.
+    let format = match format {
+        0 => resvg::PixelFormat::Rgba8,
+        1 => resvg::PixelFormat::Rgba8Premultiplied,
+        2 => resvg::PixelFormat::Bgra8,
+        3 => resvg::PixelFormat::Bgra8Premultiplied,
+        _ => {
+            let c_str = CString::new("Error: invalid pixel format.").unwrap();
+            unsafe { *error = c_str.into_raw(); }
+            return false;
+        }
+    };
+
+    let buffer = unsafe {
+        assert!(!buffer.is_null());
+        std::slice::from_raw_parts_mut(buffer, stride as usize * height as usize)
+    };
+
+    let opt = resvg::Options {
+        dpi: doc.0.dpi,
+        .. resvg::Options::default()
+    };
+
+    match resvg::render_to_buffer(&doc.0, &opt, buffer, width, height, stride as usize, format) {
+        Ok(_) => true,
+        Err(e) => {
+            let c_str = CString::new(e.to_string()).unwrap();
+            unsafe { *error = c_str.into_raw(); }
+            false
+        }
+    }
+}
+
 #[no_mangle]
 pub extern fn resvg_get_image_size(
     doc: *mut resvg_document,
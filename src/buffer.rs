@@ -0,0 +1,297 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+use std::cmp;
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+use dom;
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+use math::Size;
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+use progress::Progress;
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+use render_utils;
+
+#[cfg(feature = "cairo-backend")]
+use render_cairo::render_band;
+#[cfg(all(feature = "qt-backend", not(feature = "cairo-backend")))]
+use render_qt::render_band;
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+use {
+    ErrorKind,
+    Result,
+};
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+use {
+    Options,
+};
+
+
+/// A pixel format of a caller-provided buffer.
+///
+/// All formats use 8 bits per channel and are stored in the specified byte order.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PixelFormat {
+    /// RGBA with a straight alpha.
+    Rgba8,
+    /// RGBA with a premultiplied alpha.
+    Rgba8Premultiplied,
+    /// BGRA with a straight alpha.
+    Bgra8,
+    /// BGRA with a premultiplied alpha.
+    Bgra8Premultiplied,
+}
+
+/// The maximum height of a band.
+///
+/// Backends can't render into a foreign memory, so each band is rendered
+/// into a temporary image first.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+const MAX_BAND_HEIGHT: u32 = 256;
+
+/// Renders SVG to a caller-provided buffer.
+///
+/// The image is scaled to fit the buffer, preserving the aspect ratio,
+/// so `Options::fit_to` is ignored.
+///
+/// `stride` is the number of bytes per buffer row. Both the buffer address
+/// and the stride must be aligned to 4 bytes.
+/// The whole buffer area is overwritten, including transparent pixels.
+///
+/// The cairo backend is used when both cairo and Qt backends are enabled.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+pub fn render_to_buffer(
+    doc: &dom::Document,
+    opt: &Options,
+    buffer: &mut [u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+) -> Result<()> {
+    check_buffer(buffer, width, height, stride)?;
+
+    #[cfg(all(feature = "qt-backend", not(feature = "cairo-backend")))]
+    let _app = ::qt::GuiApp::new("resvg");
+
+    let img_size = Size::new(width as f64, height as f64);
+    let threads = cmp::max(opt.threads, 1);
+    let bands = bands_count(height, threads);
+    let progress = Progress::new(opt, &doc.elements);
+    render_utils::render_bands(buffer, img_size, stride, bands, threads, &progress, |tile, band| {
+        render_band(doc, opt, tile, band, stride, &progress)?;
+        convert_from_bgra(band, tile.rect.w as u32, tile.rect.h as u32, stride, format);
+        Ok(())
+    })?;
+
+    progress.finish()
+}
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+fn bands_count(height: u32, threads: usize) -> u32 {
+    cmp::max(threads as u32, height.div_ceil(MAX_BAND_HEIGHT))
+}
+
+/// Checks that the buffer can hold an image with the specified size.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+fn check_buffer(buffer: &[u8], width: u32, height: u32, stride: usize) -> Result<()> {
+    if width == 0 || height == 0 {
+        return Err(ErrorKind::InvalidBuffer.into());
+    }
+
+    // Backends are writing pixels as 32-bit values.
+    if stride & 3 != 0 || buffer.as_ptr() as usize & 3 != 0 {
+        return Err(ErrorKind::InvalidBuffer.into());
+    }
+
+    if !is_rows_fit(buffer.len(), stride, width as usize * 4, height) {
+        return Err(ErrorKind::InvalidBuffer.into());
+    }
+
+    Ok(())
+}
+
+/// Copies image rows between buffers with different strides.
+///
+/// Both buffers are checked first, so nothing is copied on error.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+pub fn copy_rows(
+    src: &[u8],
+    src_stride: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    let row_len = width as usize * 4;
+
+    // Alignment doesn't matter here.
+    if !is_rows_fit(src.len(), src_stride, row_len, height)
+        || !is_rows_fit(dst.len(), dst_stride, row_len, height) {
+        return Err(ErrorKind::InvalidBuffer.into());
+    }
+
+    for y in 0..height as usize {
+        let src_row = &src[(y * src_stride)..(y * src_stride + row_len)];
+        dst[(y * dst_stride)..(y * dst_stride + row_len)].copy_from_slice(src_row);
+    }
+
+    Ok(())
+}
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+fn is_rows_fit(len: usize, stride: usize, row_len: usize, height: u32) -> bool {
+    if height == 0 {
+        return true;
+    }
+
+    if stride < row_len {
+        return false;
+    }
+
+    // The last row doesn't need any padding.
+    let min_len = stride.checked_mul(height as usize - 1).and_then(|n| n.checked_add(row_len));
+    match min_len {
+        Some(min_len) => len >= min_len,
+        None => false,
+    }
+}
+
+/// Converts a premultiplied BGRA image into the specified format in place.
+///
+/// Both backends are using a premultiplied ARGB32 canvas,
+/// which is stored as BGRA on little-endian systems.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+fn convert_from_bgra(buffer: &mut [u8], width: u32, height: u32, stride: usize, format: PixelFormat) {
+    if format == PixelFormat::Bgra8Premultiplied {
+        return;
+    }
+
+    let row_len = width as usize * 4;
+
+    for y in 0..height as usize {
+        let row = &mut buffer[(y * stride)..(y * stride + row_len)];
+
+        for p in row.chunks_mut(4) {
+            let (b, g, r, a) = (p[0], p[1], p[2], p[3]);
+
+            let (r, g, b) = match format {
+                PixelFormat::Rgba8 | PixelFormat::Bgra8 => {
+                    (unpremultiply(r, a), unpremultiply(g, a), unpremultiply(b, a))
+                }
+                PixelFormat::Rgba8Premultiplied | PixelFormat::Bgra8Premultiplied => {
+                    (r, g, b)
+                }
+            };
+
+            match format {
+                PixelFormat::Rgba8 | PixelFormat::Rgba8Premultiplied => {
+                    p.copy_from_slice(&[r, g, b, a]);
+                }
+                PixelFormat::Bgra8 | PixelFormat::Bgra8Premultiplied => {
+                    p.copy_from_slice(&[b, g, r, a]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+fn unpremultiply(c: u8, a: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+
+    // Round to the nearest value.
+    let n = (c as u32 * 255 + a as u32 / 2) / a as u32;
+    if n > 255 { 255 } else { n as u8 }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Returns a 4-byte aligned slice.
+    fn aligned(data: &mut Vec<u8>, len: usize) -> &mut [u8] {
+        data.resize(len + 3, 0);
+        let offset = (4 - data.as_ptr() as usize % 4) % 4;
+        &mut data[offset..(offset + len)]
+    }
+
+    #[test]
+    fn check_buffer_size() {
+        let mut data = Vec::new();
+        let buf = aligned(&mut data, 48);
+
+        assert!(check_buffer(buf, 2, 2, 8).is_ok());
+        // The last row has no padding.
+        assert!(check_buffer(&buf[..40], 2, 2, 32).is_ok());
+        assert!(check_buffer(&buf[..39], 2, 2, 32).is_err());
+        // Stride is smaller than a row.
+        assert!(check_buffer(buf, 3, 2, 8).is_err());
+        assert!(check_buffer(buf, 0, 2, 8).is_err());
+        assert!(check_buffer(buf, 2, 0, 8).is_err());
+    }
+
+    #[test]
+    fn check_buffer_alignment() {
+        let mut data = Vec::new();
+        let buf = aligned(&mut data, 48);
+
+        assert!(check_buffer(buf, 2, 2, 10).is_err());
+        assert!(check_buffer(&buf[1..], 2, 2, 8).is_err());
+    }
+
+    #[test]
+    fn copy_rows_with_strides() {
+        // One pixel per row and a 4 bytes padding in the source.
+        let src = [1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8];
+        let mut dst = [9; 8];
+        copy_rows(&src, 8, &mut dst, 4, 1, 2).unwrap();
+        assert_eq!(dst, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        // Nothing is copied when any of the buffers is too small.
+        let mut dst = [9; 7];
+        assert!(copy_rows(&src, 8, &mut dst, 4, 1, 2).is_err());
+        assert_eq!(dst, [9; 7]);
+        assert!(copy_rows(&src[..11], 8, &mut [0; 8], 4, 1, 2).is_err());
+        // Stride is smaller than a row.
+        assert!(copy_rows(&[0; 16], 8, &mut [0; 16], 4, 2, 2).is_err());
+    }
+
+    #[test]
+    fn convert_keeps_padding() {
+        // Two pixels per row and a 4 bytes padding.
+        let mut buf = [
+            10, 20, 30, 255,   0,  0,  0, 0,   1, 1, 1, 1,
+             0, 64,  0, 128,  40, 50, 60, 255, 1, 1, 1, 1,
+        ];
+        convert_from_bgra(&mut buf, 2, 2, 12, PixelFormat::Rgba8Premultiplied);
+        assert_eq!(buf, [
+            30, 20, 10, 255,   0,  0,  0, 0,   1, 1, 1, 1,
+             0, 64,  0, 128,  60, 50, 40, 255, 1, 1, 1, 1,
+        ]);
+    }
+
+    #[test]
+    fn convert_unpremultiplied() {
+        let mut buf = [0, 64, 32, 128,  7, 7, 7, 0];
+        convert_from_bgra(&mut buf, 2, 1, 8, PixelFormat::Bgra8);
+        assert_eq!(buf, [0, 128, 64, 128,  0, 0, 0, 0]);
+
+        let mut buf = [0, 64, 32, 128];
+        convert_from_bgra(&mut buf, 1, 1, 4, PixelFormat::Rgba8);
+        assert_eq!(buf, [64, 128, 0, 128]);
+    }
+
+    #[test]
+    fn convert_premultiplied_bgra_is_noop() {
+        let mut buf = [1, 2, 3, 4];
+        convert_from_bgra(&mut buf, 1, 1, 4, PixelFormat::Bgra8Premultiplied);
+        assert_eq!(buf, [1, 2, 3, 4]);
+    }
+}
@@ -21,9 +21,11 @@ use traits::{
 
 pub fn convert(
     defs: &[dom::RefElement],
-    attrs: &svgdom::Attributes,
+    node: &svgdom::Node,
 ) -> Option<dom::Fill>
 {
+    let attrs = node.attributes();
+
     let paint = if let Some(fill) = attrs.get_type(AId::Fill) {
         match *fill {
             AValue::Color(c) => {
@@ -40,7 +42,8 @@ pub fn convert(
                 match p {
                     Some(p) => p,
                     None => {
-                        warn!("Filling with {:?} is not supported.", link.tag_id().unwrap());
+                        diag!(Warning, UnsupportedPaint, node.id(),
+                              "Filling with {:?} is not supported.", link.tag_id().unwrap());
                         return None;
                     }
                 }
@@ -49,7 +52,8 @@ pub fn convert(
                 return None;
             }
             _ => {
-                warn!("An invalid fill value: {}. Skipped.", fill);
+                diag!(Warning, InvalidAttribute, node.id(),
+                      "An invalid fill value: {}. Skipped.", fill);
                 return None;
             }
         }
@@ -131,7 +131,8 @@ fn convert_stops(node: &svgdom::Node) -> Option<Vec<dom::Stop>> {
     }
 
     if stops.len() < 2 {
-        warn!("Gradient '{}' contains less than 2 stop children. Skipped.", node.id());
+        diag!(Warning, InvalidGradient, node.id(),
+              "Gradient '{}' contains less than 2 stop children. Skipped.", node.id());
         return None;
     }
 
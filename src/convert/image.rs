@@ -2,7 +2,11 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
 use std::path;
+use std::sync::Arc;
 
 use base64;
 use svgdom;
@@ -21,11 +25,28 @@ use math::{
 };
 
 use {
+    parse_image_doc,
     Options,
 };
 
 
-pub fn convert(node: &svgdom::Node, opt: &Options) -> Option<dom::Element> {
+// Prevents a stack overflow on deeply nested, but not recursive, SVG images.
+const MAX_SVG_NESTING: usize = 16;
+
+thread_local! {
+    // Parent documents of SVG images that are currently loading.
+    // Used to detect recursive references.
+    static LOADING: RefCell<Vec<Option<path::PathBuf>>> = const { RefCell::new(Vec::new()) };
+}
+
+
+/// Raster images data by links.
+///
+/// `use` elements are resolved by copying, so the same image can be referenced many times.
+/// Such images are loaded only once and share the same data.
+pub type SharedImages = HashMap<String, (Arc<Vec<u8>>, dom::ImageDataKind)>;
+
+pub fn convert(node: &svgdom::Node, images: &mut SharedImages, opt: &Options) -> Option<dom::Element> {
     let attrs = node.attributes();
 
     let ts = attrs.get_transform(AId::Transform).unwrap_or_default();
@@ -38,7 +59,8 @@ pub fn convert(node: &svgdom::Node, opt: &Options) -> Option<dom::Element> {
             if let Some(v) = attrs.get_type($aid) {
                 v
             } else {
-                warn!("The 'image' element lacks '{}' attribute. Skipped.", $aid);
+                diag!(Warning, MissingAttribute, node.id(),
+                      "The 'image' element lacks '{}' attribute. Skipped.", $aid);
                 return None;
             }
         )
@@ -49,7 +71,19 @@ pub fn convert(node: &svgdom::Node, opt: &Options) -> Option<dom::Element> {
 
     let href: &String = get_attr!(AId::XlinkHref);
 
-    if let Some(data) = get_href_data(href, opt.path.as_ref()) {
+    let data = match images.get(href) {
+        Some(&(ref data, kind)) => Some(dom::ImageData::Raw(data.clone(), kind)),
+        None => {
+            let data = get_href_data(href, &node.id(), opt);
+            if let Some(dom::ImageData::Raw(ref data, kind)) = data {
+                images.insert(href.clone(), (data.clone(), kind));
+            }
+
+            data
+        }
+    };
+
+    if let Some(data) = data {
         let elem = dom::Element {
             id: node.id().clone(),
             data: dom::Type::Image(dom::Image {
@@ -65,44 +99,276 @@ pub fn convert(node: &svgdom::Node, opt: &Options) -> Option<dom::Element> {
     }
 }
 
-fn get_href_data(href: &str, path: Option<&path::PathBuf>) -> Option<dom::ImageData> {
-    if href.starts_with("data:image") {
-        if let Some(idx) = href.find(',') {
-            let kind = if href[..idx].contains("image/jpg") {
-                dom::ImageDataKind::JPEG
-            } else if href[..idx].contains("image/png") {
-                dom::ImageDataKind::PNG
-            } else {
-                return None;
-            };
+fn get_href_data(href: &str, id: &str, opt: &Options) -> Option<dom::ImageData> {
+    if href.starts_with("data:") {
+        return load_data_url(href, id, opt);
+    }
+
+    let path = opt.loader.resolve(href, opt.path.as_deref());
+    load_file(href, &path, id, opt)
+}
+
+fn load_data_url(href: &str, id: &str, opt: &Options) -> Option<dom::ImageData> {
+    let data = match decode_data_url(href) {
+        Some(data) => data,
+        None => {
+            diag!(Warning, InvalidImage, id, "Invalid xlink:href content.");
+            return None;
+        }
+    };
+
+    // The MIME type is often wrong, so we are checking the content instead.
+    if let Some(kind) = image_kind(&data) {
+        return Some(dom::ImageData::Raw(Arc::new(data), kind));
+    }
+
+    if is_svg_data(&data) {
+        // Links inside an embedded image are resolved relative to the current document.
+        return load_svg(data, opt.path.clone(), id, opt);
+    }
+
+    diag!(Warning, InvalidImage, id, "Unsupported embedded image format.");
+    None
+}
+
+fn load_file(href: &str, path: &path::Path, id: &str, opt: &Options) -> Option<dom::ImageData> {
+    // The checked path is used from now on, so the resource can't be swapped after the check.
+    let path = match opt.policy.check(path) {
+        Some(path) => path,
+        None => {
+            diag!(Warning, AccessDenied, id,
+                  "Access to '{}' is denied by the resource policy. Skipped.", path.display());
+            return None;
+        }
+    };
+    let path = path.as_path();
+
+    // Only SVG documents can be parents, so there is no need to check the format first.
+    if is_loading(path, opt) {
+        diag!(Warning, RecursiveImage, id,
+              "SVG image '{}' references itself. Skipped.", path.display());
+        return None;
+    }
+
+    let data = match opt.loader.load(href, path) {
+        Ok(data) => data,
+        Err(e) => {
+            diag!(Warning, ImageLoadFailed, id, "Failed to load '{}': {}.", path.display(), e);
+            return None;
+        }
+    };
+
+    // The file extension is often wrong, so we are checking the content instead.
+    if let Some(kind) = image_kind(&data) {
+        return Some(dom::ImageData::Raw(Arc::new(data), kind));
+    }
+
+    if is_svg_path(path) || is_svg_data(&data) {
+        return load_svg(data, Some(path.to_owned()), id, opt);
+    }
+
+    diag!(Warning, InvalidImage, id, "Unsupported image format: '{}'.", path.display());
+    None
+}
+
+/// Decodes a base64 or a percent-encoded `data:` URL.
+fn decode_data_url(href: &str) -> Option<Vec<u8>> {
+    let idx = href.find(',')?;
+    let header = &href[5..idx];
+    let content = &href[(idx + 1)..];
+
+    if header.to_lowercase().ends_with(";base64") {
+        let conf = base64::Config::new(
+            base64::CharacterSet::Standard,
+            true,
+            true,
+            base64::LineWrap::NoWrap,
+        );
+
+        base64::decode_config(content, conf).ok()
+    } else {
+        Some(percent_decode(content))
+    }
+}
 
-            let base_data = &href[(idx + 1)..];
+fn percent_decode(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut data = Vec::with_capacity(bytes.len());
 
-            let conf = base64::Config::new(
-                base64::CharacterSet::Standard,
-                true,
-                true,
-                base64::LineWrap::NoWrap,
-            );
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let c = bytes.get((i + 1)..(i + 3))
+                .and_then(|hex| ::std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
 
-            if let Ok(data) = base64::decode_config(base_data, conf) {
-                return Some(dom::ImageData::Raw(data.to_owned(), kind));
+            if let Some(c) = c {
+                data.push(c);
+                i += 3;
+                continue;
             }
         }
 
-        warn!("Invalid xlink:href content.");
+        data.push(bytes[i]);
+        i += 1;
+    }
+
+    data
+}
+
+/// Checks that the data looks like SVG or SVGZ.
+fn is_svg_data(data: &[u8]) -> bool {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        return true;
+    }
+
+    let data = if data.starts_with(b"\xEF\xBB\xBF") { &data[3..] } else { data };
+    data.iter().find(|c| !c.is_ascii_whitespace()) == Some(&b'<')
+}
+
+fn is_svg_path(path: &path::Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => {
+            let ext = ext.to_lowercase();
+            ext == "svg" || ext == "svgz"
+        }
+        None => false,
+    }
+}
+
+/// Checks that the path is a parent document of the current SVG image.
+fn is_loading(path: &path::Path, opt: &Options) -> bool {
+    let file = Some(normalize_path(path));
+    let parent = opt.path.as_ref().map(|p| normalize_path(p));
+
+    file == parent || LOADING.with(|stack| stack.borrow().contains(&file))
+}
+
+// Custom loaders are not required to use real files.
+fn normalize_path(path: &path::Path) -> path::PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Parses an SVG image.
+///
+/// `path` is used to resolve links inside the image.
+fn load_svg(
+    data: Vec<u8>,
+    path: Option<path::PathBuf>,
+    id: &str,
+    opt: &Options,
+) -> Option<dom::ImageData> {
+    let depth = LOADING.with(|stack| stack.borrow().len());
+    if depth >= MAX_SVG_NESTING {
+        diag!(Warning, RecursiveImage, id, "SVG images are nested too deeply. Skipped.");
+        return None;
+    }
+
+    let sub_opt = Options {
+        path,
+        dpi: opt.dpi,
+        loader: opt.loader.clone(),
+        policy: opt.policy.clone(),
+        limits: opt.limits,
+        progress: opt.progress.clone(),
+        languages: opt.languages.clone(),
+        extensions: opt.extensions.clone(),
+        .. Options::default()
+    };
+
+    let parent = opt.path.as_ref().map(|p| normalize_path(p));
+    LOADING.with(|stack| stack.borrow_mut().push(parent));
+    let res = parse_image_doc(data, &sub_opt);
+    LOADING.with(|stack| stack.borrow_mut().pop());
+
+    match res {
+        Ok(doc) => Some(dom::ImageData::Svg(Box::new(doc))),
+        Err(e) => {
+            diag!(Warning, InvalidImage, id, "Failed to parse an SVG image: {}.", e);
+            None
+        }
+    }
+}
+
+/// Detects an image kind using the file signature.
+fn image_kind(data: &[u8]) -> Option<dom::ImageDataKind> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(dom::ImageDataKind::PNG)
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some(dom::ImageDataKind::JPEG)
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some(dom::ImageDataKind::GIF)
+    } else if data.starts_with(b"BM") {
+        Some(dom::ImageDataKind::BMP)
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some(dom::ImageDataKind::WEBP)
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        Some(dom::ImageDataKind::TIFF)
+    } else if data.starts_with(&[0, 0, 1, 0]) {
+        Some(dom::ImageDataKind::ICO)
     } else {
-        let path = match path {
-            Some(path) => path.parent().unwrap().join(href),
-            None => path::PathBuf::from(href),
-        };
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
 
-        if path.exists() {
-            return Some(dom::ImageData::Path(path.to_owned()));
+    use super::*;
+    use dom;
+    use test_utils::parse;
+
+    #[test]
+    fn copies_share_data() {
+        // A PNG signature is enough, because images are decoded only during rendering.
+        let doc = parse("width='100' height='100'", "\
+            <image id='img' width='10' height='10' xlink:href='data:image/png;base64,iVBORw0KGgo='/>\
+            <use xlink:href='#img' x='20'/>");
+
+        let mut list = Vec::new();
+        for elem in doc.descendants() {
+            if let dom::Type::Image(ref img) = elem.data {
+                if let dom::ImageData::Raw(ref data, _) = img.data {
+                    list.push(data.clone());
+                }
+            }
         }
 
-        warn!("Linked file does not exist: '{}'.", href);
+        assert_eq!(list.len(), 2);
+        assert!(Arc::ptr_eq(&list[0], &list[1]));
     }
 
-    None
+    #[test]
+    fn kinds() {
+        use dom::ImageDataKind::*;
+
+        assert!(image_kind(b"\x89PNG\r\n\x1a\n") == Some(PNG));
+        assert!(image_kind(&[0xff, 0xd8, 0xff, 0xe0]) == Some(JPEG));
+        assert!(image_kind(b"GIF87a") == Some(GIF));
+        assert!(image_kind(b"GIF89a") == Some(GIF));
+        assert!(image_kind(b"BM\0\0") == Some(BMP));
+        assert!(image_kind(b"RIFF\0\0\0\0WEBPVP8 ") == Some(WEBP));
+        assert!(image_kind(b"II*\0") == Some(TIFF));
+        assert!(image_kind(b"MM\0*") == Some(TIFF));
+        assert!(image_kind(&[0, 0, 1, 0, 1, 0]) == Some(ICO));
+
+        assert!(image_kind(b"RIFF\0\0\0\0WAVE").is_none());
+        assert!(image_kind(b"GIF").is_none());
+        assert!(image_kind(b"<svg/>").is_none());
+        assert!(image_kind(b"").is_none());
+    }
+
+    #[test]
+    fn data_urls() {
+        assert_eq!(decode_data_url("data:image/gif;base64,R0lGODlh"), Some(b"GIF89a".to_vec()));
+        // Line breaks are allowed.
+        assert_eq!(decode_data_url("data:;BASE64,R0lG\nODlh"), Some(b"GIF89a".to_vec()));
+        assert_eq!(decode_data_url("data:image/gif;base64,R0l!"), None);
+        assert_eq!(decode_data_url("data:image/gif;base64"), None);
+
+        assert_eq!(decode_data_url("data:image/svg+xml,%3Csvg%2F%3e"), Some(b"<svg/>".to_vec()));
+        // Invalid escapes are preserved.
+        assert_eq!(decode_data_url("data:,100%25%zz%4"), Some(b"100%%zz%4".to_vec()));
+    }
 }
@@ -24,6 +24,10 @@ use math::{
     Size,
 };
 
+use preproc::{
+    ContentSize,
+};
+
 use {
     ErrorKind,
     Options,
@@ -39,7 +43,11 @@ mod shapes;
 mod text;
 
 
-pub fn convert_doc(svg_doc: &svgdom::Document, opt: &Options) -> Result<dom::Document> {
+pub fn convert_doc(
+    svg_doc: &svgdom::Document,
+    content_size: Option<&ContentSize>,
+    opt: &Options,
+) -> Result<dom::Document> {
     let svg = if let Some(svg) = svg_doc.svg_element() {
         svg
     } else {
@@ -52,13 +60,19 @@ pub fn convert_doc(svg_doc: &svgdom::Document, opt: &Options) -> Result<dom::Doc
 
     let defs = convert_ref_nodes(&svg);
 
-    Ok(dom::Document {
+    let mut doc = dom::Document {
         size: get_img_size(&svg)?,
         view_box: get_view_box(&svg)?,
         dpi: opt.dpi,
-        elements: convert_nodes(&svg, &defs, opt),
+        elements: convert_nodes(&svg, &defs, &mut image::SharedImages::new(), opt),
         defs: defs,
-    })
+    };
+
+    if let Some(content_size) = content_size {
+        fit_to_content(&mut doc, content_size)?;
+    }
+
+    Ok(doc)
 }
 
 pub fn convert_ref_nodes(parent: &svgdom::Node) -> Vec<dom::RefElement> {
@@ -85,7 +99,7 @@ pub fn convert_ref_nodes(parent: &svgdom::Node) -> Vec<dom::RefElement> {
                 }
             }
             _ => {
-                warn!("Unsupported element '{}'.", id);
+                diag!(Warning, UnsupportedElement, node.id(), "Unsupported element '{}'.", id);
             }
         }
     }
@@ -96,6 +110,7 @@ pub fn convert_ref_nodes(parent: &svgdom::Node) -> Vec<dom::RefElement> {
 pub fn convert_nodes(
     parent: &svgdom::Node,
     defs: &[dom::RefElement],
+    images: &mut image::SharedImages,
     opt: &Options,
 ) -> Vec<dom::Element>
 {
@@ -120,7 +135,7 @@ pub fn convert_nodes(
 
                 let ts = attrs.get_transform(AId::Transform).unwrap_or_default();
                 let opacity = attrs.get_number(AId::Opacity);
-                let children = convert_nodes(&node, defs, opt);
+                let children = convert_nodes(&node, defs, images, opt);
 
                 // TODO: check that opacity != 1.0
 
@@ -149,10 +164,10 @@ pub fn convert_nodes(
             }
               EId::Use
             | EId::Switch => {
-                warn!("'{}' must be resolved.", id);
+                diag!(Warning, UnsupportedElement, node.id(), "'{}' must be resolved. Skipped.", id);
             }
             EId::Svg => {
-                warn!("Nested 'svg' unsupported.");
+                diag!(Warning, UnsupportedElement, node.id(), "Nested 'svg' unsupported.");
             }
             EId::Path => {
                 let attrs = node.attributes();
@@ -168,12 +183,12 @@ pub fn convert_nodes(
                 }
             }
             EId::Image => {
-                if let Some(elem) = image::convert(&node, opt) {
+                if let Some(elem) = image::convert(&node, images, opt) {
                     elements.push(elem);
                 }
             }
             _ => {
-                warn!("Unsupported element '{}'.", id);
+                diag!(Warning, UnsupportedElement, node.id(), "Unsupported element '{}'.", id);
             }
         }
     }
@@ -181,6 +196,29 @@ pub fn convert_nodes(
     elements
 }
 
+/// Resolves the percent-based SVG size without a `viewBox`
+/// using the bounding box of all elements.
+///
+/// Text elements are ignored, because we don't have text metrics here.
+fn fit_to_content(doc: &mut dom::Document, content_size: &ContentSize) -> Result<()> {
+    let bbox = match doc.stroke_bbox() {
+        Some(bbox) => bbox,
+        None => return Err(ErrorKind::SizeDeterminationUnsupported.into()),
+    };
+
+    let w = content_size.width.map(|f| bbox.w * f).unwrap_or(doc.size.w).round();
+    let h = content_size.height.map(|f| bbox.h * f).unwrap_or(doc.size.h).round();
+
+    if !(w > 0.0 && h > 0.0) {
+        return Err(ErrorKind::SizeDeterminationUnsupported.into());
+    }
+
+    doc.size = Size::new(w, h);
+    doc.view_box = bbox;
+
+    Ok(())
+}
+
 fn get_img_size(svg: &svgdom::Node) -> Result<Size> {
     let attrs = svg.attributes();
 
@@ -41,8 +41,8 @@ pub fn convert(
 ) -> Result<dom::Element> {
     let attrs = node.attributes();
 
-    let fill = fill::convert(defs, &attrs);
-    let stroke = stroke::convert(defs, &attrs);
+    let fill = fill::convert(defs, node);
+    let stroke = stroke::convert(defs, node);
     let d = convert_path(d);
 
     let ts = attrs.get_transform(AId::Transform).unwrap_or_default();
@@ -45,12 +45,14 @@ fn convert_rect(node: &svgdom::Node) -> Option<path::Path> {
     let width  = attrs.get_number(AId::Width).unwrap_or(0.0);
     let height = attrs.get_number(AId::Height).unwrap_or(0.0);
     if !(width > 0.0) {
-        warn!("Rect '{}' has an invalid 'width' value. Skipped.", node.id());
+        diag!(Warning, InvalidAttribute, node.id(),
+              "Rect '{}' has an invalid 'width' value. Skipped.", node.id());
         return None;
     }
 
     if !(height > 0.0) {
-        warn!("Rect '{}' has an invalid 'height' value. Skipped.", node.id());
+        diag!(Warning, InvalidAttribute, node.id(),
+              "Rect '{}' has an invalid 'height' value. Skipped.", node.id());
         return None;
     }
 
@@ -157,13 +159,15 @@ fn points_to_path(node: &svgdom::Node, eid: &str) -> Option<path::Path> {
     let points = if let Some(p) = attrs.get_number_list(AId::Points) {
         p
     } else {
-        warn!("{} '{}' has an invalid 'points' value. Skipped.", eid, node.id());
+        diag!(Warning, InvalidAttribute, node.id(),
+              "{} '{}' has an invalid 'points' value. Skipped.", eid, node.id());
         return None;
     };
 
     // 'polyline' and 'polygon' elements must contain at least 4 coordinates.
     if points.len() < 4 {
-        warn!("{} '{}' has less than 4 points. Skipped.", eid, node.id());
+        diag!(Warning, InvalidAttribute, node.id(),
+              "{} '{}' has less than 4 points. Skipped.", eid, node.id());
         return None;
     }
 
@@ -194,7 +198,8 @@ fn convert_circle(node: &svgdom::Node) -> Option<path::Path> {
     let r  = attrs.get_number(AId::R).unwrap_or(0.0);
 
     if !(r > 0.0) {
-        warn!("Circle '{}' has an invalid 'r' value. Skipped.", node.id());
+        diag!(Warning, InvalidAttribute, node.id(),
+              "Circle '{}' has an invalid 'r' value. Skipped.", node.id());
         return None;
     }
 
@@ -213,12 +218,14 @@ fn convert_ellipse(node: &svgdom::Node) -> Option<path::Path> {
     let ry = attrs.get_number(AId::Ry).unwrap_or(0.0);
 
     if !(rx > 0.0) {
-        warn!("Ellipse '{}' has an invalid 'rx' value. Skipped.", node.id());
+        diag!(Warning, InvalidAttribute, node.id(),
+              "Ellipse '{}' has an invalid 'rx' value. Skipped.", node.id());
         return None;
     }
 
     if !(ry > 0.0) {
-        warn!("Ellipse '{}' has an invalid 'ry' value. Skipped.", node.id());
+        diag!(Warning, InvalidAttribute, node.id(),
+              "Ellipse '{}' has an invalid 'ry' value. Skipped.", node.id());
         return None;
     }
 
@@ -26,9 +26,11 @@ use traits::{
 
 pub fn convert(
     defs: &[dom::RefElement],
-    attrs: &svgdom::Attributes,
+    node: &svgdom::Node,
 ) -> Option<dom::Stroke>
 {
+    let attrs = node.attributes();
+
     let dashoffset  = attrs.get_number(AId::StrokeDashoffset).unwrap_or(0.0);
     let miterlimit  = attrs.get_number(AId::StrokeMiterlimit).unwrap_or(4.0);
     let opacity     = attrs.get_number(AId::StrokeOpacity).unwrap_or(1.0);
@@ -50,7 +52,8 @@ pub fn convert(
                 match p {
                     Some(p) => p,
                     None => {
-                        warn!("Stroking with {:?} is not supported.", link.tag_name().unwrap());
+                        diag!(Warning, UnsupportedPaint, node.id(),
+                              "Stroking with {:?} is not supported.", link.tag_name().unwrap());
                         return None;
                     }
                 }
@@ -59,7 +62,8 @@ pub fn convert(
                 return None;
             }
             _ => {
-                warn!("An invalid stroke value: {}. Skipped.", stroke);
+                diag!(Warning, InvalidAttribute, node.id(),
+                      "An invalid stroke value: {}. Skipped.", stroke);
                 return None;
             }
         }
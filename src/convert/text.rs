@@ -52,9 +52,8 @@ fn convert_chunks(
     let mut chunks = Vec::new();
     let mut tspans = Vec::new();
 
-    let ref root_attrs = text_elem.attributes();
-    let mut prev_x = resolve_pos(root_attrs, AId::X).unwrap_or(0.0);
-    let mut prev_y = resolve_pos(root_attrs, AId::Y).unwrap_or(0.0);
+    let mut prev_x = resolve_pos(text_elem, AId::X).unwrap_or(0.0);
+    let mut prev_y = resolve_pos(text_elem, AId::Y).unwrap_or(0.0);
 
     let mut first_chunk = text_elem.clone();
 
@@ -69,8 +68,8 @@ fn convert_chunks(
 
 
         let ref attrs = tspan.attributes();
-        let x = resolve_pos(attrs, AId::X);
-        let y = resolve_pos(attrs, AId::Y);
+        let x = resolve_pos(&tspan, AId::X);
+        let y = resolve_pos(&tspan, AId::Y);
 
         if x.is_some() || y.is_some() {
             let tx = x.unwrap_or(0.0);
@@ -89,10 +88,10 @@ fn convert_chunks(
         }
 
         tspans.push(dom::TSpan {
-            fill: fill::convert(defs, attrs),
-            stroke: stroke::convert(defs, attrs),
+            fill: fill::convert(defs, &tspan),
+            stroke: stroke::convert(defs, &tspan),
             font: convert_font(attrs),
-            decoration: conv_tspan_decoration2(defs, text_elem, &tspan),
+            decoration: convert_decoration(defs, &tspan),
             text: text,
         });
     }
@@ -104,11 +103,12 @@ fn convert_chunks(
     Some(chunks)
 }
 
-fn resolve_pos(attrs: &svgdom::Attributes, aid: AId) -> Option<f64> {
-    if let Some(ref list) = attrs.get_number_list(aid) {
+fn resolve_pos(node: &svgdom::Node, aid: AId) -> Option<f64> {
+    if let Some(ref list) = node.attributes().get_number_list(aid) {
         if !list.is_empty() {
             if list.len() > 1 {
-                warn!("List of 'x', 'y' coordinates are not supported in a 'text' element.");
+                diag!(Warning, UnsupportedAttribute, node.id(),
+                      "List of 'x', 'y' coordinates are not supported in a 'text' element.");
             }
 
             return Some(list[0]);
@@ -134,84 +134,29 @@ fn create_text_chunk(
     }
 }
 
-struct TextDecoTypes {
-    has_underline: bool,
-    has_overline: bool,
-    has_line_through: bool,
-}
-
-// 'text-decoration' defined in the 'text' element
-// should be generated by 'prepare_text_decoration'.
-fn conv_text_decoration(node: &svgdom::Node) -> TextDecoTypes {
-    debug_assert!(node.is_tag_name(EId::Text));
-
-    let attrs = node.attributes();
-
-    let def = String::new();
-    let text = attrs.get_string(AId::TextDecoration).unwrap_or(&def);
-
-    TextDecoTypes {
-        has_underline: text.contains("underline"),
-        has_overline: text.contains("overline"),
-        has_line_through: text.contains("linethrough"),
-    }
-}
-
-// 'text-decoration' in 'tspan' does not depend on parent elements.
-fn conv_tspan_decoration(tspan: &svgdom::Node) -> TextDecoTypes {
-    debug_assert!(tspan.is_tag_name(EId::Tspan));
+// Decorations are stored in empty 'tspan' children by 'prepare_text_nodes'.
+fn convert_decoration(defs: &[dom::RefElement], tspan: &svgdom::Node) -> dom::TextDecoration {
+    let mut decoration = dom::TextDecoration {
+        underline: None,
+        overline: None,
+        line_through: None,
+    };
 
-    let attrs = tspan.attributes();
+    for (_, node) in tspan.children().svg() {
+        let style = Some(dom::TextDecorationStyle {
+            fill: fill::convert(defs, &node),
+            stroke: stroke::convert(defs, &node),
+        });
 
-    let has_attr = |decoration_id: svgdom::ValueId| {
-        if let Some(id) = attrs.get_predef(AId::TextDecoration) {
-            if id == decoration_id {
-                return true;
-            }
+        match node.attributes().get_predef(AId::TextDecoration) {
+            Some(svgdom::ValueId::Underline) => decoration.underline = style,
+            Some(svgdom::ValueId::Overline) => decoration.overline = style,
+            Some(svgdom::ValueId::LineThrough) => decoration.line_through = style,
+            _ => {}
         }
-
-        false
-    };
-
-    TextDecoTypes {
-        has_underline: has_attr(svgdom::ValueId::Underline),
-        has_overline: has_attr(svgdom::ValueId::Overline),
-        has_line_through: has_attr(svgdom::ValueId::LineThrough),
     }
-}
-
-fn conv_tspan_decoration2(
-    defs: &[dom::RefElement],
-    node: &svgdom::Node,
-    tspan: &svgdom::Node
-) -> dom::TextDecoration {
-    let text_dec = conv_text_decoration(node);
-    let tspan_dec = conv_tspan_decoration(tspan);
-
-    let gen_style = |in_tspan: bool, in_text: bool| {
-        let n = if in_tspan {
-            tspan.clone()
-        } else if in_text {
-            node.clone()
-        } else {
-            return None;
-        };
-
-        let ref attrs = n.attributes();
-        let fill = fill::convert(defs, attrs);
-        let stroke = stroke::convert(defs, attrs);
-
-        Some(dom::TextDecorationStyle {
-            fill,
-            stroke,
-        })
-    };
 
-    dom::TextDecoration {
-        underline: gen_style(tspan_dec.has_underline, text_dec.has_underline),
-        overline: gen_style(tspan_dec.has_overline, text_dec.has_overline),
-        line_through: gen_style(tspan_dec.has_line_through, text_dec.has_line_through),
-    }
+    decoration
 }
 
 fn conv_text_anchor(attrs: &svgdom::Attributes) -> dom::TextAnchor {
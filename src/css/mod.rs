@@ -0,0 +1,21 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Style sheets parsing.
+//!
+//! Style sheets are resolved by the `preproc::resolve_css` pass.
+
+mod stylesheet;
+
+pub use self::stylesheet::{
+    parse_declarations,
+    AttributeOperator,
+    Combinator,
+    Compound,
+    Declaration,
+    Selector,
+    Specificity,
+    StyleSheet,
+    SubSelector,
+};
@@ -0,0 +1,636 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A minimal CSS parser.
+//!
+//! Rules are tokenized by `simplecss`. Supports only the subset of CSS 2.1 that is used
+//! by SVG editors: type, universal, class, ID and attribute selectors, all combinators,
+//! the `:first-child` pseudo-class and `!important` declarations.
+//! At-rules are skipped.
+
+use std::mem;
+
+use simplecss::{
+    self,
+    Token,
+};
+
+
+/// A CSS declaration.
+#[derive(Clone, Copy, Debug)]
+pub struct Declaration<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+    pub important: bool,
+}
+
+/// A selector specificity.
+///
+/// Contains the number of ID selectors, the number of class, attribute and pseudo-class
+/// selectors and the number of type selectors.
+pub type Specificity = [u32; 3];
+
+/// A CSS rule with a single selector.
+///
+/// A rule with a selectors list is split into multiple rules with the same declarations.
+pub struct Rule<'a> {
+    pub selector: Selector<'a>,
+    pub specificity: Specificity,
+    /// Index of the declarations block in `StyleSheet::blocks`.
+    pub block: usize,
+}
+
+/// A parsed style sheet.
+pub struct StyleSheet<'a> {
+    /// Rules in the source order.
+    pub rules: Vec<Rule<'a>>,
+    pub blocks: Vec<Vec<Declaration<'a>>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Combinator {
+    /// `a b`
+    Descendant,
+    /// `a > b`
+    Child,
+    /// `a + b`
+    NextSibling,
+    /// `a ~ b`
+    SubsequentSibling,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AttributeOperator<'a> {
+    /// `[attr]`
+    Exists,
+    /// `[attr=value]`
+    Matches(&'a str),
+    /// `[attr~=value]`
+    Contains(&'a str),
+    /// `[attr|=value]`
+    StartsWithDash(&'a str),
+    /// `[attr^=value]`
+    StartsWith(&'a str),
+    /// `[attr$=value]`
+    EndsWith(&'a str),
+    /// `[attr*=value]`
+    Substring(&'a str),
+}
+
+impl<'a> AttributeOperator<'a> {
+    /// Checks that the attribute value matches the operator.
+    pub fn matches(&self, value: &str) -> bool {
+        match *self {
+            AttributeOperator::Exists => true,
+            AttributeOperator::Matches(v) => value == v,
+            AttributeOperator::Contains(v) => value.split_whitespace().any(|s| s == v),
+            AttributeOperator::StartsWithDash(v) => {
+                value == v || (value.starts_with(v) && value[v.len()..].starts_with('-'))
+            }
+            AttributeOperator::StartsWith(v) => !v.is_empty() && value.starts_with(v),
+            AttributeOperator::EndsWith(v) => !v.is_empty() && value.ends_with(v),
+            AttributeOperator::Substring(v) => !v.is_empty() && value.contains(v),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SubSelector<'a> {
+    Id(&'a str),
+    Class(&'a str),
+    Attribute(&'a str, AttributeOperator<'a>),
+    FirstChild,
+}
+
+/// A compound selector, like `rect.cls#id`.
+#[derive(Clone, Debug)]
+pub struct Compound<'a> {
+    /// Type selector. `None` for the universal one.
+    pub tag_name: Option<&'a str>,
+    pub sub_selectors: Vec<SubSelector<'a>>,
+}
+
+/// A complex selector.
+#[derive(Clone, Debug)]
+pub struct Selector<'a> {
+    /// Compound selectors with a combinator to the previous one.
+    ///
+    /// The combinator of the first compound selector is ignored.
+    pub components: Vec<(Combinator, Compound<'a>)>,
+}
+
+impl<'a> Selector<'a> {
+    fn specificity(&self) -> Specificity {
+        let mut spec = [0; 3];
+        for compound in self.components.iter().map(|c| &c.1) {
+            if compound.tag_name.is_some() {
+                spec[2] += 1;
+            }
+
+            for sub in &compound.sub_selectors {
+                match *sub {
+                    SubSelector::Id(_) => spec[0] += 1,
+                    _ => spec[1] += 1,
+                }
+            }
+        }
+
+        spec
+    }
+}
+
+impl<'a> StyleSheet<'a> {
+    /// Parses a style sheet.
+    ///
+    /// Invalid and unsupported rules are skipped and reported via `warn`.
+    pub fn parse<F>(text: &'a str, mut warn: F) -> StyleSheet<'a>
+        where F: FnMut(&str)
+    {
+        let mut sheet = StyleSheet {
+            rules: Vec::new(),
+            blocks: Vec::new(),
+        };
+
+        let mut s = Stream::new(text);
+        loop {
+            s.skip_spaces();
+            if s.at_end() {
+                break;
+            }
+
+            let start = s.pos;
+            if s.curr() == b'@' {
+                s.skip_at_rule();
+                warn(&format!("At-rule '{}' is not supported", at_rule_name(&text[start..s.pos])));
+                continue;
+            }
+
+            s.skip_until_block();
+            if s.at_end() {
+                warn(&format!("CSS rule '{}' is not closed", text[start..].trim()));
+                break;
+            }
+            s.skip_block();
+
+            // `simplecss` doesn't stop at the end of a block, so each rule is tokenized separately.
+            match parse_rule(text, start, s.pos, &mut warn) {
+                Some((selectors, declarations)) => {
+                    if !selectors.is_empty() {
+                        sheet.blocks.push(declarations);
+                        let block = sheet.blocks.len() - 1;
+                        for selector in selectors {
+                            let specificity = selector.specificity();
+                            sheet.rules.push(Rule { selector, specificity, block });
+                        }
+                    }
+                }
+                None => warn(&format!("Invalid CSS rule '{}'", text[start..s.pos].trim())),
+            }
+        }
+
+        sheet
+    }
+}
+
+/// Parses a single rule.
+///
+/// Returns `None` on a syntax error. Unsupported selectors are skipped and reported via `warn`.
+fn parse_rule<'a, F>(
+    text: &'a str,
+    start: usize,
+    end: usize,
+    warn: &mut F,
+) -> Option<(Vec<Selector<'a>>, Vec<Declaration<'a>>)>
+    where F: FnMut(&str)
+{
+    let mut selectors = Vec::new();
+    let mut builder = SelectorBuilder::new(start);
+    let mut tokenizer = simplecss::Tokenizer::new_bound(text, start, end);
+
+    // Selectors.
+    loop {
+        let pos = tokenizer.pos();
+        let token = match tokenizer.parse_next() {
+            Ok(token) => token,
+            Err(_) => {
+                // `simplecss` doesn't support the subsequent-sibling combinator,
+                // so the tokenizing is continued after it.
+                let pos = tokenizer.pos();
+                if text.as_bytes().get(pos) != Some(&b'~') {
+                    return None;
+                }
+
+                builder.set_combinator(Combinator::SubsequentSibling);
+                tokenizer = simplecss::Tokenizer::new_bound(text, pos + 1, end);
+                continue;
+            }
+        };
+
+        // `simplecss` skips spaces around some selectors without reporting a combinator.
+        let is_space = |c: char| c.is_ascii_whitespace();
+        if text[..pos].ends_with(is_space) || text[pos..].starts_with(is_space) {
+            builder.set_descendant_combinator();
+        }
+
+        match token {
+            Token::UniversalSelector => builder.push_universal(),
+            Token::TypeSelector(name) => builder.push_type(name),
+            Token::IdSelector(id) => builder.push(Some(SubSelector::Id(id))),
+            Token::ClassSelector(class) => builder.push(Some(SubSelector::Class(class))),
+            Token::AttributeSelector(attr) => builder.push(parse_attribute_selector(attr)),
+            // Other pseudo-classes are never matched during rendering.
+            Token::PseudoClass("first-child") => builder.push(Some(SubSelector::FirstChild)),
+            Token::PseudoClass(_) | Token::LangPseudoClass(_) => builder.push(None),
+            Token::Combinator(simplecss::Combinator::Space) => builder.set_descendant_combinator(),
+            Token::Combinator(simplecss::Combinator::GreaterThan) => {
+                builder.set_combinator(Combinator::Child);
+            }
+            Token::Combinator(simplecss::Combinator::Plus) => {
+                builder.set_combinator(Combinator::NextSibling);
+            }
+            Token::Comma | Token::BlockStart => {
+                let sel_text = text[builder.start..pos].trim();
+                match builder.finish() {
+                    Some(selector) => selectors.push(selector),
+                    None => warn(&format!("Unsupported CSS selector '{}'", sel_text)),
+                }
+
+                if token == Token::BlockStart {
+                    break;
+                }
+
+                builder = SelectorBuilder::new(tokenizer.pos());
+            }
+            _ => return None,
+        }
+    }
+
+    // Declarations.
+    let mut declarations = Vec::new();
+    loop {
+        match tokenizer.parse_next() {
+            Ok(Token::Declaration(name, value)) => {
+                let (value, important) = split_important(value);
+                if !value.is_empty() {
+                    declarations.push(Declaration { name, value, important });
+                }
+            }
+            Ok(Token::BlockEnd) => break,
+            _ => return None,
+        }
+    }
+
+    Some((selectors, declarations))
+}
+
+/// Collects selector tokens into a `Selector`.
+struct SelectorBuilder<'a> {
+    /// The selector position in the text.
+    start: usize,
+    components: Vec<(Combinator, Compound<'a>)>,
+    /// The combinator before the current compound selector.
+    prev_combinator: Combinator,
+    compound: Compound<'a>,
+    universal: bool,
+    /// The combinator after the current compound selector.
+    combinator: Option<Combinator>,
+    is_valid: bool,
+}
+
+impl<'a> SelectorBuilder<'a> {
+    fn new(start: usize) -> Self {
+        SelectorBuilder {
+            start,
+            components: Vec::new(),
+            prev_combinator: Combinator::Descendant,
+            compound: Compound { tag_name: None, sub_selectors: Vec::new() },
+            universal: false,
+            combinator: None,
+            is_valid: true,
+        }
+    }
+
+    fn is_compound_empty(&self) -> bool {
+        self.compound.tag_name.is_none() && self.compound.sub_selectors.is_empty() && !self.universal
+    }
+
+    fn set_combinator(&mut self, combinator: Combinator) {
+        // Two combinators in a row or a combinator at the start.
+        let after_combinator = self.combinator.is_some() && self.combinator != Some(Combinator::Descendant);
+        if self.is_compound_empty() || after_combinator {
+            self.is_valid = false;
+        }
+
+        // Overrides a descendant combinator, since spaces are allowed around other combinators.
+        self.combinator = Some(combinator);
+    }
+
+    fn set_descendant_combinator(&mut self) {
+        if self.combinator.is_none() && !self.is_compound_empty() {
+            self.combinator = Some(Combinator::Descendant);
+        }
+    }
+
+    /// Starts a new compound selector if there was a combinator.
+    fn start_compound(&mut self) {
+        if let Some(combinator) = self.combinator.take() {
+            let compound = Compound { tag_name: None, sub_selectors: Vec::new() };
+            let prev = mem::replace(&mut self.compound, compound);
+            self.components.push((self.prev_combinator, prev));
+            self.prev_combinator = combinator;
+            self.universal = false;
+        }
+    }
+
+    fn push_universal(&mut self) {
+        self.start_compound();
+        if !self.is_compound_empty() {
+            self.is_valid = false;
+        }
+
+        self.universal = true;
+    }
+
+    fn push_type(&mut self, name: &'a str) {
+        self.start_compound();
+        if !self.is_compound_empty() {
+            self.is_valid = false;
+        }
+
+        self.compound.tag_name = Some(name);
+    }
+
+    /// Adds a sub-selector. `None` indicates an unsupported one.
+    fn push(&mut self, sub: Option<SubSelector<'a>>) {
+        self.start_compound();
+        match sub {
+            Some(sub) => self.compound.sub_selectors.push(sub),
+            None => self.is_valid = false,
+        }
+    }
+
+    fn finish(mut self) -> Option<Selector<'a>> {
+        // A trailing descendant combinator is just a space.
+        if self.combinator == Some(Combinator::Descendant) {
+            self.combinator = None;
+        }
+
+        if !self.is_valid || self.combinator.is_some() || self.is_compound_empty() {
+            return None;
+        }
+
+        self.components.push((self.prev_combinator, self.compound));
+        Some(Selector { components: self.components })
+    }
+}
+
+/// Parses an attribute selector content, like `attr="value"`.
+fn parse_attribute_selector(text: &str) -> Option<SubSelector<'_>> {
+    let text = text.trim();
+    let name_end = text.bytes().position(|c| !is_ident_char(c)).unwrap_or(text.len());
+    let name = &text[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+
+    let rest = text[name_end..].trim_start();
+    if rest.is_empty() {
+        return Some(SubSelector::Attribute(name, AttributeOperator::Exists));
+    }
+
+    // `=` or a prefixed one, like `~=`.
+    let op_len = match rest.find('=') {
+        Some(idx) if idx < 2 => idx + 1,
+        _ => return None,
+    };
+    let op = rest.as_bytes()[0];
+
+    let value = rest[op_len..].trim();
+    let value = match value.as_bytes().first() {
+        Some(&quote) if quote == b'"' || quote == b'\'' => {
+            if value.len() < 2 || !value.ends_with(quote as char) {
+                return None;
+            }
+
+            &value[1..value.len() - 1]
+        }
+        Some(_) if value.bytes().all(is_ident_char) => value,
+        _ => return None,
+    };
+
+    let op = match op {
+        b'=' => AttributeOperator::Matches(value),
+        b'~' => AttributeOperator::Contains(value),
+        b'|' => AttributeOperator::StartsWithDash(value),
+        b'^' => AttributeOperator::StartsWith(value),
+        b'$' => AttributeOperator::EndsWith(value),
+        b'*' => AttributeOperator::Substring(value),
+        _ => return None,
+    };
+
+    Some(SubSelector::Attribute(name, op))
+}
+
+/// Parses a declarations list, like a `style` attribute value.
+///
+/// Invalid declarations are ignored.
+pub fn parse_declarations<'a>(text: &'a str) -> Vec<Declaration<'a>> {
+    let mut list = Vec::new();
+
+    let mut s = Stream::new(text);
+    loop {
+        s.skip_spaces();
+        if s.at_end() {
+            break;
+        }
+
+        let start = s.pos;
+        s.skip_value(b';');
+        let decl = &text[start..s.pos];
+        if !s.at_end() {
+            s.advance(1); // ; or }
+        }
+
+        let (name, value) = match decl.find(':') {
+            Some(idx) => (decl[..idx].trim(), decl[idx + 1..].trim()),
+            None => continue,
+        };
+
+        let (value, important) = split_important(value);
+        if name.is_empty() || value.is_empty() {
+            continue;
+        }
+
+        list.push(Declaration { name, value, important });
+    }
+
+    list
+}
+
+fn split_important(value: &str) -> (&str, bool) {
+    if let Some(idx) = value.rfind('!') {
+        if value[idx + 1..].trim().eq_ignore_ascii_case("important") {
+            return (value[..idx].trim(), true);
+        }
+    }
+
+    (value, false)
+}
+
+fn at_rule_name(text: &str) -> &str {
+    let end = text.find(|c: char| c.is_whitespace() || c == '{' || c == ';').unwrap_or(text.len());
+    &text[..end]
+}
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'-' || c == b'_' || c >= 0x80
+}
+
+/// Splits a style sheet into rules and declarations.
+struct Stream<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Stream<'a> {
+    fn new(text: &'a str) -> Self {
+        Stream { text, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn curr(&self) -> u8 {
+        self.text.as_bytes()[self.pos]
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.pos += n;
+    }
+
+    fn starts_with(&self, s: &[u8]) -> bool {
+        self.text.as_bytes()[self.pos..].starts_with(s)
+    }
+
+    /// Skips spaces and comments.
+    fn skip_spaces(&mut self) {
+        while !self.at_end() {
+            if self.curr().is_ascii_whitespace() {
+                self.advance(1);
+            } else if self.starts_with(b"/*") {
+                self.skip_comment();
+            } else if self.starts_with(b"<!--") {
+                // HTML comments are allowed around a style sheet.
+                self.advance(4);
+            } else if self.starts_with(b"-->") {
+                self.advance(3);
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        match self.text[self.pos + 2..].find("*/") {
+            Some(idx) => self.pos += idx + 4,
+            None => self.pos = self.text.len(),
+        }
+    }
+
+    /// Skips a value until the `end` character.
+    ///
+    /// Strings, parentheses and comments are skipped as a whole.
+    /// Stops before `}` too.
+    fn skip_value(&mut self, end: u8) {
+        while !self.at_end() {
+            match self.curr() {
+                c if c == end => break,
+                b'}' => break,
+                b'"' | b'\'' => self.skip_string(),
+                b'(' => {
+                    self.advance(1);
+                    self.skip_value(b')');
+                    if !self.at_end() && self.curr() == b')' {
+                        self.advance(1);
+                    }
+                }
+                b'/' if self.starts_with(b"/*") => self.skip_comment(),
+                _ => self.advance(1),
+            }
+        }
+    }
+
+    /// Skips a selectors list until the `{`.
+    fn skip_until_block(&mut self) {
+        while !self.at_end() && self.curr() != b'{' {
+            if self.starts_with(b"/*") {
+                self.skip_comment();
+            } else {
+                self.advance(1);
+            }
+        }
+    }
+
+    /// Skips a `{}` block, including nested ones.
+    fn skip_block(&mut self) {
+        self.advance(1); // {
+
+        let mut depth = 1;
+        while !self.at_end() {
+            match self.curr() {
+                b'{' => depth += 1,
+                b'}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.advance(1);
+                        return;
+                    }
+                }
+                b'"' | b'\'' => {
+                    self.skip_string();
+                    continue;
+                }
+                b'/' if self.starts_with(b"/*") => {
+                    self.skip_comment();
+                    continue;
+                }
+                _ => {}
+            }
+
+            self.advance(1);
+        }
+    }
+
+    /// Skips a quoted string.
+    fn skip_string(&mut self) {
+        let quote = self.curr();
+        self.advance(1);
+        while !self.at_end() && self.curr() != quote {
+            self.advance(1);
+        }
+
+        if !self.at_end() {
+            self.advance(1);
+        }
+    }
+
+    /// Skips an at-rule, which ends with `;` or a `{}` block.
+    fn skip_at_rule(&mut self) {
+        while !self.at_end() {
+            match self.curr() {
+                b';' => {
+                    self.advance(1);
+                    return;
+                }
+                b'{' => {
+                    self.skip_block();
+                    return;
+                }
+                b'"' | b'\'' => self.skip_string(),
+                _ => self.advance(1),
+            }
+        }
+    }
+}
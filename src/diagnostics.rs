@@ -0,0 +1,152 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::cell::RefCell;
+
+
+/// Reports a diagnostic and passes it to the `log` crate when allowed.
+///
+/// The message is logged from the call site, so the log record points to the actual module.
+macro_rules! diag {
+    (@log Info, $msg:expr) => (info!("{}", $msg));
+    (@log Warning, $msg:expr) => (warn!("{}", $msg));
+    ($severity:ident, $code:ident, $id:expr, $($arg:tt)+) => ({
+        let msg = format!($($arg)+);
+        if $crate::diagnostics::report($crate::diagnostics::Severity::$severity,
+                                       $crate::diagnostics::DiagnosticCode::$code,
+                                       &$id, &msg) {
+            diag!(@log $severity, msg);
+        }
+    });
+}
+
+
+/// A diagnostic severity.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    /// Something was replaced with a fallback value.
+    Info,
+    /// Something was skipped, so the image may look wrong.
+    Warning,
+}
+
+/// A diagnostic code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiagnosticCode {
+    /// An unsupported element was skipped.
+    UnsupportedElement,
+    /// An unsupported attribute value was ignored.
+    UnsupportedAttribute,
+    /// A fill or stroke references an unsupported element, like a `pattern`.
+    UnsupportedPaint,
+    /// An element has an invalid attribute value.
+    InvalidAttribute,
+    /// An element lacks a required attribute.
+    MissingAttribute,
+    /// A gradient has less than 2 stops.
+    InvalidGradient,
+    /// An image has an invalid or unsupported content.
+    InvalidImage,
+    /// An external image can't be loaded.
+    ImageLoadFailed,
+    /// An external resource is blocked by `Options::policy`.
+    AccessDenied,
+    /// An SVG image references itself or is nested too deeply.
+    RecursiveImage,
+    /// A default font is used.
+    FontFallback,
+    /// A style sheet rule is invalid or unsupported.
+    InvalidCss,
+    /// A backend failed to allocate a group layer, so the group was not rendered.
+    LayerFailed,
+}
+
+/// A parsing diagnostic.
+///
+/// Describes why the document differs from the original SVG.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Diagnostic code.
+    pub code: DiagnosticCode,
+    /// Diagnostic severity.
+    pub severity: Severity,
+    /// ID of the affected element.
+    ///
+    /// Can be empty.
+    pub element_id: String,
+    /// A human-readable message.
+    pub message: String,
+}
+
+struct Sink {
+    log: bool,
+    list: Option<Vec<Diagnostic>>,
+}
+
+thread_local! {
+    // Diagnostics are reported from all over the preprocessor and the converter,
+    // so the current sink is stored per thread instead of being passed everywhere.
+    static SINK: RefCell<Option<Sink>> = const { RefCell::new(None) };
+}
+
+// Restores the previous sink even on panic.
+struct SinkGuard {
+    prev: Option<Option<Sink>>,
+}
+
+impl Drop for SinkGuard {
+    fn drop(&mut self) {
+        if let Some(prev) = self.prev.take() {
+            SINK.with(|sink| *sink.borrow_mut() = prev);
+        }
+    }
+}
+
+/// Runs `f` with a new diagnostics sink.
+///
+/// Diagnostics are collected only when `collect` is set
+/// and are passed to the `log` crate only when `log` is set.
+pub fn with_sink<T, F>(log: bool, collect: bool, f: F) -> (T, Vec<Diagnostic>)
+    where F: FnOnce() -> T
+{
+    let new = Sink {
+        log,
+        list: if collect { Some(Vec::new()) } else { None },
+    };
+
+    let prev = SINK.with(|sink| sink.replace(Some(new)));
+    let mut guard = SinkGuard { prev: Some(prev) };
+
+    let res = f();
+
+    let prev = guard.prev.take().unwrap();
+    let sink = SINK.with(|sink| sink.replace(prev));
+    let list = sink.and_then(|s| s.list).unwrap_or_default();
+
+    (res, list)
+}
+
+/// Reports a diagnostic to the current sink.
+///
+/// Returns `true` when the diagnostic should be logged.
+/// Everything is logged when there is no sink.
+pub fn report(severity: Severity, code: DiagnosticCode, element_id: &str, message: &str) -> bool {
+    SINK.with(|sink| {
+        match *sink.borrow_mut() {
+            Some(ref mut sink) => {
+                if let Some(ref mut list) = sink.list {
+                    list.push(Diagnostic {
+                        code,
+                        severity,
+                        element_id: element_id.to_string(),
+                        message: message.to_string(),
+                    });
+                }
+
+                sink.log
+            }
+            None => true,
+        }
+    })
+}
@@ -8,73 +8,120 @@ use svgdom::types::{
 };
 
 
+/// A line cap.
+///
+/// `stroke-linecap` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum LineCap {
     Butt,
     Round,
     Square,
 }
 
+/// A line join.
+///
+/// `stroke-linejoin` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum LineJoin {
     Miter,
     Round,
     Bevel,
 }
 
+/// A fill rule.
+///
+/// `fill-rule` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum FillRule {
     NonZero,
     EvenOdd,
 }
 
+/// A gradient units.
+///
+/// `gradientUnits` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum GradientUnits {
     UserSpaceOnUse,
     ObjectBoundingBox,
 }
 
+/// A spread method.
+///
+/// `spreadMethod` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum SpreadMethod {
     Pad,
     Reflect,
     Repeat,
 }
 
+/// A text decoration style.
+///
+/// Defines the style of the line.
 #[derive(Clone)]
 pub struct TextDecorationStyle {
+    /// Line fill style.
     pub fill: Option<Fill>,
+    /// Line stroke style.
     pub stroke: Option<Stroke>,
 }
 
+/// A text decoration.
+///
+/// `text-decoration` attribute in SVG.
 #[derive(Clone)]
 pub struct TextDecoration {
+    /// Draw underline using specified style.
     pub underline: Option<TextDecorationStyle>,
+    /// Draw overline using specified style.
     pub overline: Option<TextDecorationStyle>,
+    /// Draw line-through using specified style.
     pub line_through: Option<TextDecorationStyle>,
 }
 
+/// A text anchor.
+///
+/// `text-anchor` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum TextAnchor {
     Start,
     Middle,
     End,
 }
 
+/// A font style.
+///
+/// `font-style` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum FontStyle {
     Normal,
     Italic,
     Oblique,
 }
 
+/// A font variant.
+///
+/// `font-variant` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum FontVariant {
     Normal,
     SmallCaps,
 }
 
+/// A font weight.
+///
+/// `font-weight` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum FontWeight {
     Normal,
     Bold,
@@ -91,7 +138,11 @@ pub enum FontWeight {
     W900,
 }
 
+/// A font stretch.
+///
+/// `font-stretch` attribute in SVG.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(missing_docs)]
 pub enum FontStretch {
     Normal,
     Wider,
@@ -106,42 +157,77 @@ pub enum FontStretch {
     UltraExpanded,
 }
 
+/// A paint style.
+///
+/// `paint` value type in SVG.
 #[derive(Copy,Clone)]
 pub enum Paint {
+    /// Paint with a color.
     Color(Color),
+    /// Paint using a referenced element.
+    ///
+    /// Contains an index of the element in `Document::defs`.
+    /// Use `Document::resolve_paint` to get the element itself.
     Link(usize),
 }
 
+/// A fill style.
 #[derive(Copy,Clone)]
 pub struct Fill {
+    /// `fill` in SVG.
     pub paint: Paint,
+    /// `fill-opacity` in SVG.
     pub opacity: f64,
+    /// `fill-rule` in SVG.
     pub rule: FillRule,
 }
 
+/// A stroke style.
 #[derive(Clone)]
 pub struct Stroke {
+    /// `stroke` in SVG.
     pub paint: Paint,
+    /// `stroke-dasharray` in SVG.
     pub dasharray: Option<NumberList>,
+    /// `stroke-dashoffset` in SVG.
     pub dashoffset: f64,
+    /// `stroke-miterlimit` in SVG.
     pub miterlimit: f64,
+    /// `stroke-opacity` in SVG.
     pub opacity: f64,
+    /// `stroke-width` in SVG.
     pub width: f64,
+    /// `stroke-linecap` in SVG.
     pub linecap: LineCap,
+    /// `stroke-linejoin` in SVG.
     pub linejoin: LineJoin,
 }
 
+/// A font.
 #[derive(Clone)]
 pub struct Font {
+    /// `font-family` in SVG.
     pub family: String,
+    /// `font-size` in SVG.
+    ///
+    /// Always resolved to an absolute value.
     pub size: f64,
+    /// `font-style` in SVG.
     pub style: FontStyle,
+    /// `font-variant` in SVG.
     pub variant: FontVariant,
+    /// `font-weight` in SVG.
     pub weight: FontWeight,
+    /// `font-stretch` in SVG.
     pub stretch: FontStretch,
 }
 
+/// A path segment.
+///
+/// Unlike the SVG path, contains only absolute
+/// move to, line to, curve to and close path segments.
 #[derive(Copy,Clone,Debug,PartialEq)]
+#[allow(missing_docs)]
 pub enum PathSegment {
     MoveTo {
         x: f64,
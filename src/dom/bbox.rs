@@ -0,0 +1,439 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::f64;
+
+use svgdom::types::{
+    Transform,
+};
+
+use math::{
+    Rect,
+};
+
+use super::{
+    Document,
+    Element,
+    LineCap,
+    LineJoin,
+    Path,
+    PathSegment,
+    Stroke,
+    Text,
+    Type,
+};
+
+
+/// A text metrics provider.
+///
+/// Text layout is done by backends, so only they can calculate a text bounding box.
+pub trait TextMetrics {
+    /// Returns a text bounding box in the text element coordinates.
+    ///
+    /// Stroke should be ignored.
+    fn text_bbox(&self, text: &Text) -> Option<Rect>;
+}
+
+impl Path {
+    /// Calculates a path bounding box without a stroke.
+    ///
+    /// Curves extrema are calculated exactly.
+    ///
+    /// Returns `None` for an empty path.
+    pub fn fill_bbox(&self) -> Option<Rect> {
+        calc_path_bbox(&self.d, &Transform::default())
+    }
+
+    /// Calculates a path bounding box including a stroke.
+    ///
+    /// Joins and caps are approximated, so the result can be bigger than the actual one.
+    ///
+    /// Returns `None` for an empty path.
+    pub fn stroke_bbox(&self) -> Option<Rect> {
+        calc_path_bbox_with_stroke(self, &Transform::default(), true)
+    }
+}
+
+impl Element {
+    /// Calculates an element bounding box without a stroke.
+    ///
+    /// The bounding box is in the parent element coordinates,
+    /// so the element transform is already applied.
+    ///
+    /// Text elements are ignored. Use `calc_bbox` with a backend `TextMetrics` instead.
+    pub fn bbox(&self) -> Option<Rect> {
+        self.calc_bbox(false, None)
+    }
+
+    /// Calculates an element bounding box including a stroke.
+    ///
+    /// Same as `bbox`, but includes a stroke.
+    pub fn stroke_bbox(&self) -> Option<Rect> {
+        self.calc_bbox(true, None)
+    }
+
+    /// Calculates an element bounding box.
+    ///
+    /// Text elements are ignored when `metrics` is not set.
+    pub fn calc_bbox(&self, with_stroke: bool, metrics: Option<&dyn TextMetrics>) -> Option<Rect> {
+        self.calc_transformed_bbox(&Transform::default(), with_stroke, metrics)
+    }
+
+    /// Calculates an element bounding box in the coordinates defined by `ts`.
+    ///
+    /// `ts` is applied after the element transform. Unlike transforming the result
+    /// of `calc_bbox`, the bounding box stays tight for rotated elements.
+    ///
+    /// Text elements are ignored when `metrics` is not set.
+    pub fn calc_transformed_bbox(
+        &self,
+        ts: &Transform,
+        with_stroke: bool,
+        metrics: Option<&dyn TextMetrics>,
+    ) -> Option<Rect> {
+        let ctx = Context {
+            with_stroke,
+            metrics,
+        };
+
+        calc_element_bbox(&ctx, self, ts)
+    }
+}
+
+impl Document {
+    /// Calculates a bounding box of all elements without a stroke.
+    ///
+    /// The bounding box is in the user coordinates, aka `viewBox`.
+    ///
+    /// Text elements are ignored. Use `calc_bbox` with a backend `TextMetrics` instead.
+    pub fn bbox(&self) -> Option<Rect> {
+        self.calc_bbox(false, None)
+    }
+
+    /// Calculates a bounding box of all elements including a stroke.
+    ///
+    /// Same as `bbox`, but includes a stroke.
+    pub fn stroke_bbox(&self) -> Option<Rect> {
+        self.calc_bbox(true, None)
+    }
+
+    /// Calculates a bounding box of all elements.
+    ///
+    /// Text elements are ignored when `metrics` is not set.
+    pub fn calc_bbox(&self, with_stroke: bool, metrics: Option<&dyn TextMetrics>) -> Option<Rect> {
+        let ctx = Context {
+            with_stroke,
+            metrics,
+        };
+
+        calc_elements_bbox(&ctx, &self.elements, &Transform::default())
+    }
+
+    /// Calculates an element bounding box in the user coordinates.
+    ///
+    /// Unlike `Element::calc_bbox`, all parents transforms are applied.
+    ///
+    /// The element must be a part of this document.
+    pub fn calc_element_bbox(
+        &self,
+        elem: &Element,
+        with_stroke: bool,
+        metrics: Option<&dyn TextMetrics>,
+    ) -> Option<Rect> {
+        let ctx = Context {
+            with_stroke,
+            metrics,
+        };
+
+        let parents: Vec<&Element> = self.parents(elem).collect();
+
+        let mut ts = Transform::default();
+        for parent in parents.iter().rev() {
+            ts.append(&parent.transform);
+        }
+
+        calc_element_bbox(&ctx, elem, &ts)
+    }
+}
+
+
+struct Context<'a> {
+    with_stroke: bool,
+    metrics: Option<&'a dyn TextMetrics>,
+}
+
+fn calc_elements_bbox(ctx: &Context, elements: &[Element], ts: &Transform) -> Option<Rect> {
+    let mut bbox: Option<Rect> = None;
+
+    for elem in elements {
+        if let Some(r) = calc_element_bbox(ctx, elem, ts) {
+            bbox = Some(match bbox {
+                Some(prev) => prev.union(&r),
+                None => r,
+            });
+        }
+    }
+
+    bbox
+}
+
+fn calc_element_bbox(ctx: &Context, elem: &Element, parent_ts: &Transform) -> Option<Rect> {
+    let mut ts = *parent_ts;
+    ts.append(&elem.transform);
+
+    match elem.data {
+        Type::Path(ref path) => {
+            calc_path_bbox_with_stroke(path, &ts, ctx.with_stroke)
+        }
+        Type::Text(ref text) => {
+            let metrics = ctx.metrics?;
+            let mut bbox = metrics.text_bbox(text)?;
+
+            if ctx.with_stroke {
+                let width = text.children.iter()
+                    .flat_map(|chunk| chunk.children.iter())
+                    .filter_map(|tspan| tspan.stroke.as_ref())
+                    .fold(0.0, |w, s| f64::max(w, s.width));
+
+                bbox = bbox.expand(width / 2.0);
+            }
+
+            Some(bbox.transform(&ts))
+        }
+        Type::Image(ref img) => {
+            Some(img.rect.transform(&ts))
+        }
+        Type::Group(ref g) => {
+            calc_elements_bbox(ctx, &g.children, &ts)
+        }
+    }
+}
+
+fn calc_path_bbox_with_stroke(path: &Path, ts: &Transform, with_stroke: bool) -> Option<Rect> {
+    let bbox = calc_path_bbox(&path.d, ts)?;
+
+    match path.stroke {
+        Some(ref stroke) if with_stroke => {
+            // The stroke is expanded in the path coordinates,
+            // so we have to use the biggest scale factor.
+            let sx = (ts.a * ts.a + ts.b * ts.b).sqrt();
+            let sy = (ts.c * ts.c + ts.d * ts.d).sqrt();
+
+            Some(bbox.expand(stroke_extent(stroke) * sx.max(sy)))
+        }
+        _ => Some(bbox),
+    }
+}
+
+/// Returns the maximum distance between the stroke outline and the path.
+fn stroke_extent(stroke: &Stroke) -> f64 {
+    let mut factor: f64 = 1.0;
+
+    if stroke.linecap == LineCap::Square {
+        factor = factor.max(f64::consts::SQRT_2);
+    }
+
+    if stroke.linejoin == LineJoin::Miter {
+        factor = factor.max(stroke.miterlimit);
+    }
+
+    stroke.width / 2.0 * factor
+}
+
+/// Calculates a bounding box of the transformed path.
+///
+/// Curves extrema are calculated exactly.
+fn calc_path_bbox(d: &[PathSegment], ts: &Transform) -> Option<Rect> {
+    let mut minx = f64::MAX;
+    let mut miny = f64::MAX;
+    let mut maxx = f64::MIN;
+    let mut maxy = f64::MIN;
+
+    {
+        let mut add = |x: f64, y: f64| {
+            minx = minx.min(x);
+            miny = miny.min(y);
+            maxx = maxx.max(x);
+            maxy = maxy.max(y);
+        };
+
+        let mut prev_x = 0.0;
+        let mut prev_y = 0.0;
+        let mut start_x = 0.0;
+        let mut start_y = 0.0;
+        for seg in d {
+            match *seg {
+                PathSegment::MoveTo { x, y } => {
+                    let (x, y) = ts.apply(x, y);
+                    add(x, y);
+
+                    prev_x = x;
+                    prev_y = y;
+                    start_x = x;
+                    start_y = y;
+                }
+                PathSegment::LineTo { x, y } => {
+                    let (x, y) = ts.apply(x, y);
+                    add(x, y);
+
+                    prev_x = x;
+                    prev_y = y;
+                }
+                PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                    // Bezier curves are affine invariant,
+                    // so we can transform the control points directly.
+                    let (x1, y1) = ts.apply(x1, y1);
+                    let (x2, y2) = ts.apply(x2, y2);
+                    let (x, y) = ts.apply(x, y);
+
+                    add(x, y);
+
+                    for t in curve_extrema(prev_x, x1, x2, x).iter()
+                        .chain(curve_extrema(prev_y, y1, y2, y).iter())
+                        .filter_map(|t| *t)
+                    {
+                        add(curve_at(prev_x, x1, x2, x, t), curve_at(prev_y, y1, y2, y, t));
+                    }
+
+                    prev_x = x;
+                    prev_y = y;
+                }
+                PathSegment::ClosePath => {
+                    prev_x = start_x;
+                    prev_y = start_y;
+                }
+            }
+        }
+    }
+
+    if minx > maxx || miny > maxy {
+        return None;
+    }
+
+    Some(Rect::new(minx, miny, maxx - minx, maxy - miny))
+}
+
+/// Finds the `t` values of a cubic Bezier curve extrema on a single axis.
+///
+/// Only values in the (0, 1) range are returned.
+fn curve_extrema(p0: f64, p1: f64, p2: f64, p3: f64) -> [Option<f64>; 2] {
+    // The curve derivative divided by 3.
+    let a = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+    let b = 2.0 * (p0 - 2.0 * p1 + p2);
+    let c = p1 - p0;
+
+    let in_range = |t: f64| if t > 0.0 && t < 1.0 { Some(t) } else { None };
+
+    if a.abs() < f64::EPSILON {
+        if b.abs() < f64::EPSILON {
+            return [None, None];
+        }
+
+        return [in_range(-c / b), None];
+    }
+
+    let d = b * b - 4.0 * a * c;
+    if d < 0.0 {
+        return [None, None];
+    }
+
+    let d = d.sqrt();
+    [in_range((-b + d) / (2.0 * a)), in_range((-b - d) / (2.0 * a))]
+}
+
+pub fn curve_at(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+    let mt = 1.0 - t;
+    mt * mt * mt * p0 + 3.0 * mt * mt * t * p1 + 3.0 * mt * t * t * p2 + t * t * t * p3
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::Size;
+    use test_utils::parse;
+
+    fn path<'a>(doc: &'a Document, id: &str) -> &'a Path {
+        match doc.element_by_id(id).unwrap().data {
+            Type::Path(ref path) => path,
+            _ => panic!("a path expected"),
+        }
+    }
+
+    fn assert_rect(r: Option<Rect>, x: f64, y: f64, w: f64, h: f64) {
+        let r = r.unwrap();
+        let eq = |a: f64, b: f64| (a - b).abs() < 1e-6;
+        assert!(eq(r.x, x) && eq(r.y, y) && eq(r.w, w) && eq(r.h, h),
+                "{:?} != {:?}", r, Rect::new(x, y, w, h));
+    }
+
+    #[test]
+    fn extrema() {
+        // A symmetric curve with a maximum at t = 0.5.
+        assert_eq!(curve_extrema(0.0, 10.0, 10.0, 0.0), [Some(0.5), None]);
+        // A monotonic curve.
+        assert_eq!(curve_extrema(0.0, 1.0, 2.0, 3.0), [None, None]);
+        assert_eq!(curve_at(0.0, 10.0, 10.0, 0.0, 0.5), 7.5);
+    }
+
+    #[test]
+    fn curve_bbox() {
+        let doc = parse("width='100' height='100'", "<path id='p' d='M 0 0 C 0 10 10 10 10 0'/>");
+        assert_rect(path(&doc, "p").fill_bbox(), 0.0, 0.0, 10.0, 7.5);
+    }
+
+    #[test]
+    fn stroke_bbox() {
+        let doc = parse("width='100' height='100'", "\
+            <path id='p1' d='M 10 10 L 20 10' stroke='black' stroke-width='2' \
+                  stroke-linejoin='round'/>\
+            <path id='p2' d='M 10 10 L 20 10' stroke='black' stroke-width='2' \
+                  stroke-linejoin='round' stroke-linecap='square'/>\
+            <path id='p3' d='M 10 10 L 20 10' stroke='black' stroke-width='2' \
+                  stroke-miterlimit='3'/>");
+
+        assert_rect(path(&doc, "p1").stroke_bbox(), 9.0, 9.0, 12.0, 2.0);
+
+        let d = f64::consts::SQRT_2;
+        assert_rect(path(&doc, "p2").stroke_bbox(), 10.0 - d, 10.0 - d, 10.0 + 2.0 * d, 2.0 * d);
+
+        assert_rect(path(&doc, "p3").stroke_bbox(), 7.0, 7.0, 16.0, 6.0);
+    }
+
+    #[test]
+    fn group_transforms() {
+        let doc = parse("width='100' height='100'", "\
+            <g id='g' opacity='0.5' transform='translate(10 20)'>\
+                <rect id='r1' width='10' height='10' transform='rotate(45)'/>\
+                <rect id='r2' x='10' width='10' height='10'/>\
+            </g>");
+
+        let d = 10.0 * f64::consts::SQRT_2;
+        let r1 = doc.element_by_id("r1").unwrap();
+        assert_rect(r1.bbox(), -d / 2.0, 0.0, d, d);
+        assert_rect(doc.calc_element_bbox(r1, false, None), 10.0 - d / 2.0, 20.0, d, d);
+
+        let g = doc.element_by_id("g").unwrap();
+        assert_rect(g.bbox(), 10.0 - d / 2.0, 20.0, 10.0 + d / 2.0 + 10.0, d);
+        assert_rect(doc.bbox(), 10.0 - d / 2.0, 20.0, 10.0 + d / 2.0 + 10.0, d);
+    }
+
+    #[test]
+    fn transformed_bbox_stays_tight() {
+        let doc = parse("width='100' height='100'", "<rect id='r' width='10' height='10' transform='rotate(45)'/>");
+        let r = doc.element_by_id("r").unwrap();
+
+        // Rotating back gives the original rect and not a bbox of the rotated bbox.
+        let mut ts = Transform::default();
+        ts.rotate(-45.0);
+        assert_rect(r.calc_transformed_bbox(&ts, false, None), 0.0, 0.0, 10.0, 10.0);
+    }
+
+    #[test]
+    fn percent_size_from_content() {
+        let doc = parse("width='50%' height='100%'", "<rect x='20' y='10' width='200' height='100'/>");
+        assert_eq!(doc.size, Size::new(100.0, 100.0));
+        assert_rect(Some(doc.view_box), 20.0, 10.0, 200.0, 100.0);
+    }
+}
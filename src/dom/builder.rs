@@ -0,0 +1,275 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom::types::{
+    Transform,
+};
+
+use math::{
+    Rect,
+    Size,
+};
+
+use super::{
+    Document,
+    Element,
+    Group,
+    Image,
+    Paint,
+    Path,
+    RefElement,
+    Text,
+    Type,
+};
+
+
+/// A `Document` builder.
+///
+/// Allows to construct a render tree directly, without an SVG parsing.
+pub struct Builder {
+    doc: Document,
+    groups: Vec<Element>,
+}
+
+impl Builder {
+    /// Creates a new builder for an image with the specified size.
+    ///
+    /// The viewbox will be set to `0 0 width height`.
+    pub fn new(size: Size) -> Self {
+        Builder {
+            doc: Document {
+                size,
+                view_box: Rect::new(0.0, 0.0, size.w, size.h),
+                dpi: 96.0,
+                defs: Vec::new(),
+                elements: Vec::new(),
+            },
+            groups: Vec::new(),
+        }
+    }
+
+    /// Sets the document viewbox.
+    pub fn view_box(&mut self, view_box: Rect) -> &mut Self {
+        self.doc.view_box = view_box;
+        self
+    }
+
+    /// Sets the document DPI.
+    ///
+    /// Used for text rendering.
+    pub fn dpi(&mut self, dpi: f64) -> &mut Self {
+        self.doc.dpi = dpi;
+        self
+    }
+
+    /// Registers a referenced element.
+    ///
+    /// Returns a paint that links to this element.
+    ///
+    /// Returns `None` when the gradient contains less than 2 stops.
+    pub fn add_defs(&mut self, elem: RefElement) -> Option<Paint> {
+        self.doc.add_defs(elem)
+    }
+
+    /// Appends a path to the current group.
+    pub fn add_path(&mut self, path: Path) -> &mut Element {
+        self.add_element(Type::Path(path))
+    }
+
+    /// Appends a text to the current group.
+    pub fn add_text(&mut self, text: Text) -> &mut Element {
+        self.add_element(Type::Text(text))
+    }
+
+    /// Appends an image to the current group.
+    pub fn add_image(&mut self, image: Image) -> &mut Element {
+        self.add_element(Type::Image(image))
+    }
+
+    /// Starts a new group.
+    ///
+    /// All elements will be added to this group until `end_group` is called.
+    pub fn begin_group(&mut self, opacity: Option<f64>) -> &mut Element {
+        self.groups.push(new_element(Type::Group(Group {
+            opacity,
+            children: Vec::new(),
+        })));
+
+        // 'unwrap' is safe, because we just added an element.
+        self.groups.last_mut().unwrap()
+    }
+
+    /// Finishes the current group.
+    ///
+    /// Does nothing when there are no open groups.
+    pub fn end_group(&mut self) {
+        if let Some(g) = self.groups.pop() {
+            self.children().push(g);
+        }
+    }
+
+    /// Finishes all open groups and returns the document.
+    pub fn finish(mut self) -> Document {
+        while !self.groups.is_empty() {
+            self.end_group();
+        }
+
+        self.doc
+    }
+
+    fn add_element(&mut self, data: Type) -> &mut Element {
+        let children = self.children();
+        children.push(new_element(data));
+
+        // 'unwrap' is safe, because we just added an element.
+        children.last_mut().unwrap()
+    }
+
+    fn children(&mut self) -> &mut Vec<Element> {
+        match self.groups.last_mut() {
+            Some(&mut Element { data: Type::Group(ref mut g), .. }) => &mut g.children,
+            _ => &mut self.doc.elements,
+        }
+    }
+}
+
+fn new_element(data: Type) -> Element {
+    Element {
+        id: String::new(),
+        data,
+        transform: Transform::default(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use svgdom::types::Color;
+
+    use super::*;
+    use dom::{
+        BaseGradient,
+        Fill,
+        FillRule,
+        GradientUnits,
+        LinearGradient,
+        PathSegment,
+        RefType,
+        ResolvedPaint,
+        SpreadMethod,
+        Stop,
+    };
+
+    fn rect_path(fill: Option<Fill>) -> Path {
+        Path {
+            fill,
+            stroke: None,
+            d: vec![
+                PathSegment::MoveTo { x: 0.0, y: 0.0 },
+                PathSegment::LineTo { x: 10.0, y: 0.0 },
+                PathSegment::LineTo { x: 10.0, y: 10.0 },
+                PathSegment::ClosePath,
+            ],
+        }
+    }
+
+    fn color_fill(c: Color) -> Option<Fill> {
+        Some(Fill { paint: Paint::Color(c), opacity: 1.0, rule: FillRule::NonZero })
+    }
+
+    fn gradient(stops: usize) -> RefElement {
+        RefElement {
+            id: "lg".to_string(),
+            data: RefType::LinearGradient(LinearGradient {
+                x1: 0.0,
+                y1: 0.0,
+                x2: 1.0,
+                y2: 0.0,
+                d: BaseGradient {
+                    units: GradientUnits::ObjectBoundingBox,
+                    transform: Transform::default(),
+                    spread_method: SpreadMethod::Pad,
+                    stops: (0..stops)
+                        .map(|_| Stop { offset: 0.0, color: Color::new(0, 0, 0), opacity: 1.0 })
+                        .collect(),
+                },
+            }),
+        }
+    }
+
+    fn ids(doc: &Document) -> Vec<&str> {
+        doc.descendants().map(|e| e.id.as_str()).collect()
+    }
+
+    #[test]
+    fn nested_groups() {
+        let mut builder = Builder::new(Size::new(20.0, 20.0));
+        builder.add_path(rect_path(None)).id = "p1".to_string();
+        builder.begin_group(Some(0.5)).id = "g1".to_string();
+        builder.add_path(rect_path(None)).id = "p2".to_string();
+        builder.begin_group(None).id = "g2".to_string();
+        builder.add_path(rect_path(None)).id = "p3".to_string();
+        builder.end_group();
+        builder.add_path(rect_path(None)).id = "p4".to_string();
+        // 'g1' is closed by 'finish'.
+        let doc = builder.finish();
+
+        assert_eq!(ids(&doc), vec!["p1", "g1", "p2", "g2", "p3", "p4"]);
+        assert_eq!(doc.elements.len(), 2);
+        assert_eq!(doc.view_box, Rect::new(0.0, 0.0, 20.0, 20.0));
+    }
+
+    #[test]
+    fn gradient_link() {
+        let mut builder = Builder::new(Size::new(20.0, 20.0));
+        assert!(builder.add_defs(gradient(1)).is_none());
+
+        let paint = builder.add_defs(gradient(2)).unwrap();
+        let fill = Fill { paint, opacity: 1.0, rule: FillRule::NonZero };
+        builder.add_path(rect_path(Some(fill)));
+        let doc = builder.finish();
+
+        assert_eq!(doc.defs.len(), 1);
+        match doc.resolve_paint(paint) {
+            ResolvedPaint::LinearGradient(lg) => assert_eq!(lg.d.stops.len(), 2),
+            _ => panic!("a linear gradient expected"),
+        }
+    }
+
+    #[test]
+    fn mutation() {
+        let mut builder = Builder::new(Size::new(20.0, 20.0));
+        builder.begin_group(None).id = "g".to_string();
+        builder.add_path(rect_path(None)).id = "p1".to_string();
+        builder.add_path(rect_path(None)).id = "p2".to_string();
+        let mut doc = builder.finish();
+
+        let red = Color::new(255, 0, 0);
+        {
+            let p1 = doc.element_by_id_mut("p1").unwrap();
+            p1.set_fill(color_fill(red));
+            p1.set_transform(Transform::new(1.0, 0.0, 0.0, 1.0, 5.0, 5.0));
+        }
+
+        let p1 = doc.element_by_id("p1").unwrap();
+        assert_eq!(p1.transform, Transform::new(1.0, 0.0, 0.0, 1.0, 5.0, 5.0));
+        match p1.data {
+            Type::Path(ref path) => {
+                match path.fill.as_ref().map(|f| f.paint) {
+                    Some(Paint::Color(c)) => assert_eq!(c, red),
+                    _ => panic!("a color fill expected"),
+                }
+            }
+            _ => panic!("a path expected"),
+        }
+
+        let removed = doc.remove_element("p2").unwrap();
+        assert_eq!(removed.id, "p2");
+        assert_eq!(ids(&doc), vec!["g", "p1"]);
+
+        assert!(doc.remove_element("p2").is_none());
+        assert!(doc.remove_element("g").is_some());
+        assert!(doc.elements.is_empty());
+    }
+}
@@ -7,6 +7,7 @@ use svgdom;
 
 use svgdom::types::{
     FuzzyEq,
+    Transform,
 };
 
 use super::*;
@@ -17,76 +18,232 @@ use short::{
 };
 
 
-// TODO: xml:space
+/// SVG export options.
+///
+/// The default options produce a lossless output.
+#[derive(Default)]
+pub struct ExportOptions {
+    /// Number of decimal places for coordinates and lengths.
+    ///
+    /// Transform matrices are rounded only in the translate part,
+    /// because rounding of the scale part can produce a noticeable error.
+    ///
+    /// `None` keeps the full precision.
+    pub precision: Option<u8>,
+    /// Applies transforms to the paths coordinates where possible.
+    ///
+    /// Paths with a non-uniformly scaled stroke or with a gradient,
+    /// that can't be transformed, will keep the `transform` attribute.
+    /// Groups will be removed if they don't have an opacity.
+    pub flatten_transforms: bool,
+    /// Removes the `xmlns:resvg` namespace and the `resvg:version` attribute.
+    pub strip_resvg_namespace: bool,
+    /// Merges identical gradients.
+    pub dedup_gradients: bool,
+    /// Removes elements ids.
+    ///
+    /// Gradients will get autogenerated ids, since they are referenced by elements.
+    pub strip_ids: bool,
+}
+
+impl ExportOptions {
+    fn round(&self, n: f64) -> f64 {
+        let n = match self.precision {
+            Some(p) => {
+                let k = 10f64.powi(p as i32);
+                (n * k).round() / k
+            }
+            None => n,
+        };
+
+        fix_zero(n)
+    }
+
+    fn round_ts(&self, ts: &Transform) -> Transform {
+        Transform::new(fix_zero(ts.a), fix_zero(ts.b), fix_zero(ts.c), fix_zero(ts.d),
+                       self.round(ts.e), self.round(ts.f))
+    }
+}
+
+// svgdom rounds numbers to 11 decimal places,
+// so a tiny negative number will be written as '-0'.
+fn fix_zero(n: f64) -> f64 {
+    if n.abs() < 1e-11 { 0.0 } else { n }
+}
+
 
-pub fn conv_doc(doc: &Document) -> svgdom::Document {
+pub fn conv_doc(doc: &Document, opt: &ExportOptions) -> svgdom::Document {
     let mut new_doc = svgdom::Document::new();
 
     let mut svg = new_doc.create_element(EId::Svg);
     new_doc.append(&svg);
 
-    let view_box = format!("{} {} {} {}", doc.view_box.x, doc.view_box.y,
-                                          doc.view_box.w, doc.view_box.h);
+    let view_box = format!("{} {} {} {}", opt.round(doc.view_box.x), opt.round(doc.view_box.y),
+                                          opt.round(doc.view_box.w), opt.round(doc.view_box.h));
+
+    let mut used_defs = vec![false; doc.defs.len()];
+    let mut has_images = false;
+    collect_refs(&doc.elements, &mut used_defs, &mut has_images);
 
     svg.set_attribute((AId::Xmlns, "http://www.w3.org/2000/svg"));
-    if !doc.defs.is_empty() {
+    if has_images {
         svg.set_attribute((AId::XmlnsXlink, "http://www.w3.org/1999/xlink"));
     }
-    svg.set_attribute((AId::Width,  doc.size.w));
-    svg.set_attribute((AId::Height, doc.size.h));
+    svg.set_attribute((AId::Width,  opt.round(doc.size.w)));
+    svg.set_attribute((AId::Height, opt.round(doc.size.h)));
     svg.set_attribute((AId::ViewBox, view_box));
-    svg.set_attribute(("xmlns:resvg", "https://github.com/RazrFalcon/libresvg"));
-    svg.set_attribute(("resvg:version", env!("CARGO_PKG_VERSION")));
+    if !opt.strip_resvg_namespace {
+        svg.set_attribute(("xmlns:resvg", "https://github.com/RazrFalcon/libresvg"));
+        svg.set_attribute(("resvg:version", env!("CARGO_PKG_VERSION")));
+    }
 
-    let mut defs_list = Vec::new();
+    // Unused elements are removed during parsing, so we are not writing them at all.
+    let mut defs_list: Vec<Option<svgdom::Node>> = Vec::new();
 
-    if !doc.defs.is_empty() {
+    if used_defs.iter().any(|v| *v) {
         let mut defs = new_doc.create_element(EId::Defs);
         svg.append(&defs);
 
-        for e in &doc.defs {
+        let mut grad_count = 0;
+        for (idx, e) in doc.defs.iter().enumerate() {
+            if !used_defs[idx] {
+                defs_list.push(None);
+                continue;
+            }
+
+            if opt.dedup_gradients {
+                // Reference the first identical gradient instead.
+                let same = doc.defs[..idx].iter().enumerate().position(|(prev_idx, prev)| {
+                    used_defs[prev_idx] && is_same_gradient(prev, e)
+                });
+
+                if let Some(prev_idx) = same {
+                    let node = defs_list[prev_idx].clone();
+                    defs_list.push(node);
+                    continue;
+                }
+            }
+
+            grad_count += 1;
+            let id = if opt.strip_ids { format!("g{}", grad_count) } else { e.id.clone() };
+
             match e.data {
                 element::RefType::LinearGradient(ref lg) => {
                     let mut grad = new_doc.create_element(EId::LinearGradient);
                     defs.append(&grad);
-                    defs_list.push(grad.clone());
+                    defs_list.push(Some(grad.clone()));
 
-                    grad.set_id(e.id.clone());
+                    grad.set_id(id);
 
-                    grad.set_attribute((AId::X1, lg.x1));
-                    grad.set_attribute((AId::Y1, lg.y1));
-                    grad.set_attribute((AId::X2, lg.x2));
-                    grad.set_attribute((AId::Y2, lg.y2));
+                    grad.set_attribute((AId::X1, opt.round(lg.x1)));
+                    grad.set_attribute((AId::Y1, opt.round(lg.y1)));
+                    grad.set_attribute((AId::X2, opt.round(lg.x2)));
+                    grad.set_attribute((AId::Y2, opt.round(lg.y2)));
 
-                    conv_base_grad(&mut new_doc, &mut grad, &lg.d);
+                    conv_base_grad(&mut new_doc, &mut grad, &lg.d, opt);
                 }
                 element::RefType::RadialGradient(ref rg) => {
                     let mut grad = new_doc.create_element(EId::RadialGradient);
                     defs.append(&grad);
-                    defs_list.push(grad.clone());
+                    defs_list.push(Some(grad.clone()));
 
-                    grad.set_id(e.id.clone());
+                    grad.set_id(id);
 
-                    grad.set_attribute((AId::Cx, rg.cx));
-                    grad.set_attribute((AId::Cy, rg.cy));
-                    grad.set_attribute((AId::R,  rg.r));
-                    grad.set_attribute((AId::Fx, rg.fx));
-                    grad.set_attribute((AId::Fy, rg.fy));
+                    grad.set_attribute((AId::Cx, opt.round(rg.cx)));
+                    grad.set_attribute((AId::Cy, opt.round(rg.cy)));
+                    grad.set_attribute((AId::R,  opt.round(rg.r)));
+                    grad.set_attribute((AId::Fx, opt.round(rg.fx)));
+                    grad.set_attribute((AId::Fy, opt.round(rg.fy)));
 
-                    conv_base_grad(&mut new_doc, &mut grad, &rg.d);
+                    conv_base_grad(&mut new_doc, &mut grad, &rg.d, opt);
                 }
             }
         }
     }
 
-    conv_elements(&doc.elements, &defs_list, &mut new_doc, &mut svg);
+    conv_elements(doc, &doc.elements, &Transform::default(), &defs_list, opt, &mut new_doc, &mut svg);
 
     new_doc
 }
 
+fn collect_refs(elements: &[Element], used_defs: &mut [bool], has_images: &mut bool) {
+    fn mark_paint(fill: Option<&Paint>, stroke: Option<&Paint>, used_defs: &mut [bool]) {
+        for paint in fill.into_iter().chain(stroke) {
+            if let Paint::Link(id) = *paint {
+                used_defs[id] = true;
+            }
+        }
+    }
+
+    for e in elements {
+        match e.data {
+            element::Type::Path(ref p) => {
+                mark_paint(p.fill.as_ref().map(|f| &f.paint), p.stroke.as_ref().map(|s| &s.paint),
+                           used_defs);
+            }
+            element::Type::Text(ref text) => {
+                for tspan in text.children.iter().flat_map(|chunk| chunk.children.iter()) {
+                    mark_paint(tspan.fill.as_ref().map(|f| &f.paint),
+                               tspan.stroke.as_ref().map(|s| &s.paint), used_defs);
+                }
+            }
+            element::Type::Image(_) => {
+                *has_images = true;
+            }
+            element::Type::Group(ref g) => {
+                collect_refs(&g.children, used_defs, has_images);
+            }
+        }
+    }
+}
+
+fn def_node(defs_list: &[Option<svgdom::Node>], id: usize) -> svgdom::Node {
+    // All referenced elements are written.
+    defs_list[id].clone().unwrap()
+}
+
+fn is_same_gradient(e1: &RefElement, e2: &RefElement) -> bool {
+    let is_same_base = |g1: &BaseGradient, g2: &BaseGradient| {
+        g1.units == g2.units
+            && g1.spread_method == g2.spread_method
+            && g1.transform.fuzzy_eq(&g2.transform)
+            && g1.stops.len() == g2.stops.len()
+            && g1.stops.iter().zip(g2.stops.iter()).all(|(s1, s2)| {
+                s1.offset.fuzzy_eq(&s2.offset)
+                    && s1.color == s2.color
+                    && s1.opacity.fuzzy_eq(&s2.opacity)
+            })
+    };
+
+    match e1.data {
+        element::RefType::LinearGradient(ref g1) => {
+            if let element::RefType::LinearGradient(ref g2) = e2.data {
+                   g1.x1.fuzzy_eq(&g2.x1) && g1.y1.fuzzy_eq(&g2.y1)
+                && g1.x2.fuzzy_eq(&g2.x2) && g1.y2.fuzzy_eq(&g2.y2)
+                && is_same_base(&g1.d, &g2.d)
+            } else {
+                false
+            }
+        }
+        element::RefType::RadialGradient(ref g1) => {
+            if let element::RefType::RadialGradient(ref g2) = e2.data {
+                   g1.cx.fuzzy_eq(&g2.cx) && g1.cy.fuzzy_eq(&g2.cy)
+                && g1.fx.fuzzy_eq(&g2.fx) && g1.fy.fuzzy_eq(&g2.fy)
+                && g1.r.fuzzy_eq(&g2.r)
+                && is_same_base(&g1.d, &g2.d)
+            } else {
+                false
+            }
+        }
+    }
+}
+
 fn conv_elements(
+    doc: &Document,
     elements: &[Element],
-    defs_list: &[svgdom::Node],
+    parent_ts: &Transform,
+    defs_list: &[Option<svgdom::Node>],
+    opt: &ExportOptions,
     new_doc: &mut svgdom::Document,
     root: &mut svgdom::Node,
 ) {
@@ -98,26 +255,51 @@ fn conv_elements(
     );
 
     for e in elements {
+        // 'parent_ts' is always default when transforms are not flattened.
+        let mut ts = *parent_ts;
+        ts.append(&e.transform);
+
         match e.data {
             element::Type::Path(ref p) => {
                 let mut path_elem = new_doc.create_element(EId::Path);
                 root.append(&path_elem);
 
-                conv_element(e, &mut path_elem);
+                conv_element(e, &mut path_elem, opt);
+
+                let flatten = opt.flatten_transforms && can_flatten_path(doc, p, &ts);
+                let path_ts = if flatten {
+                    ts
+                } else {
+                    if !ts.is_default() {
+                        path_elem.set_attribute((AId::Transform, opt.round_ts(&ts)));
+                    }
+
+                    Transform::default()
+                };
 
                 use svgdom::types::path::Path as SvgDomPath;
                 use svgdom::types::path::Segment;
 
+                let p_conv = |x: f64, y: f64| {
+                    let (x, y) = path_ts.apply(x, y);
+                    (opt.round(x), opt.round(y))
+                };
+
                 let mut path = SvgDomPath::with_capacity(p.d.len());
                 for seg in &p.d {
                     match *seg {
                         PathSegment::MoveTo { x, y } => {
+                            let (x, y) = p_conv(x, y);
                             path.d.push(Segment::new_move_to(x, y));
                         }
                         PathSegment::LineTo { x, y } => {
+                            let (x, y) = p_conv(x, y);
                             path.d.push(Segment::new_line_to(x, y));
                         }
                         PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                            let (x1, y1) = p_conv(x1, y1);
+                            let (x2, y2) = p_conv(x2, y2);
+                            let (x, y) = p_conv(x, y);
                             path.d.push(Segment::new_curve_to(x1, y1, x2, y2, x, y));
                         }
                         PathSegment::ClosePath => {
@@ -128,46 +310,70 @@ fn conv_elements(
 
                 path_elem.set_attribute((AId::D, path));
 
-                conv_fill(&p.fill, &mut path_elem, &defs_list);
-                conv_stroke(&p.stroke, &mut path_elem, &defs_list);
+                // A flattened transform is a similarity, so the stroke can be scaled uniformly.
+                let stroke_scale = (path_ts.a * path_ts.a + path_ts.b * path_ts.b).sqrt();
+
+                conv_fill(&p.fill, &mut path_elem, defs_list);
+                conv_stroke(&p.stroke, stroke_scale, &mut path_elem, defs_list, opt);
             }
             element::Type::Text(ref text) => {
                 let mut text_elem = new_doc.create_element(EId::Text);
                 root.append(&text_elem);
 
-                conv_element(e, &mut text_elem);
+                conv_element(e, &mut text_elem, opt);
 
-                // conv_text_decoration(&text.decoration, &mut text_elem);
+                if !ts.is_default() {
+                    text_elem.set_attribute((AId::Transform, opt.round_ts(&ts)));
+                }
+
+                // Text is already preprocessed, so we have to keep all the spaces as is.
+                text_elem.set_attribute((AId::XmlSpace, "preserve"));
 
+                // svgdom writes an element without indention only when it has a text child,
+                // otherwise indention will be treated as a text. Empty text nodes are
+                // removed during parsing.
+                let empty_text = new_doc.create_node(svgdom::NodeType::Text, "");
+                text_elem.append(&empty_text);
+
+                // Chunk attributes are stored in the first 'tspan' of the chunk,
+                // because nested 'tspan' elements are flattened during parsing.
                 for chunk in &text.children {
-                    let mut chunk_tspan_elem = new_doc.create_element(EId::Tspan);
-                    text_elem.append(&chunk_tspan_elem);
-
-                    chunk_tspan_elem.set_attribute((AId::X, chunk.x.clone()));
-                    chunk_tspan_elem.set_attribute((AId::Y, chunk.y.clone()));
-
-                    if chunk.anchor != TextAnchor::Start {
-                        chunk_tspan_elem.set_attribute((AId::TextAnchor,
-                            match chunk.anchor {
-                                TextAnchor::Start => svgdom::ValueId::Start,
-                                TextAnchor::Middle => svgdom::ValueId::Middle,
-                                TextAnchor::End => svgdom::ValueId::End,
-                            }
-                        ));
-                    }
+                    // Empty 'tspan' elements are removed during parsing.
+                    let tspans = chunk.children.iter().filter(|tspan| !tspan.text.is_empty());
+                    for (i, tspan) in tspans.enumerate() {
+                        let mut parent = conv_text_decoration(new_doc, &tspan.decoration,
+                                                      &text_elem, defs_list, opt);
 
-                    for tspan in &chunk.children {
                         let mut tspan_elem = new_doc.create_element(EId::Tspan);
-                        chunk_tspan_elem.append(&tspan_elem);
+                        parent.append(&tspan_elem);
+
+                        if i == 0 {
+                            tspan_elem.set_attribute((AId::X, opt.round(chunk.x)));
+                            tspan_elem.set_attribute((AId::Y, opt.round(chunk.y)));
+
+                            if chunk.anchor != TextAnchor::Start {
+                                tspan_elem.set_attribute((AId::TextAnchor,
+                                    match chunk.anchor {
+                                        TextAnchor::Start => svgdom::ValueId::Start,
+                                        TextAnchor::Middle => svgdom::ValueId::Middle,
+                                        TextAnchor::End => svgdom::ValueId::End,
+                                    }
+                                ));
+                            }
+                        }
 
                         let text_node = new_doc.create_node(svgdom::NodeType::Text, &tspan.text);
                         tspan_elem.append(&text_node);
 
-                        conv_fill(&tspan.fill, &mut tspan_elem, &defs_list);
-                        conv_stroke(&tspan.stroke, &mut tspan_elem, &defs_list);
-                        conv_font(&tspan.font, &mut tspan_elem);
+                        // svgdom skips a whitespace-only text when it's the only child.
+                        if tspan.text.trim().is_empty() {
+                            let empty_text = new_doc.create_node(svgdom::NodeType::Text, "");
+                            tspan_elem.append(&empty_text);
+                        }
 
-                        // TODO: text-decoration
+                        conv_fill(&tspan.fill, &mut tspan_elem, defs_list);
+                        conv_stroke(&tspan.stroke, 1.0, &mut tspan_elem, defs_list, opt);
+                        conv_font(&tspan.font, &mut tspan_elem, opt);
                     }
                 }
             }
@@ -175,25 +381,42 @@ fn conv_elements(
                 let mut img_elem = new_doc.create_element(EId::Image);
                 root.append(&img_elem);
 
-                conv_element(e, &mut img_elem);
+                conv_element(e, &mut img_elem, opt);
+
+                if !ts.is_default() {
+                    img_elem.set_attribute((AId::Transform, opt.round_ts(&ts)));
+                }
 
-                img_elem.set_attribute((AId::X, img.rect.x));
-                img_elem.set_attribute((AId::Y, img.rect.y));
-                img_elem.set_attribute((AId::Width, img.rect.w));
-                img_elem.set_attribute((AId::Height, img.rect.h));
+                img_elem.set_attribute((AId::X, opt.round(img.rect.x)));
+                img_elem.set_attribute((AId::Y, opt.round(img.rect.y)));
+                img_elem.set_attribute((AId::Width, opt.round(img.rect.w)));
+                img_elem.set_attribute((AId::Height, opt.round(img.rect.h)));
 
                 let href = match img.data {
-                    ImageData::Path(ref path) => path.to_str().unwrap().to_owned(),
                     ImageData::Raw(ref data, kind) => {
                         let mut d = String::with_capacity(data.len() + 20);
 
                         d.push_str("data:image/");
                         match kind {
                             ImageDataKind::PNG => d.push_str("png"),
-                            ImageDataKind::JPEG => d.push_str("jpg"),
+                            ImageDataKind::JPEG => d.push_str("jpeg"),
+                            ImageDataKind::GIF => d.push_str("gif"),
+                            ImageDataKind::BMP => d.push_str("bmp"),
+                            ImageDataKind::WEBP => d.push_str("webp"),
+                            ImageDataKind::TIFF => d.push_str("tiff"),
+                            ImageDataKind::ICO => d.push_str("x-icon"),
                         }
                         d.push_str(";base64,\n");
-                        d.push_str(&base64::encode_config(data, base64_conf));
+                        d.push_str(&base64::encode_config(data.as_slice(), base64_conf));
+
+                        d
+                    }
+                    ImageData::Svg(ref sub_doc) => {
+                        let data = conv_doc(sub_doc, opt).to_string();
+
+                        let mut d = String::with_capacity(data.len() + 30);
+                        d.push_str("data:image/svg+xml;base64,\n");
+                        d.push_str(&base64::encode_config(&data, base64_conf));
 
                         d
                     }
@@ -202,39 +425,99 @@ fn conv_elements(
                 img_elem.set_attribute((AId::XlinkHref, href));
             }
             element::Type::Group(ref g) => {
+                let opacity = g.opacity.and_then(|o| if o.fuzzy_ne(&1.0) { Some(o) } else { None });
+                let has_id = !e.id.is_empty() && !opt.strip_ids;
+
+                // When flattening, the transform is passed to children.
+                let (g_ts, children_ts) = if opt.flatten_transforms {
+                    (Transform::default(), ts)
+                } else {
+                    (ts, Transform::default())
+                };
+
+                // A group without attributes is useless.
+                if opacity.is_none() && !has_id && g_ts.is_default() {
+                    conv_elements(doc, &g.children, &children_ts, defs_list, opt, new_doc, root);
+                    continue;
+                }
+
                 let mut g_elem = new_doc.create_element(EId::G);
                 root.append(&g_elem);
 
-                conv_element(e, &mut g_elem);
+                conv_element(e, &mut g_elem, opt);
 
-                if let Some(opacity) = g.opacity {
-                    if opacity.fuzzy_ne(&1.0) {
-                        g_elem.set_attribute((AId::Opacity, opacity));
-                    }
+                if !g_ts.is_default() {
+                    g_elem.set_attribute((AId::Transform, opt.round_ts(&g_ts)));
+                }
+
+                if let Some(opacity) = opacity {
+                    g_elem.set_attribute((AId::Opacity, opacity));
                 }
 
-                conv_elements(&g.children, &defs_list, new_doc, &mut g_elem);
+                conv_elements(doc, &g.children, &children_ts, defs_list, opt,
+                              new_doc, &mut g_elem);
             }
         }
     }
 }
 
-fn conv_element(elem: &Element, node: &mut svgdom::Node) {
-    if !elem.transform.is_default() {
-        node.set_attribute((AId::Transform, elem.transform));
+// Path coordinates can absorb any transform, unlike stroke and gradients.
+fn can_flatten_path(doc: &Document, p: &Path, ts: &Transform) -> bool {
+    if ts.is_default() {
+        return true;
     }
 
-    if !elem.id.is_empty() {
+    // A stroke can be scaled only uniformly: rotation, uniform scale and translate.
+    if p.stroke.is_some() && !(ts.a.fuzzy_eq(&ts.d) && ts.b.fuzzy_eq(&-ts.c)) {
+        return false;
+    }
+
+    // 'objectBoundingBox' gradients follow the bbox, but only when it's
+    // transformed without a rotation or a flip. 'userSpaceOnUse' gradients
+    // depend on the user space, so they should be transformed too.
+    let is_safe_paint = |paint: &Paint| {
+        match *paint {
+            Paint::Color(_) => true,
+            Paint::Link(id) => {
+                let units = match doc.get_defs(id).data {
+                    element::RefType::LinearGradient(ref lg) => lg.d.units,
+                    element::RefType::RadialGradient(ref rg) => rg.d.units,
+                };
+
+                units == GradientUnits::ObjectBoundingBox
+                    && ts.b.fuzzy_eq(&0.0) && ts.c.fuzzy_eq(&0.0)
+                    && ts.a > 0.0 && ts.d > 0.0
+            }
+        }
+    };
+
+    if let Some(ref fill) = p.fill {
+        if !is_safe_paint(&fill.paint) {
+            return false;
+        }
+    }
+
+    if let Some(ref stroke) = p.stroke {
+        if !is_safe_paint(&stroke.paint) {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn conv_element(elem: &Element, node: &mut svgdom::Node, opt: &ExportOptions) {
+    if !elem.id.is_empty() && !opt.strip_ids {
         node.set_id(elem.id.clone());
     }
 }
 
-fn conv_fill(fill: &Option<Fill>, node: &mut svgdom::Node, defs_list: &[svgdom::Node]) {
+fn conv_fill(fill: &Option<Fill>, node: &mut svgdom::Node, defs_list: &[Option<svgdom::Node>]) {
     match *fill {
         Some(ref fill) => {
             match fill.paint {
                 Paint::Color(c) => node.set_attribute((AId::Fill, c)),
-                Paint::Link(id) => node.set_attribute((AId::Fill, defs_list[id].clone())),
+                Paint::Link(id) => node.set_attribute((AId::Fill, def_node(defs_list, id))),
             }
 
             if fill.opacity.fuzzy_ne(&1.0) {
@@ -251,12 +534,19 @@ fn conv_fill(fill: &Option<Fill>, node: &mut svgdom::Node, defs_list: &[svgdom::
     }
 }
 
-fn conv_stroke(stroke: &Option<Stroke>, node: &mut svgdom::Node, defs_list: &[svgdom::Node]) {
+// 'scale' is applied to all stroke lengths. Used by the transform flattening.
+fn conv_stroke(
+    stroke: &Option<Stroke>,
+    scale: f64,
+    node: &mut svgdom::Node,
+    defs_list: &[Option<svgdom::Node>],
+    opt: &ExportOptions,
+) {
     match *stroke {
         Some(ref stroke) => {
             match stroke.paint {
                 Paint::Color(c) => node.set_attribute((AId::Stroke, c)),
-                Paint::Link(id) => node.set_attribute((AId::Stroke, defs_list[id].clone())),
+                Paint::Link(id) => node.set_attribute((AId::Stroke, def_node(defs_list, id))),
             }
 
             if stroke.opacity.fuzzy_ne(&1.0) {
@@ -264,15 +554,16 @@ fn conv_stroke(stroke: &Option<Stroke>, node: &mut svgdom::Node, defs_list: &[sv
             }
 
             if stroke.dashoffset.fuzzy_ne(&0.0) {
-                node.set_attribute((AId::StrokeDashoffset, stroke.dashoffset));
+                node.set_attribute((AId::StrokeDashoffset, opt.round(stroke.dashoffset * scale)));
             }
 
             if stroke.miterlimit.fuzzy_ne(&4.0) {
                 node.set_attribute((AId::StrokeMiterlimit, stroke.miterlimit));
             }
 
-            if stroke.width.fuzzy_ne(&1.0) {
-                node.set_attribute((AId::StrokeWidth, stroke.width));
+            let width = opt.round(stroke.width * scale);
+            if width.fuzzy_ne(&1.0) {
+                node.set_attribute((AId::StrokeWidth, width));
             }
 
             if stroke.linecap != LineCap::Butt {
@@ -296,7 +587,8 @@ fn conv_stroke(stroke: &Option<Stroke>, node: &mut svgdom::Node, defs_list: &[sv
             }
 
             if let Some(ref array) = stroke.dasharray {
-                node.set_attribute((AId::StrokeDasharray, array.clone()));
+                let array: Vec<f64> = array.iter().map(|n| opt.round(n * scale)).collect();
+                node.set_attribute((AId::StrokeDasharray, array));
             }
         }
         None => {
@@ -305,7 +597,12 @@ fn conv_stroke(stroke: &Option<Stroke>, node: &mut svgdom::Node, defs_list: &[sv
     }
 }
 
-fn conv_base_grad(doc: &mut svgdom::Document, node: &mut svgdom::Node, g: &element::BaseGradient) {
+fn conv_base_grad(
+    doc: &mut svgdom::Document,
+    node: &mut svgdom::Node,
+    g: &element::BaseGradient,
+    opt: &ExportOptions,
+) {
     node.set_attribute((AId::GradientUnits,
         match g.units {
             GradientUnits::UserSpaceOnUse => svgdom::ValueId::UserSpaceOnUse,
@@ -322,7 +619,7 @@ fn conv_base_grad(doc: &mut svgdom::Document, node: &mut svgdom::Node, g: &eleme
     ));
 
     if !g.transform.is_default() {
-        node.set_attribute((AId::GradientTransform, g.transform));
+        node.set_attribute((AId::GradientTransform, opt.round_ts(&g.transform)));
     }
 
     for s in &g.stops {
@@ -335,9 +632,9 @@ fn conv_base_grad(doc: &mut svgdom::Document, node: &mut svgdom::Node, g: &eleme
     }
 }
 
-fn conv_font(font: &Font, node: &mut svgdom::Node) {
+fn conv_font(font: &Font, node: &mut svgdom::Node, opt: &ExportOptions) {
     node.set_attribute((AId::FontFamily, font.family.clone()));
-    node.set_attribute((AId::FontSize, font.size));
+    node.set_attribute((AId::FontSize, opt.round(font.size)));
 
     if font.style != FontStyle::Normal {
         node.set_attribute((AId::FontStyle,
@@ -396,3 +693,109 @@ fn conv_font(font: &Font, node: &mut svgdom::Node) {
         ));
     }
 }
+
+// Each decoration has its own style, which is taken from the element
+// where it was declared during parsing. So every decoration is written
+// as a separate 'tspan' with its own style around the text 'tspan':
+//
+// <tspan fill="green" text-decoration="underline">
+//   <tspan fill="blue" text-decoration="overline">
+//     <tspan fill="red">Text</tspan>
+//   </tspan>
+// </tspan>
+//
+// Returns the innermost element.
+fn conv_text_decoration(
+    doc: &mut svgdom::Document,
+    decoration: &TextDecoration,
+    parent: &svgdom::Node,
+    defs_list: &[Option<svgdom::Node>],
+    opt: &ExportOptions,
+) -> svgdom::Node {
+    let list = [
+        (svgdom::ValueId::Underline, &decoration.underline),
+        (svgdom::ValueId::Overline, &decoration.overline),
+        (svgdom::ValueId::LineThrough, &decoration.line_through),
+    ];
+
+    let mut parent = parent.clone();
+    for &(id, style) in &list {
+        if let Some(ref style) = *style {
+            let mut deco_elem = doc.create_element(EId::Tspan);
+            parent.append(&deco_elem);
+
+            deco_elem.set_attribute((AId::TextDecoration, id));
+            conv_fill(&style.fill, &mut deco_elem, defs_list);
+            conv_stroke(&style.stroke, 1.0, &mut deco_elem, defs_list, opt);
+
+            parent = deco_elem;
+        }
+    }
+
+    parent
+}
+
+#[cfg(test)]
+mod tests {
+    use svgdom::types::Color;
+
+    use dom::{
+        Document,
+        Paint,
+        TextDecorationStyle,
+        Type,
+    };
+    use {
+        parse_doc_from_data,
+        Options,
+    };
+
+    fn deco_color(style: &Option<TextDecorationStyle>) -> Option<Color> {
+        let fill = style.as_ref().and_then(|s| s.fill.as_ref());
+        match fill.map(|f| f.paint) {
+            Some(Paint::Color(c)) => Some(c),
+            _ => None,
+        }
+    }
+
+    // Decoration colors of all tspans: underline, overline, line-through.
+    fn decorations(doc: &Document) -> Vec<[Option<Color>; 3]> {
+        let mut list = Vec::new();
+        for elem in doc.descendants() {
+            if let Type::Text(ref text) = elem.data {
+                for tspan in text.children.iter().flat_map(|c| c.children.iter()) {
+                    let d = &tspan.decoration;
+                    list.push([deco_color(&d.underline), deco_color(&d.overline),
+                               deco_color(&d.line_through)]);
+                }
+            }
+        }
+
+        list
+    }
+
+    #[test]
+    fn text_decoration_round_trip() {
+        let text = "\
+<svg xmlns='http://www.w3.org/2000/svg' width='200' height='100'>
+    <g fill='#ff0000' text-decoration='underline'>
+        <text x='10' y='50' fill='#00ff00' font-family='sans-serif'><tspan fill='#0000ff' \
+text-decoration='overline'><tspan fill='#000000' text-decoration='line-through'>Text</tspan>\
+</tspan><tspan fill='#808080'>Text</tspan></text>
+    </g>
+</svg>";
+
+        let opt = Options::default();
+        let doc = parse_doc_from_data(text, &opt).unwrap();
+
+        let green = Some(Color::new(0, 255, 0));
+        let blue = Some(Color::new(0, 0, 255));
+        let black = Some(Color::new(0, 0, 0));
+        assert_eq!(decorations(&doc), vec![[green, blue, black], [green, None, None]]);
+
+        let exported = doc.to_svgdom().to_string();
+        let doc2 = parse_doc_from_data(&exported, &opt).unwrap();
+        assert_eq!(decorations(&doc2), decorations(&doc));
+        assert_eq!(doc2.to_svgdom().to_string(), exported);
+    }
+}
@@ -3,7 +3,7 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::fmt;
-use std::path::PathBuf;
+use std::sync::Arc;
 
 use svgdom::types::{
     Transform,
@@ -15,50 +15,178 @@ use math::{
 };
 
 use super::attribute::*;
+use super::Document;
+use super::iterators::Descendants;
 
 
+/// A renderable element.
+#[derive(Clone)]
 pub struct Element {
+    /// Element's ID.
+    ///
+    /// Taken from the SVG itself. Can be empty.
     pub id: String,
+    /// Element's data.
     pub data: Type,
+    /// Element's transform.
+    ///
+    /// Relative to the parent element.
     pub transform: Transform,
 }
 
+impl Element {
+    /// Returns children elements.
+    ///
+    /// Only groups can have children.
+    pub fn children(&self) -> &[Element] {
+        match self.data {
+            Type::Group(ref g) => &g.children,
+            _ => &[],
+        }
+    }
+
+    /// Returns an iterator over this element and all its descendants.
+    pub fn descendants(&self) -> Descendants<'_> {
+        Descendants::new(Some(self), self.children())
+    }
+
+    /// Checks that element is a group.
+    pub fn is_group(&self) -> bool {
+        matches!(self.data, Type::Group(_))
+    }
+
+    /// Sets element's transform.
+    pub fn set_transform(&mut self, ts: Transform) {
+        self.transform = ts;
+    }
+
+    /// Replaces the fill of a path or of all text spans.
+    ///
+    /// `Paint::Link` must be obtained from the same document.
+    ///
+    /// Does nothing for images and groups.
+    pub fn set_fill(&mut self, fill: Option<Fill>) {
+        match self.data {
+            Type::Path(ref mut path) => {
+                path.fill = fill;
+            }
+            Type::Text(ref mut text) => {
+                for tspan in text.children.iter_mut().flat_map(|c| c.children.iter_mut()) {
+                    tspan.fill = fill;
+                }
+            }
+            Type::Image(_) | Type::Group(_) => {}
+        }
+    }
+
+    /// Replaces the stroke of a path or of all text spans.
+    ///
+    /// `Paint::Link` must be obtained from the same document.
+    ///
+    /// Does nothing for images and groups.
+    pub fn set_stroke(&mut self, stroke: Option<Stroke>) {
+        match self.data {
+            Type::Path(ref mut path) => {
+                path.stroke = stroke;
+            }
+            Type::Text(ref mut text) => {
+                for tspan in text.children.iter_mut().flat_map(|c| c.children.iter_mut()) {
+                    tspan.stroke = stroke.clone();
+                }
+            }
+            Type::Image(_) | Type::Group(_) => {}
+        }
+    }
+}
+
+/// A renderable element type.
+#[derive(Clone)]
 pub enum Type {
+    /// A path.
     Path(Path),
+    /// A text.
     Text(Text),
+    /// A raster image.
     Image(Image),
+    /// A group of elements.
     Group(Group),
 }
 
+/// A referenced element.
+///
+/// Referenced elements are not rendered directly and can be accessed
+/// only via `Document::defs`.
+#[derive(Clone)]
 pub struct RefElement {
+    /// Element's ID.
+    ///
+    /// Taken from the SVG itself. Can be empty.
     pub id: String,
+    /// Element's data.
     pub data: RefType,
 }
 
+/// A referenced element type.
+#[derive(Clone)]
 pub enum RefType {
+    /// A linear gradient.
     LinearGradient(LinearGradient),
+    /// A radial gradient.
     RadialGradient(RadialGradient),
 }
 
+/// A resolved paint.
+///
+/// Returned by `Document::resolve_paint`.
+#[derive(Clone, Copy)]
+pub enum ResolvedPaint<'a> {
+    /// Paint with a color.
+    Color(Color),
+    /// Paint with a linear gradient.
+    LinearGradient(&'a LinearGradient),
+    /// Paint with a radial gradient.
+    RadialGradient(&'a RadialGradient),
+}
+
+/// A path element.
+///
+/// All basic shapes are converted into paths.
+#[derive(Clone)]
 pub struct Path {
+    /// Fill style.
     pub fill: Option<Fill>,
+    /// Stroke style.
     pub stroke: Option<Stroke>,
+    /// Segments list.
+    ///
     /// All segments are in absolute coordinates.
     pub d: Vec<PathSegment>,
 }
 
+/// A generic gradient.
+#[derive(Clone)]
 pub struct BaseGradient {
+    /// `gradientUnits` in SVG.
     pub units: GradientUnits,
+    /// `gradientTransform` in SVG.
     pub transform: Transform,
+    /// `spreadMethod` in SVG.
     pub spread_method: SpreadMethod,
+    /// A list of `stop` elements.
+    ///
+    /// Always contains at least two stops.
     pub stops: Vec<Stop>,
 }
 
+/// A linear gradient.
+#[derive(Clone)]
+#[allow(missing_docs)]
 pub struct LinearGradient {
     pub x1: f64,
     pub y1: f64,
     pub x2: f64,
     pub y2: f64,
+    /// Base gradient data.
     pub d: BaseGradient,
 }
 
@@ -83,12 +211,16 @@ impl fmt::Debug for LinearGradient {
     }
 }
 
+/// A radial gradient.
+#[derive(Clone)]
+#[allow(missing_docs)]
 pub struct RadialGradient {
     pub cx: f64,
     pub cy: f64,
     pub r: f64,
     pub fx: f64,
     pub fy: f64,
+    /// Base gradient data.
     pub d: BaseGradient,
 }
 
@@ -114,9 +246,16 @@ impl fmt::Debug for RadialGradient {
     }
 }
 
+/// A gradient stop.
+#[derive(Clone)]
 pub struct Stop {
+    /// `offset` in SVG.
+    ///
+    /// Always in the 0..1 range.
     pub offset: f64,
+    /// `stop-color` in SVG.
     pub color: Color,
+    /// `stop-opacity` in SVG.
     pub opacity: f64,
 }
 
@@ -127,45 +266,100 @@ impl fmt::Debug for Stop {
     }
 }
 
+/// A text element.
+#[derive(Clone)]
 pub struct Text {
+    /// A list of text chunks.
     pub children: Vec<TextChunk>,
 }
 
+/// A text chunk.
+///
+/// Contains a text that starts from an absolute position.
+#[derive(Clone)]
 pub struct TextChunk {
+    /// An absolute X coordinate.
     pub x: f64,
+    /// An absolute Y coordinate.
     pub y: f64,
+    /// `text-anchor` in SVG.
     pub anchor: TextAnchor,
+    /// A list of text spans.
     pub children: Vec<TSpan>
 }
 
+/// A text span.
+///
+/// A part of a text chunk with the same style.
 // TODO: dx, dy
 #[derive(Clone)]
 pub struct TSpan {
+    /// Fill style.
     pub fill: Option<Fill>,
+    /// Stroke style.
     pub stroke: Option<Stroke>,
+    /// Font properties.
     pub font: Font,
+    /// Text decoration.
     pub decoration: TextDecoration,
+    /// A text content.
+    ///
+    /// All whitespaces are already processed.
     pub text: String,
 }
 
+/// An image element.
+#[derive(Clone)]
 pub struct Image {
+    /// An image rectangle in which it should be fit.
     pub rect: Rect,
+    /// An image data.
     pub data: ImageData,
 }
 
+/// An image data.
+#[derive(Clone)]
 pub enum ImageData {
-    Path(PathBuf),
-    Raw(Vec<u8>, ImageDataKind),
+    /// A raster image data.
+    ///
+    /// Linked images are loaded by the parser via `Options::loader`,
+    /// so renderers never access the file system.
+    ///
+    /// The data is shared between copies of the same image,
+    /// so renderers can cache decoded images by the data address.
+    Raw(Arc<Vec<u8>>, ImageDataKind),
+    /// A parsed SVG image.
+    ///
+    /// Both linked and embedded SVG images are stored this way.
+    /// Rendered as vectors into the image rectangle.
+    Svg(Box<Document>),
 }
 
+/// A raster image data kind.
+///
+/// Detected from the image content and not from the MIME type.
+/// GIF images are rendered using the first frame.
 #[derive(Copy,Clone,PartialEq)]
+#[allow(missing_docs)]
 pub enum ImageDataKind {
     PNG,
     JPEG,
+    GIF,
+    BMP,
+    WEBP,
+    TIFF,
+    ICO,
 }
 
+/// A group element.
 // TODO: no need for a separate vector
+#[derive(Clone)]
 pub struct Group {
+    /// Group opacity.
+    ///
+    /// After the group is rendered we should combine
+    /// it with a parent group using the specified opacity.
     pub opacity: Option<f64>,
+    /// A list of children elements.
     pub children: Vec<Element>,
 }
@@ -0,0 +1,343 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom::types::{
+    Transform,
+};
+
+use math::{
+    Point,
+    Rect,
+};
+
+use render_utils;
+
+use super::bbox::curve_at;
+use super::{
+    Document,
+    Element,
+    FillRule,
+    Path,
+    PathSegment,
+    TextMetrics,
+    Type,
+};
+
+
+/// Number of lines that will be used to approximate a curve.
+const CURVE_STEPS: usize = 16;
+
+impl Document {
+    /// Finds the topmost element under the point.
+    ///
+    /// `point` and `view_rect` are in the canvas coordinates,
+    /// and `view_rect` should be the same as the one passed to a backend `render_to_canvas`.
+    ///
+    /// Returns IDs of the found element and all its parents, starting from the topmost parent.
+    /// Elements without an ID are skipped.
+    ///
+    /// Fill is tested using the element fill rule. Stroke is tested as a polyline
+    /// with round joins and caps, and without dashes.
+    ///
+    /// Text elements are ignored. Use `hit_test_with_metrics` with a backend `TextMetrics` instead.
+    /// Fully transparent groups are ignored too, since they are not rendered.
+    pub fn hit_test(&self, point: Point, view_rect: Rect) -> Option<Vec<String>> {
+        self.hit_test_with_metrics(point, view_rect, None)
+    }
+
+    /// Finds the topmost element under the point.
+    ///
+    /// Same as `hit_test`, but text elements are tested using their bounding box
+    /// when `metrics` is set.
+    pub fn hit_test_with_metrics(
+        &self,
+        point: Point,
+        view_rect: Rect,
+        metrics: Option<&dyn TextMetrics>,
+    ) -> Option<Vec<String>> {
+        let ts = {
+            let (dx, dy, sx, sy) = render_utils::view_box_transform(&self.view_box, &view_rect);
+            Transform::new(sx, 0.0, 0.0, sy, dx, dy)
+        };
+
+        let list = hit_elements(&self.elements, &ts, point, metrics)?;
+
+        let ids = list.iter()
+            .filter(|e| !e.id.is_empty())
+            .map(|e| e.id.clone())
+            .collect();
+
+        Some(ids)
+    }
+}
+
+fn hit_elements<'a>(
+    elements: &'a [Element],
+    parent_ts: &Transform,
+    point: Point,
+    metrics: Option<&dyn TextMetrics>,
+) -> Option<Vec<&'a Element>> {
+    // Elements are rendered in order, so the last one is the topmost one.
+    for elem in elements.iter().rev() {
+        let mut ts = *parent_ts;
+        ts.append(&elem.transform);
+
+        if let Type::Group(ref g) = elem.data {
+            if render_utils::is_invisible(g) {
+                continue;
+            }
+
+            if let Some(mut list) = hit_elements(&g.children, &ts, point, metrics) {
+                list.insert(0, elem);
+                return Some(list);
+            }
+
+            continue;
+        }
+
+        // Test the point in the element coordinates.
+        let (x, y) = match invert(&ts) {
+            Some(ts) => ts.apply(point.x, point.y),
+            None => continue,
+        };
+
+        let is_hit = match elem.data {
+            Type::Path(ref path) => {
+                hit_path(path, x, y)
+            }
+            Type::Text(ref text) => {
+                match metrics.and_then(|m| m.text_bbox(text)) {
+                    Some(r) => contains(&r, x, y),
+                    None => false,
+                }
+            }
+            Type::Image(ref img) => {
+                contains(&img.rect, x, y)
+            }
+            Type::Group(_) => false,
+        };
+
+        if is_hit {
+            return Some(vec![elem]);
+        }
+    }
+
+    None
+}
+
+fn hit_path(path: &Path, x: f64, y: f64) -> bool {
+    let polylines = flatten(&path.d);
+
+    if let Some(ref fill) = path.fill {
+        // Fill area is always closed, so we don't care about 'ClosePath'.
+        let mut winding = 0;
+        for points in polylines.iter().map(|p| &p.0) {
+            for (i, p1) in points.iter().enumerate() {
+                let p2 = points[(i + 1) % points.len()];
+                winding += edge_winding(*p1, p2, x, y);
+            }
+        }
+
+        let is_inside = match fill.rule {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        };
+
+        if is_inside {
+            return true;
+        }
+    }
+
+    if let Some(ref stroke) = path.stroke {
+        let half_width = stroke.width / 2.0;
+
+        for &(ref points, is_closed) in &polylines {
+            let count = if is_closed { points.len() } else { points.len() - 1 };
+
+            for i in 0..count {
+                let p1 = points[i];
+                let p2 = points[(i + 1) % points.len()];
+
+                if segment_distance(p1, p2, x, y) <= half_width {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+/// Converts a path into a list of polylines with a closed flag.
+fn flatten(d: &[PathSegment]) -> Vec<(Vec<(f64, f64)>, bool)> {
+    let mut list: Vec<(Vec<(f64, f64)>, bool)> = Vec::new();
+    let mut points: Vec<(f64, f64)> = Vec::new();
+
+    for seg in d {
+        match *seg {
+            PathSegment::MoveTo { x, y } => {
+                if points.len() > 1 {
+                    list.push((points, false));
+                }
+
+                points = vec![(x, y)];
+            }
+            PathSegment::LineTo { x, y } => {
+                points.push((x, y));
+            }
+            PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                let (x0, y0) = points.last().cloned().unwrap_or((0.0, 0.0));
+
+                for i in 1..(CURVE_STEPS + 1) {
+                    let t = i as f64 / CURVE_STEPS as f64;
+                    points.push((curve_at(x0, x1, x2, x, t), curve_at(y0, y1, y2, y, t)));
+                }
+            }
+            PathSegment::ClosePath => {
+                if !points.is_empty() {
+                    // The next segment starts from the start of the closed subpath.
+                    let start = points[0];
+                    list.push((points, true));
+                    points = vec![start];
+                }
+            }
+        }
+    }
+
+    if points.len() > 1 {
+        list.push((points, false));
+    }
+
+    list
+}
+
+/// Returns the edge winding number contribution.
+fn edge_winding(p1: (f64, f64), p2: (f64, f64), x: f64, y: f64) -> i32 {
+    let is_left = (p2.0 - p1.0) * (y - p1.1) - (x - p1.0) * (p2.1 - p1.1);
+
+    if p1.1 <= y {
+        if p2.1 > y && is_left > 0.0 {
+            return 1;
+        }
+    } else if p2.1 <= y && is_left < 0.0 {
+        return -1;
+    }
+
+    0
+}
+
+fn segment_distance(p1: (f64, f64), p2: (f64, f64), x: f64, y: f64) -> f64 {
+    let dx = p2.0 - p1.0;
+    let dy = p2.1 - p1.1;
+    let len2 = dx * dx + dy * dy;
+
+    let t = if len2 > 0.0 {
+        (((x - p1.0) * dx + (y - p1.1) * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let px = p1.0 + t * dx - x;
+    let py = p1.1 + t * dy - y;
+    (px * px + py * py).sqrt()
+}
+
+fn contains(r: &Rect, x: f64, y: f64) -> bool {
+    x >= r.x && y >= r.y && x <= r.x + r.w && y <= r.y + r.h
+}
+
+fn invert(ts: &Transform) -> Option<Transform> {
+    let det = ts.a * ts.d - ts.b * ts.c;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    Some(Transform::new(
+        ts.d / det,
+        -ts.b / det,
+        -ts.c / det,
+        ts.a / det,
+        (ts.c * ts.f - ts.d * ts.e) / det,
+        (ts.b * ts.e - ts.a * ts.f) / det,
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use math::{
+        Point,
+        Rect,
+    };
+    use test_utils::parse;
+
+    // The canvas has the same size as the document, so coordinates are the same.
+    fn hit(content: &str, x: f64, y: f64) -> Option<Vec<String>> {
+        let doc = parse("width='100' height='100'", content);
+        doc.hit_test(Point::new(x, y), Rect::new(0.0, 0.0, 100.0, 100.0))
+    }
+
+    // Two nested squares with the same direction.
+    const NESTED: &str = "M 10 10 L 90 10 L 90 90 L 10 90 Z M 30 30 L 70 30 L 70 70 L 30 70 Z";
+
+    #[test]
+    fn fill_rule_nonzero() {
+        let s = format!("<path id='p' fill-rule='nonzero' d='{}'/>", NESTED);
+        assert_eq!(hit(&s, 20.0, 20.0), Some(vec!["p".to_string()]));
+        assert_eq!(hit(&s, 50.0, 50.0), Some(vec!["p".to_string()]));
+        assert_eq!(hit(&s, 95.0, 95.0), None);
+    }
+
+    #[test]
+    fn fill_rule_evenodd() {
+        let s = format!("<path id='p' fill-rule='evenodd' d='{}'/>", NESTED);
+        assert_eq!(hit(&s, 20.0, 20.0), Some(vec!["p".to_string()]));
+        assert_eq!(hit(&s, 50.0, 50.0), None);
+    }
+
+    #[test]
+    fn stroke_only() {
+        let s = "<path id='p' fill='none' stroke='black' stroke-width='10' \
+                 d='M 10 50 L 90 50'/>";
+        assert_eq!(hit(s, 50.0, 54.0), Some(vec!["p".to_string()]));
+        assert_eq!(hit(s, 50.0, 56.0), None);
+        // Round caps.
+        assert_eq!(hit(s, 93.0, 50.0), Some(vec!["p".to_string()]));
+
+        // An unclosed subpath has no closing segment.
+        let s = "<path id='p' fill='none' stroke='black' stroke-width='2' \
+                 d='M 10 10 L 90 10 L 90 90'/>";
+        assert_eq!(hit(s, 50.0, 50.0), None);
+        assert_eq!(hit(s, 90.0, 50.0), Some(vec!["p".to_string()]));
+    }
+
+    #[test]
+    fn nested_transforms() {
+        let s = "<g id='g1' opacity='0.5' transform='translate(50 0)'>\
+                   <g id='g2' opacity='0.5' transform='scale(2)'>\
+                     <rect id='r1' width='10' height='10'/>\
+                     <rect id='r2' x='20' y='20' width='5' height='5'/>\
+                   </g>\
+                   <rect id='r3' y='80' width='10' height='10'/>\
+                 </g>";
+        assert_eq!(hit(s, 65.0, 15.0),
+                   Some(vec!["g1".to_string(), "g2".to_string(), "r1".to_string()]));
+        assert_eq!(hit(s, 95.0, 45.0),
+                   Some(vec!["g1".to_string(), "g2".to_string(), "r2".to_string()]));
+        assert_eq!(hit(s, 55.0, 85.0), Some(vec!["g1".to_string(), "r3".to_string()]));
+        assert_eq!(hit(s, 5.0, 5.0), None);
+        assert_eq!(hit(s, 75.0, 15.0), None);
+    }
+
+    #[test]
+    fn transparent_group_is_skipped() {
+        let s = "<rect id='r1' width='50' height='50'/>\
+                 <g id='g' opacity='0'>\
+                   <rect id='r2' width='50' height='50'/>\
+                   <rect id='r3' x='50' width='50' height='50'/>\
+                 </g>";
+        assert_eq!(hit(s, 10.0, 10.0), Some(vec!["r1".to_string()]));
+        assert_eq!(hit(s, 60.0, 10.0), None);
+    }
+}
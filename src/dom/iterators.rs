@@ -0,0 +1,107 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::iter;
+use std::slice;
+use std::vec;
+
+use super::element::{
+    Element,
+    Type,
+};
+
+
+/// An iterator over descendant elements.
+///
+/// Elements are returned in the rendering order.
+pub struct Descendants<'a> {
+    first: Option<&'a Element>,
+    stack: Vec<slice::Iter<'a, Element>>,
+}
+
+impl<'a> Descendants<'a> {
+    pub(crate) fn new(first: Option<&'a Element>, elements: &'a [Element]) -> Self {
+        Descendants {
+            first,
+            stack: vec![elements.iter()],
+        }
+    }
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a Element;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(elem) = self.first.take() {
+            return Some(elem);
+        }
+
+        loop {
+            let elem = match self.stack.last_mut() {
+                Some(iter) => iter.next(),
+                None => return None,
+            };
+
+            match elem {
+                Some(elem) => {
+                    if let Type::Group(ref g) = elem.data {
+                        self.stack.push(g.children.iter());
+                    }
+
+                    return Some(elem);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+
+/// An iterator over parent elements.
+///
+/// Starts from the nearest parent.
+pub struct Parents<'a> {
+    iter: iter::Rev<vec::IntoIter<&'a Element>>,
+}
+
+impl<'a> Parents<'a> {
+    pub(crate) fn new(elements: &'a [Element], elem: &Element) -> Self {
+        let mut list = Vec::new();
+        find_parents(elements, elem, &mut list);
+
+        Parents {
+            iter: list.into_iter().rev(),
+        }
+    }
+}
+
+impl<'a> Iterator for Parents<'a> {
+    type Item = &'a Element;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+fn find_parents<'a>(elements: &'a [Element], elem: &Element, list: &mut Vec<&'a Element>) -> bool {
+    for child in elements {
+        if ::std::ptr::eq(child, elem) {
+            return true;
+        }
+
+        if let Type::Group(ref g) = child.data {
+            list.push(child);
+
+            if find_parents(&g.children, elem, list) {
+                return true;
+            }
+
+            list.pop();
+        }
+    }
+
+    false
+}
@@ -2,6 +2,12 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+//! A preprocessed SVG representation.
+//!
+//! This is a render tree that is used by all backends and exporters.
+//! It contains only supported, resolved elements and attributes,
+//! so a custom backend doesn't have to deal with the SVG complexity.
+
 use svgdom;
 
 use math::{
@@ -11,17 +17,28 @@ use math::{
 
 
 mod attribute;
+mod bbox;
+mod builder;
 mod dump;
 mod element;
+mod hit_test;
+mod iterators;
 
 pub use self::element::*;
 pub use self::attribute::*;
+pub use self::bbox::TextMetrics;
+pub use self::builder::Builder;
+pub use self::dump::ExportOptions;
+pub use self::iterators::*;
 
 
 /// Container for a preprocessed SVG.
 ///
 /// Unlike svgdom's `Document` this one is immutable for a backend code
 /// and contains only supported, resolved elements and attributes.
+///
+/// It's `Send` and `Sync`, so it can be shared between rendering threads.
+#[derive(Clone)]
 pub struct Document {
     /// Image size.
     ///
@@ -45,16 +62,242 @@ pub struct Document {
     pub elements: Vec<Element>,
 }
 
+// Makes sure that the document stays thread-safe.
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn check<T: Send + Sync>() {}
+    check::<Document>();
+}
+
 impl Document {
     /// Returns referenced element by id.
     pub fn get_defs<'a>(&'a self, id: usize) -> &'a RefElement {
         &self.defs[id]
     }
 
-    /// Converts the document to `svgdom::Document`.
+    /// Returns an iterator over all elements.
+    ///
+    /// Elements are returned in the rendering order.
+    pub fn descendants(&self) -> Descendants<'_> {
+        Descendants::new(None, &self.elements)
+    }
+
+    /// Returns an iterator over parents of the element.
+    ///
+    /// The element must be a part of this document.
+    /// Otherwise, the iterator will be empty.
+    pub fn parents<'a>(&'a self, elem: &Element) -> Parents<'a> {
+        Parents::new(&self.elements, elem)
+    }
+
+    /// Returns an element by its SVG ID.
+    ///
+    /// Elements with an empty ID are ignored.
+    pub fn element_by_id(&self, id: &str) -> Option<&Element> {
+        if id.is_empty() {
+            return None;
+        }
+
+        self.descendants().find(|e| e.id == id)
+    }
+
+    /// Returns a referenced element by its SVG ID.
+    ///
+    /// Elements with an empty ID are ignored.
+    pub fn defs_by_id(&self, id: &str) -> Option<&RefElement> {
+        if id.is_empty() {
+            return None;
+        }
+
+        self.defs.iter().find(|e| e.id == id)
+    }
+
+    /// Returns a mutable element by its SVG ID.
     ///
-    /// Used to save document to file for debug purposes.
+    /// Elements with an empty ID are ignored.
+    pub fn element_by_id_mut(&mut self, id: &str) -> Option<&mut Element> {
+        if id.is_empty() {
+            return None;
+        }
+
+        find_element_mut(&mut self.elements, id)
+    }
+
+    /// Removes an element by its SVG ID.
+    ///
+    /// Returns the removed element, including all its children.
+    pub fn remove_element(&mut self, id: &str) -> Option<Element> {
+        if id.is_empty() {
+            return None;
+        }
+
+        remove_element(&mut self.elements, id)
+    }
+
+    /// Registers a referenced element.
+    ///
+    /// Returns a paint that links to this element.
+    ///
+    /// Returns `None` when the gradient contains less than 2 stops.
+    pub fn add_defs(&mut self, elem: RefElement) -> Option<Paint> {
+        let stops_count = match elem.data {
+            RefType::LinearGradient(ref lg) => lg.d.stops.len(),
+            RefType::RadialGradient(ref rg) => rg.d.stops.len(),
+        };
+
+        if stops_count < 2 {
+            diag!(Warning, InvalidGradient, elem.id,
+                  "Gradient '{}' contains less than 2 stops. Skipped.", elem.id);
+            return None;
+        }
+
+        self.defs.push(elem);
+        Some(Paint::Link(self.defs.len() - 1))
+    }
+
+    /// Resolves a paint into a color or a referenced paint server.
+    pub fn resolve_paint(&self, paint: Paint) -> ResolvedPaint<'_> {
+        match paint {
+            Paint::Color(c) => ResolvedPaint::Color(c),
+            Paint::Link(id) => {
+                match self.defs[id].data {
+                    RefType::LinearGradient(ref lg) => ResolvedPaint::LinearGradient(lg),
+                    RefType::RadialGradient(ref rg) => ResolvedPaint::RadialGradient(rg),
+                }
+            }
+        }
+    }
+
+    /// Converts the document to `svgdom::Document` using default `ExportOptions`.
+    ///
+    /// The result can be parsed back by `parse_doc_from_data`
+    /// and will produce an equivalent document.
     pub fn to_svgdom(&self) -> svgdom::Document {
-        dump::conv_doc(self)
+        dump::conv_doc(self, &ExportOptions::default())
+    }
+
+    /// Converts the document to `svgdom::Document` using specified `ExportOptions`.
+    pub fn to_svgdom_opt(&self, opt: &ExportOptions) -> svgdom::Document {
+        dump::conv_doc(self, opt)
+    }
+}
+
+fn find_element_mut<'a>(elements: &'a mut [Element], id: &str) -> Option<&'a mut Element> {
+    for elem in elements {
+        if elem.id == id {
+            return Some(elem);
+        }
+
+        if let Type::Group(ref mut g) = elem.data {
+            if let Some(elem) = find_element_mut(&mut g.children, id) {
+                return Some(elem);
+            }
+        }
+    }
+
+    None
+}
+
+fn remove_element(elements: &mut Vec<Element>, id: &str) -> Option<Element> {
+    for i in 0..elements.len() {
+        if elements[i].id == id {
+            return Some(elements.remove(i));
+        }
+
+        if let Type::Group(ref mut g) = elements[i].data {
+            if let Some(elem) = remove_element(&mut g.children, id) {
+                return Some(elem);
+            }
+        }
+    }
+
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use svgdom::types::Color;
+
+    use super::*;
+    use test_utils;
+
+    const CONTENT: &str = "\
+<linearGradient id='lg'>
+    <stop offset='0' stop-color='red'/>
+    <stop offset='1' stop-color='blue'/>
+</linearGradient>
+<g id='g1' opacity='0.5'>
+    <rect id='r1' width='10' height='10' fill='url(#lg)'/>
+    <g id='g2' opacity='0.5'>
+        <rect id='r2' width='10' height='10' fill='green'/>
+        <rect id='r3' width='10' height='10'/>
+    </g>
+</g>
+<rect id='r4' width='10' height='10'/>";
+
+    fn parse() -> Document {
+        test_utils::parse("width='100' height='100'", CONTENT)
+    }
+
+    fn ids<'a, I: Iterator<Item = &'a Element>>(iter: I) -> Vec<&'a str> {
+        iter.map(|e| e.id.as_str()).collect()
+    }
+
+    #[test]
+    fn descendants_order() {
+        let doc = parse();
+        assert_eq!(ids(doc.descendants()), vec!["g1", "r1", "g2", "r2", "r3", "r4"]);
+
+        let g2 = doc.element_by_id("g2").unwrap();
+        assert_eq!(ids(g2.descendants()), vec!["g2", "r2", "r3"]);
+    }
+
+    #[test]
+    fn parents_order() {
+        let doc = parse();
+
+        let r2 = doc.element_by_id("r2").unwrap();
+        assert_eq!(ids(doc.parents(r2)), vec!["g2", "g1"]);
+
+        let r4 = doc.element_by_id("r4").unwrap();
+        assert_eq!(doc.parents(r4).count(), 0);
+
+        // An element from another document.
+        let other = parse();
+        assert_eq!(doc.parents(other.element_by_id("r2").unwrap()).count(), 0);
+    }
+
+    #[test]
+    fn lookup_by_id() {
+        let doc = parse();
+
+        assert!(doc.element_by_id("r3").is_some());
+        assert!(doc.element_by_id("missing").is_none());
+        assert!(doc.element_by_id("").is_none());
+        assert!(doc.defs_by_id("lg").is_some());
+        assert!(doc.defs_by_id("r1").is_none());
+    }
+
+    #[test]
+    fn resolve_paints() {
+        let doc = parse();
+
+        let fill_of = |id: &str| {
+            match doc.element_by_id(id).unwrap().data {
+                Type::Path(ref path) => path.fill.as_ref().unwrap().paint,
+                _ => unreachable!(),
+            }
+        };
+
+        match doc.resolve_paint(fill_of("r1")) {
+            ResolvedPaint::LinearGradient(lg) => assert_eq!(lg.d.stops.len(), 2),
+            _ => panic!("a linear gradient expected"),
+        }
+
+        match doc.resolve_paint(fill_of("r2")) {
+            ResolvedPaint::Color(c) => assert_eq!(c, Color::new(0, 128, 0)),
+            _ => panic!("a color expected"),
+        }
     }
 }
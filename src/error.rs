@@ -21,11 +21,21 @@ error_chain! {
     errors {
         /// Failed to find an SVG size.
         ///
-        /// SVG size must be explicitly defined.
-        /// Automatic image size determination is not supported.
+        /// SVG size is relative and there is no `viewBox`,
+        /// so it should be detected from the content, which is empty.
         SizeDeterminationUnsupported {
-            display("file doesn't have 'width', 'height' and 'viewBox' attributes. \
-                     Automatic image size determination is not supported")
+            display("file doesn't have 'width', 'height' and 'viewBox' attributes \
+                     and the size can't be detected from the content")
+        }
+
+        /// The data is detected as UTF-16 text, but it is not valid.
+        InvalidUtf16 {
+            display("invalid UTF-16 data")
+        }
+
+        /// The operation was cancelled using `Options::progress`.
+        Cancelled {
+            display("the operation was cancelled")
         }
 
         /// The `svg` node is missing.
@@ -47,6 +57,51 @@ error_chain! {
             display("invalid 'viewBox' attribute value: '{}'", s)
         }
 
+        /// An element with the specified ID is not found.
+        ElementNotFound(id: String) {
+            display("element '{}' is not found", id)
+        }
+
+        /// An element has an empty bounding box, so it can't be rendered.
+        EmptyElement(id: String) {
+            display("element '{}' has an empty bounding box", id)
+        }
+
+        /// A rendering area is empty.
+        EmptyArea {
+            display("the rendering area is empty")
+        }
+
+        /// A caller-provided buffer is too small, misaligned or has an invalid stride.
+        InvalidBuffer {
+            display("the buffer is too small, misaligned or has an invalid stride")
+        }
+
+        /// The number of nodes exceeds `Limits::max_elements`.
+        TooManyElements(limit: usize) {
+            display("the number of elements exceeds the limit of {}", limit)
+        }
+
+        /// Nodes nesting exceeds `Limits::max_depth`.
+        NestingTooDeep(limit: usize) {
+            display("elements nesting exceeds the limit of {}", limit)
+        }
+
+        /// A decompressed SVGZ data exceeds `Limits::max_decompressed_size`.
+        DecompressedSizeTooLarge(limit: usize) {
+            display("decompressed data exceeds the limit of {} bytes", limit)
+        }
+
+        /// A `data:` URL exceeds `Limits::max_data_url_size`.
+        DataUrlTooLarge(limit: usize) {
+            display("a data URL exceeds the limit of {} bytes", limit)
+        }
+
+        /// A canvas exceeds `Limits::max_canvas_pixels`.
+        CanvasTooLarge(limit: u64) {
+            display("the canvas exceeds the limit of {} pixels", limit)
+        }
+
         /// Failed to allocate an image.
         ///
         /// Probably because it's too big or there is not enough memory.
@@ -0,0 +1,273 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Exporters to the non-SVG vector formats.
+//!
+//! Exporters are working with a preprocessed `Document`, so they have to support
+//! only absolute paths, solid and gradient paints, groups and transforms.
+//! Everything that can't be represented in the target format is reported
+//! as a `Warning`.
+
+use std::fmt;
+
+use svgdom;
+use svgdom::types::{
+    Color,
+    Transform,
+};
+
+use dom;
+
+mod vector_drawable;
+mod xaml;
+
+pub use self::vector_drawable::to_vector_drawable;
+pub use self::xaml::to_xaml;
+
+
+/// A kind of unsupported construct.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WarningKind {
+    /// The `text` element was skipped.
+    Text,
+    /// The `image` element was skipped.
+    Image,
+    /// The group opacity was applied to the children paint.
+    ///
+    /// Overlapping children will be rendered differently.
+    GroupOpacity,
+    /// The dashed stroke was replaced with a solid one.
+    StrokeDasharray,
+    /// The stroke has a non-uniform transform and its width was averaged.
+    NonUniformStroke,
+    /// The gradient has a skew or a non-uniform scale, which can't be represented.
+    GradientTransform,
+    /// The radial gradient focal point was ignored.
+    GradientFocalPoint,
+}
+
+/// An export warning.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Warning {
+    /// The warning kind.
+    pub kind: WarningKind,
+    /// The ID of the element that caused the warning.
+    ///
+    /// Can be empty.
+    pub element_id: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self.kind {
+            WarningKind::Text => "text is not supported",
+            WarningKind::Image => "images are not supported",
+            WarningKind::GroupOpacity => "group opacity was applied to children",
+            WarningKind::StrokeDasharray => "dashed stroke is not supported",
+            WarningKind::NonUniformStroke => "stroke with a non-uniform transform was approximated",
+            WarningKind::GradientTransform => "gradient transform was approximated",
+            WarningKind::GradientFocalPoint => "radial gradient focal point was ignored",
+        };
+
+        if self.element_id.is_empty() {
+            write!(f, "{}", msg)
+        } else {
+            write!(f, "'{}': {}", self.element_id, msg)
+        }
+    }
+}
+
+/// An export result.
+pub struct Output {
+    /// The exported document.
+    pub doc: svgdom::Document,
+    /// A list of unsupported constructs.
+    pub warnings: Vec<Warning>,
+}
+
+
+fn add_warning(warnings: &mut Vec<Warning>, kind: WarningKind, elem: &dom::Element) {
+    warnings.push(Warning {
+        kind,
+        element_id: elem.id.clone(),
+    });
+}
+
+/// Formats a number without an exponent and with a limited precision.
+fn num(n: f64) -> String {
+    let mut s = format!("{:.6}", n);
+    while s.ends_with('0') {
+        s.pop();
+    }
+
+    if s.ends_with('.') {
+        s.pop();
+    }
+
+    if s == "-0" {
+        s = "0".to_string();
+    }
+
+    s
+}
+
+/// Formats a color as `#AARRGGBB`.
+fn argb(c: Color, alpha: f64) -> String {
+    let a = (alpha * 255.0).round().clamp(0.0, 255.0) as u8;
+    format!("#{:02X}{:02X}{:02X}{:02X}", a, c.red, c.green, c.blue)
+}
+
+/// Converts path segments into the SVG path data notation,
+/// which is also used by VectorDrawable and XAML.
+fn path_data(d: &[dom::PathSegment], ts: &Transform) -> String {
+    let mut s = String::new();
+
+    for seg in d {
+        if !s.is_empty() {
+            s.push(' ');
+        }
+
+        match *seg {
+            dom::PathSegment::MoveTo { x, y } => {
+                let (x, y) = ts.apply(x, y);
+                s.push_str(&format!("M {},{}", num(x), num(y)));
+            }
+            dom::PathSegment::LineTo { x, y } => {
+                let (x, y) = ts.apply(x, y);
+                s.push_str(&format!("L {},{}", num(x), num(y)));
+            }
+            dom::PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                let (x1, y1) = ts.apply(x1, y1);
+                let (x2, y2) = ts.apply(x2, y2);
+                let (x, y) = ts.apply(x, y);
+                s.push_str(&format!("C {},{} {},{} {},{}",
+                                    num(x1), num(y1), num(x2), num(y2), num(x), num(y)));
+            }
+            dom::PathSegment::ClosePath => {
+                s.push('Z');
+            }
+        }
+    }
+
+    s
+}
+
+/// Checks that transform consists only of a translate, rotate, flip and uniform scale.
+fn is_similarity(ts: &Transform) -> bool {
+    let eq = |a: f64, b: f64| (a - b).abs() < 1e-6;
+    (eq(ts.a, ts.d) && eq(ts.b, -ts.c)) || (eq(ts.a, -ts.d) && eq(ts.b, ts.c))
+}
+
+/// Returns an average scale factor of the transform.
+fn avg_scale(ts: &Transform) -> f64 {
+    (ts.a * ts.d - ts.b * ts.c).abs().sqrt()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::parse;
+
+    fn kinds(warnings: &[Warning]) -> Vec<WarningKind> {
+        warnings.iter().map(|w| w.kind).collect()
+    }
+
+    #[test]
+    fn format_numbers() {
+        assert_eq!(num(1.0), "1");
+        assert_eq!(num(1.5), "1.5");
+        assert_eq!(num(-0.0000001), "0");
+        assert_eq!(num(1.0 / 3.0), "0.333333");
+        assert_eq!(num(1e10), "10000000000");
+    }
+
+    #[test]
+    fn format_argb() {
+        assert_eq!(argb(Color::new(255, 0, 16), 1.0), "#FFFF0010");
+        assert_eq!(argb(Color::new(0, 0, 0), 0.5), "#80000000");
+    }
+
+    #[test]
+    fn transformed_path_data() {
+        let d = [
+            dom::PathSegment::MoveTo { x: 1.0, y: 2.0 },
+            dom::PathSegment::LineTo { x: 3.0, y: 4.0 },
+            dom::PathSegment::ClosePath,
+        ];
+        let ts = Transform::new(2.0, 0.0, 0.0, 2.0, 10.0, 0.0);
+        assert_eq!(path_data(&d, &ts), "M 12,4 L 16,8 Z");
+    }
+
+    #[test]
+    fn similarity() {
+        assert!(is_similarity(&Transform::new(2.0, 0.0, 0.0, 2.0, 5.0, 5.0)));
+        assert!(is_similarity(&Transform::new(0.0, 1.0, -1.0, 0.0, 0.0, 0.0)));
+        assert!(is_similarity(&Transform::new(-1.0, 0.0, 0.0, 1.0, 0.0, 0.0)));
+        assert!(!is_similarity(&Transform::new(2.0, 0.0, 0.0, 1.0, 0.0, 0.0)));
+        assert!(!is_similarity(&Transform::new(1.0, 0.0, 0.5, 1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn vector_drawable_path() {
+        let doc = parse("width='20' height='20'",
+                        "<rect id='r' x='1' y='2' width='3' height='4' fill='#ff0000'/>");
+        let out = to_vector_drawable(&doc);
+        let text = out.doc.to_string();
+
+        assert!(text.contains("android:pathData=\"M 1,2 L 4,2 L 4,6 L 1,6 Z\""), "{}", text);
+        assert!(text.contains("android:fillColor=\"#FFFF0000\""), "{}", text);
+        assert!(out.warnings.is_empty());
+    }
+
+    #[test]
+    fn vector_drawable_warnings() {
+        let doc = parse("width='20' height='20'",
+                        "<g id='g' opacity='0.5'>\
+                           <path id='p1' d='M 0 0 L 10 10' stroke='black' stroke-dasharray='2'/>\
+                           <path id='p2' d='M 0 0 L 10 10' stroke='black'/>\
+                         </g>\
+                         <text id='t' x='5' y='5'>Text</text>");
+        let out = to_vector_drawable(&doc);
+
+        assert_eq!(kinds(&out.warnings), vec![
+            WarningKind::GroupOpacity,
+            WarningKind::StrokeDasharray,
+            WarningKind::Text,
+        ]);
+        assert_eq!(out.warnings[1].element_id, "p1");
+    }
+
+    #[test]
+    fn xaml_path() {
+        let doc = parse("width='20' height='20'",
+                        "<rect id='r' x='1' y='2' width='3' height='4' fill='#ff0000'/>");
+        let out = to_xaml(&doc);
+        let text = out.doc.to_string();
+
+        assert!(text.contains("M 1,2 L 4,2 L 4,6 L 1,6 Z"), "{}", text);
+        assert!(text.contains("#FFFF0000"), "{}", text);
+        assert!(out.warnings.is_empty());
+    }
+
+    #[test]
+    fn xaml_keeps_transform_and_dashes() {
+        // Unlike VectorDrawable, XAML supports transforms and dashes.
+        let doc = parse("width='20' height='20'",
+                        "<path id='p' d='M 0 0 L 10 0' stroke='black' stroke-width='2' \
+                         stroke-dasharray='4 2' transform='scale(2 1)' fill='none'/>");
+        let out = to_xaml(&doc);
+        let text = out.doc.to_string();
+
+        assert!(text.contains("Transform=\"2,0,0,1,0,0\""), "{}", text);
+        assert!(text.contains("Dashes=\"2 1\""), "{}", text);
+        assert!(out.warnings.is_empty());
+
+        let out = to_vector_drawable(&doc);
+        assert_eq!(kinds(&out.warnings), vec![
+            WarningKind::NonUniformStroke,
+            WarningKind::StrokeDasharray,
+        ]);
+    }
+}
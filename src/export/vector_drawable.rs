@@ -0,0 +1,266 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom;
+use svgdom::types::{
+    FuzzyEq,
+    Transform,
+};
+
+use dom;
+
+use math::{
+    Rect,
+};
+
+use super::{
+    add_warning,
+    argb,
+    avg_scale,
+    is_similarity,
+    num,
+    path_data,
+    Output,
+    Warning,
+    WarningKind,
+};
+
+
+struct Context<'a> {
+    doc: &'a dom::Document,
+    new_doc: svgdom::Document,
+    warnings: Vec<Warning>,
+    has_gradients: bool,
+}
+
+/// Exports the document as an Android `VectorDrawable`.
+///
+/// VectorDrawable doesn't support groups opacity, so it will be applied
+/// to the children paint. All transforms are applied to paths coordinates,
+/// because VectorDrawable groups can't represent a skew.
+///
+/// Gradients require API 24.
+pub fn to_vector_drawable(doc: &dom::Document) -> Output {
+    let mut ctx = Context {
+        doc,
+        new_doc: svgdom::Document::new(),
+        warnings: Vec::new(),
+        has_gradients: false,
+    };
+
+    let mut root = ctx.new_doc.create_element("vector");
+    ctx.new_doc.append(&root);
+
+    root.set_attribute(("xmlns:android", "http://schemas.android.com/apk/res/android"));
+    root.set_attribute(("android:width", format!("{}dp", num(doc.size.w))));
+    root.set_attribute(("android:height", format!("{}dp", num(doc.size.h))));
+    root.set_attribute(("android:viewportWidth", num(doc.view_box.w)));
+    root.set_attribute(("android:viewportHeight", num(doc.view_box.h)));
+
+    // VectorDrawable viewport always starts at zero.
+    let mut ts = Transform::default();
+    ts.translate(-doc.view_box.x, -doc.view_box.y);
+
+    conv_elements(&mut ctx, &doc.elements, &ts, 1.0, &mut root);
+
+    if ctx.has_gradients {
+        root.set_attribute(("xmlns:aapt", "http://schemas.android.com/aapt"));
+    }
+
+    Output {
+        doc: ctx.new_doc,
+        warnings: ctx.warnings,
+    }
+}
+
+fn conv_elements(
+    ctx: &mut Context,
+    elements: &[dom::Element],
+    parent_ts: &Transform,
+    opacity: f64,
+    parent: &mut svgdom::Node,
+) {
+    for elem in elements {
+        let mut ts = *parent_ts;
+        ts.append(&elem.transform);
+
+        match elem.data {
+            dom::Type::Path(ref path) => {
+                conv_path(ctx, elem, path, &ts, opacity, parent);
+            }
+            dom::Type::Text(_) => {
+                add_warning(&mut ctx.warnings, WarningKind::Text, elem);
+            }
+            dom::Type::Image(_) => {
+                add_warning(&mut ctx.warnings, WarningKind::Image, elem);
+            }
+            dom::Type::Group(ref g) => {
+                let g_opacity = g.opacity.unwrap_or(1.0);
+                if g_opacity.fuzzy_ne(&1.0) {
+                    add_warning(&mut ctx.warnings, WarningKind::GroupOpacity, elem);
+                }
+
+                conv_elements(ctx, &g.children, &ts, opacity * g_opacity, parent);
+            }
+        }
+    }
+}
+
+fn conv_path(
+    ctx: &mut Context,
+    elem: &dom::Element,
+    path: &dom::Path,
+    ts: &Transform,
+    opacity: f64,
+    parent: &mut svgdom::Node,
+) {
+    let bbox = match path.fill_bbox() {
+        Some(bbox) => bbox,
+        None => return,
+    };
+
+    let mut path_elem = ctx.new_doc.create_element("path");
+    parent.append(&path_elem);
+
+    path_elem.set_attribute(("android:pathData", path_data(&path.d, ts)));
+
+    if let Some(ref fill) = path.fill {
+        match fill.paint {
+            dom::Paint::Color(c) => {
+                path_elem.set_attribute(("android:fillColor", argb(c, 1.0)));
+            }
+            dom::Paint::Link(id) => {
+                conv_gradient(ctx, elem, id, "android:fillColor", &bbox, ts, &mut path_elem);
+            }
+        }
+
+        let alpha = fill.opacity * opacity;
+        if alpha.fuzzy_ne(&1.0) {
+            path_elem.set_attribute(("android:fillAlpha", num(alpha)));
+        }
+
+        if fill.rule == dom::FillRule::EvenOdd {
+            path_elem.set_attribute(("android:fillType", "evenOdd"));
+        }
+    }
+
+    if let Some(ref stroke) = path.stroke {
+        match stroke.paint {
+            dom::Paint::Color(c) => {
+                path_elem.set_attribute(("android:strokeColor", argb(c, 1.0)));
+            }
+            dom::Paint::Link(id) => {
+                conv_gradient(ctx, elem, id, "android:strokeColor", &bbox, ts, &mut path_elem);
+            }
+        }
+
+        let alpha = stroke.opacity * opacity;
+        if alpha.fuzzy_ne(&1.0) {
+            path_elem.set_attribute(("android:strokeAlpha", num(alpha)));
+        }
+
+        if !is_similarity(ts) {
+            add_warning(&mut ctx.warnings, WarningKind::NonUniformStroke, elem);
+        }
+
+        path_elem.set_attribute(("android:strokeWidth", num(stroke.width * avg_scale(ts))));
+
+        let linecap = match stroke.linecap {
+            dom::LineCap::Butt => "butt",
+            dom::LineCap::Round => "round",
+            dom::LineCap::Square => "square",
+        };
+        path_elem.set_attribute(("android:strokeLineCap", linecap));
+
+        let linejoin = match stroke.linejoin {
+            dom::LineJoin::Miter => "miter",
+            dom::LineJoin::Round => "round",
+            dom::LineJoin::Bevel => "bevel",
+        };
+        path_elem.set_attribute(("android:strokeLineJoin", linejoin));
+
+        path_elem.set_attribute(("android:strokeMiterLimit", num(stroke.miterlimit)));
+
+        if stroke.dasharray.is_some() {
+            add_warning(&mut ctx.warnings, WarningKind::StrokeDasharray, elem);
+        }
+    }
+}
+
+fn conv_gradient(
+    ctx: &mut Context,
+    elem: &dom::Element,
+    id: usize,
+    attr_name: &str,
+    bbox: &Rect,
+    ts: &Transform,
+    path_elem: &mut svgdom::Node,
+) {
+    ctx.has_gradients = true;
+
+    let mut attr_elem = ctx.new_doc.create_element("aapt:attr");
+    path_elem.append(&attr_elem);
+    attr_elem.set_attribute(("name", attr_name));
+
+    let mut grad_elem = ctx.new_doc.create_element("gradient");
+    attr_elem.append(&grad_elem);
+
+    let doc = ctx.doc;
+    let base = match doc.get_defs(id).data {
+        dom::RefType::LinearGradient(ref lg) => &lg.d,
+        dom::RefType::RadialGradient(ref rg) => &rg.d,
+    };
+
+    // VectorDrawable gradients are always in the user space and without a transform,
+    // so we have to apply them to the gradient coordinates.
+    let mut grad_ts = *ts;
+    if base.units == dom::GradientUnits::ObjectBoundingBox {
+        grad_ts.append(&Transform::new(bbox.w, 0.0, 0.0, bbox.h, bbox.x, bbox.y));
+    }
+    grad_ts.append(&base.transform);
+
+    if !is_similarity(&grad_ts) {
+        add_warning(&mut ctx.warnings, WarningKind::GradientTransform, elem);
+    }
+
+    match doc.get_defs(id).data {
+        dom::RefType::LinearGradient(ref lg) => {
+            let (x1, y1) = grad_ts.apply(lg.x1, lg.y1);
+            let (x2, y2) = grad_ts.apply(lg.x2, lg.y2);
+
+            grad_elem.set_attribute(("android:type", "linear"));
+            grad_elem.set_attribute(("android:startX", num(x1)));
+            grad_elem.set_attribute(("android:startY", num(y1)));
+            grad_elem.set_attribute(("android:endX", num(x2)));
+            grad_elem.set_attribute(("android:endY", num(y2)));
+        }
+        dom::RefType::RadialGradient(ref rg) => {
+            if rg.fx.fuzzy_ne(&rg.cx) || rg.fy.fuzzy_ne(&rg.cy) {
+                add_warning(&mut ctx.warnings, WarningKind::GradientFocalPoint, elem);
+            }
+
+            let (cx, cy) = grad_ts.apply(rg.cx, rg.cy);
+
+            grad_elem.set_attribute(("android:type", "radial"));
+            grad_elem.set_attribute(("android:centerX", num(cx)));
+            grad_elem.set_attribute(("android:centerY", num(cy)));
+            grad_elem.set_attribute(("android:gradientRadius", num(rg.r * avg_scale(&grad_ts))));
+        }
+    }
+
+    let tile_mode = match base.spread_method {
+        dom::SpreadMethod::Pad => "clamp",
+        dom::SpreadMethod::Reflect => "mirror",
+        dom::SpreadMethod::Repeat => "repeat",
+    };
+    grad_elem.set_attribute(("android:tileMode", tile_mode));
+
+    for stop in &base.stops {
+        let mut item = ctx.new_doc.create_element("item");
+        grad_elem.append(&item);
+
+        item.set_attribute(("android:offset", num(stop.offset)));
+        item.set_attribute(("android:color", argb(stop.color, stop.opacity)));
+    }
+}
@@ -0,0 +1,260 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom;
+use svgdom::types::{
+    FuzzyEq,
+    Transform,
+};
+
+use dom;
+
+use super::{
+    add_warning,
+    argb,
+    num,
+    path_data,
+    Output,
+    Warning,
+    WarningKind,
+};
+
+
+struct Context<'a> {
+    doc: &'a dom::Document,
+    new_doc: svgdom::Document,
+    warnings: Vec<Warning>,
+}
+
+/// Exports the document as a WPF/XAML `DrawingImage`.
+pub fn to_xaml(doc: &dom::Document) -> Output {
+    let mut ctx = Context {
+        doc,
+        new_doc: svgdom::Document::new(),
+        warnings: Vec::new(),
+    };
+
+    let mut root = ctx.new_doc.create_element("DrawingImage");
+    ctx.new_doc.append(&root);
+    root.set_attribute(("xmlns", "http://schemas.microsoft.com/winfx/2006/xaml/presentation"));
+
+    let mut drawing = ctx.new_doc.create_element("DrawingImage.Drawing");
+    root.append(&drawing);
+
+    // Clip the content by the viewbox and move it to the origin.
+    // The 'ClipGeometry' is applied before the 'Transform'.
+    let vb = doc.view_box;
+    let mut group = ctx.new_doc.create_element("DrawingGroup");
+    drawing.append(&group);
+    group.set_attribute(("ClipGeometry", format!("M {},{} H {} V {} H {} Z",
+                                                 num(vb.x), num(vb.y), num(vb.x + vb.w),
+                                                 num(vb.y + vb.h), num(vb.x))));
+
+    let mut ts = Transform::default();
+    ts.translate(-vb.x, -vb.y);
+    if !ts.is_default() {
+        group.set_attribute(("Transform", matrix(&ts)));
+    }
+
+    conv_elements(&mut ctx, &doc.elements, &mut group);
+
+    Output {
+        doc: ctx.new_doc,
+        warnings: ctx.warnings,
+    }
+}
+
+fn conv_elements(ctx: &mut Context, elements: &[dom::Element], parent: &mut svgdom::Node) {
+    for elem in elements {
+        match elem.data {
+            dom::Type::Path(ref path) => {
+                let mut parent = wrap_transform(ctx, elem, parent);
+                conv_path(ctx, path, &mut parent);
+            }
+            dom::Type::Text(_) => {
+                add_warning(&mut ctx.warnings, WarningKind::Text, elem);
+            }
+            dom::Type::Image(_) => {
+                add_warning(&mut ctx.warnings, WarningKind::Image, elem);
+            }
+            dom::Type::Group(ref g) => {
+                let mut g_elem = ctx.new_doc.create_element("DrawingGroup");
+                parent.append(&g_elem);
+
+                if !elem.transform.is_default() {
+                    g_elem.set_attribute(("Transform", matrix(&elem.transform)));
+                }
+
+                if let Some(opacity) = g.opacity {
+                    if opacity.fuzzy_ne(&1.0) {
+                        g_elem.set_attribute(("Opacity", num(opacity)));
+                    }
+                }
+
+                conv_elements(ctx, &g.children, &mut g_elem);
+            }
+        }
+    }
+}
+
+// 'GeometryDrawing' doesn't have a transform, so we have to use a group.
+fn wrap_transform(
+    ctx: &mut Context,
+    elem: &dom::Element,
+    parent: &mut svgdom::Node,
+) -> svgdom::Node {
+    if elem.transform.is_default() {
+        return parent.clone();
+    }
+
+    let mut g_elem = ctx.new_doc.create_element("DrawingGroup");
+    parent.append(&g_elem);
+    g_elem.set_attribute(("Transform", matrix(&elem.transform)));
+
+    g_elem
+}
+
+fn conv_path(ctx: &mut Context, path: &dom::Path, parent: &mut svgdom::Node) {
+    let mut path_elem = ctx.new_doc.create_element("GeometryDrawing");
+    parent.append(&path_elem);
+
+    // 'F0' is an even-odd fill rule and 'F1' is a non-zero one.
+    let rule = match path.fill {
+        Some(ref fill) if fill.rule == dom::FillRule::EvenOdd => "F0",
+        _ => "F1",
+    };
+
+    let data = path_data(&path.d, &Transform::default());
+    path_elem.set_attribute(("Geometry", format!("{} {}", rule, data)));
+
+    if let Some(ref fill) = path.fill {
+        match fill.paint {
+            dom::Paint::Color(c) => {
+                path_elem.set_attribute(("Brush", argb(c, fill.opacity)));
+            }
+            dom::Paint::Link(id) => {
+                let mut brush_elem = ctx.new_doc.create_element("GeometryDrawing.Brush");
+                path_elem.append(&brush_elem);
+                conv_gradient(ctx, id, fill.opacity, &mut brush_elem);
+            }
+        }
+    }
+
+    if let Some(ref stroke) = path.stroke {
+        let mut pen_prop = ctx.new_doc.create_element("GeometryDrawing.Pen");
+        path_elem.append(&pen_prop);
+
+        let mut pen = ctx.new_doc.create_element("Pen");
+        pen_prop.append(&pen);
+
+        match stroke.paint {
+            dom::Paint::Color(c) => {
+                pen.set_attribute(("Brush", argb(c, stroke.opacity)));
+            }
+            dom::Paint::Link(id) => {
+                let mut brush_elem = ctx.new_doc.create_element("Pen.Brush");
+                pen.append(&brush_elem);
+                conv_gradient(ctx, id, stroke.opacity, &mut brush_elem);
+            }
+        }
+
+        pen.set_attribute(("Thickness", num(stroke.width)));
+
+        let linecap = match stroke.linecap {
+            dom::LineCap::Butt => "Flat",
+            dom::LineCap::Round => "Round",
+            dom::LineCap::Square => "Square",
+        };
+        pen.set_attribute(("StartLineCap", linecap));
+        pen.set_attribute(("EndLineCap", linecap));
+        pen.set_attribute(("DashCap", linecap));
+
+        let linejoin = match stroke.linejoin {
+            dom::LineJoin::Miter => "Miter",
+            dom::LineJoin::Round => "Round",
+            dom::LineJoin::Bevel => "Bevel",
+        };
+        pen.set_attribute(("LineJoin", linejoin));
+
+        // WPF miter limit is relative to the half of the stroke width.
+        pen.set_attribute(("MiterLimit", num(stroke.miterlimit * 2.0)));
+
+        if let Some(ref list) = stroke.dasharray {
+            let mut style_prop = ctx.new_doc.create_element("Pen.DashStyle");
+            pen.append(&style_prop);
+
+            let mut style = ctx.new_doc.create_element("DashStyle");
+            style_prop.append(&style);
+
+            // WPF dashes are relative to the stroke width.
+            let dashes: Vec<String> = list.iter().map(|n| num(n / stroke.width)).collect();
+            style.set_attribute(("Dashes", dashes.join(" ")));
+            style.set_attribute(("Offset", num(stroke.dashoffset / stroke.width)));
+        }
+    }
+}
+
+fn conv_gradient(ctx: &mut Context, id: usize, opacity: f64, parent: &mut svgdom::Node) {
+    let doc = ctx.doc;
+    let (mut brush, base) = match doc.get_defs(id).data {
+        dom::RefType::LinearGradient(ref lg) => {
+            let mut brush = ctx.new_doc.create_element("LinearGradientBrush");
+            brush.set_attribute(("StartPoint", format!("{},{}", num(lg.x1), num(lg.y1))));
+            brush.set_attribute(("EndPoint", format!("{},{}", num(lg.x2), num(lg.y2))));
+            (brush, &lg.d)
+        }
+        dom::RefType::RadialGradient(ref rg) => {
+            let mut brush = ctx.new_doc.create_element("RadialGradientBrush");
+            brush.set_attribute(("Center", format!("{},{}", num(rg.cx), num(rg.cy))));
+            brush.set_attribute(("GradientOrigin", format!("{},{}", num(rg.fx), num(rg.fy))));
+            brush.set_attribute(("RadiusX", num(rg.r)));
+            brush.set_attribute(("RadiusY", num(rg.r)));
+            (brush, &rg.d)
+        }
+    };
+    parent.append(&brush);
+
+    match base.units {
+        dom::GradientUnits::UserSpaceOnUse => {
+            brush.set_attribute(("MappingMode", "Absolute"));
+
+            if !base.transform.is_default() {
+                brush.set_attribute(("Transform", matrix(&base.transform)));
+            }
+        }
+        dom::GradientUnits::ObjectBoundingBox => {
+            brush.set_attribute(("MappingMode", "RelativeToBoundingBox"));
+
+            // 'RelativeTransform' is applied in the bounding box space,
+            // just like 'gradientTransform' with 'objectBoundingBox' units.
+            if !base.transform.is_default() {
+                brush.set_attribute(("RelativeTransform", matrix(&base.transform)));
+            }
+        }
+    }
+
+    let spread = match base.spread_method {
+        dom::SpreadMethod::Pad => "Pad",
+        dom::SpreadMethod::Reflect => "Reflect",
+        dom::SpreadMethod::Repeat => "Repeat",
+    };
+    brush.set_attribute(("SpreadMethod", spread));
+
+    if opacity.fuzzy_ne(&1.0) {
+        brush.set_attribute(("Opacity", num(opacity)));
+    }
+
+    for stop in &base.stops {
+        let mut stop_elem = ctx.new_doc.create_element("GradientStop");
+        brush.append(&stop_elem);
+
+        stop_elem.set_attribute(("Offset", num(stop.offset)));
+        stop_elem.set_attribute(("Color", argb(stop.color, stop.opacity)));
+    }
+}
+
+fn matrix(ts: &Transform) -> String {
+    format!("{},{},{},{},{},{}",
+            num(ts.a), num(ts.b), num(ts.c), num(ts.d), num(ts.e), num(ts.f))
+}
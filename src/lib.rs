@@ -20,28 +20,42 @@ And as an embeddable library to paint SVG on an application native canvas.
 pub extern crate svgdom;
 extern crate base64;
 extern crate libflate;
+extern crate simplecss;
+extern crate svgparser;
 #[macro_use] pub extern crate log;
 #[macro_use] extern crate error_chain;
 
 #[cfg(feature = "cairo-backend")] pub extern crate cairo;
 #[cfg(feature = "cairo-backend")] extern crate pango;
 #[cfg(feature = "cairo-backend")] extern crate pangocairo;
-#[cfg(feature = "cairo-backend")] extern crate image;
+#[cfg(any(feature = "cairo-backend", feature = "pdf-backend"))] extern crate image;
 
 #[cfg(feature = "qt-backend")] pub extern crate resvg_qt as qt;
 
 
+// Should be declared first, so the 'diag!' macro will be available in other modules.
+#[macro_use] mod diagnostics;
+
 #[cfg(feature = "cairo-backend")] pub mod render_cairo;
 #[cfg(feature = "qt-backend")] pub mod render_qt;
+#[cfg(feature = "pdf-backend")] pub mod render_pdf;
+pub mod export;
+pub mod dom;
 
+mod buffer;
 mod math;
 mod convert;
-mod dom;
+mod css;
 mod error;
+mod loader;
 mod options;
 mod preproc;
+mod progress;
 mod render_utils;
+mod source;
+mod tiles;
 mod traits;
+#[cfg(test)] mod test_utils;
 
 
 use std::fs;
@@ -56,20 +70,49 @@ pub use svgdom::{
     ChainedErrorExt,
 };
 
+pub use buffer::{
+    PixelFormat,
+};
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+pub use buffer::{
+    render_to_buffer,
+};
+pub use diagnostics::{
+    Diagnostic,
+    DiagnosticCode,
+    Severity,
+};
 pub use error::{
     Error,
     ErrorKind,
     Result,
 };
+pub use loader::{
+    FileLoader,
+    ResourceLoader,
+    ResourcePolicy,
+};
 pub use options::{
     FitTo,
+    Limits,
     Options,
 };
+pub use progress::{
+    CancellationToken,
+    ProgressCallback,
+};
 pub use dom::{
     Document,
+    ExportOptions,
 };
 pub use math::{
+    Point,
     Rect,
+    Size,
+};
+pub use tiles::{
+    Tile,
+    Tiles,
 };
 
 /// Shorthand names for modules.
@@ -88,45 +131,161 @@ use preproc::{
 
 /// Creates `Document` from SVG data.
 pub fn parse_doc_from_data(text: &str, opt: &Options) -> Result<dom::Document> {
-    let mut doc = parse_svg(text)?;
-    prepare_doc(&mut doc, opt)?;
-    let re_doc = convert_doc(&doc, opt)?;
+    diagnostics::with_sink(opt.log_diagnostics, false, || parse_doc(text, opt)).0
+}
 
-    Ok(re_doc)
+/// Creates `Document` from SVG data and returns all the parsing diagnostics.
+///
+/// Diagnostics are passed to the `log` crate too, unless `Options::log_diagnostics` is disabled.
+pub fn parse_doc_from_data_with_diagnostics(
+    text: &str,
+    opt: &Options,
+) -> Result<(dom::Document, Vec<Diagnostic>)> {
+    let (doc, list) = diagnostics::with_sink(opt.log_diagnostics, true, || parse_doc(text, opt));
+    Ok((doc?, list))
+}
+
+/// Creates `Document` from raw SVG data.
+///
+/// Unlike `parse_doc_from_data`, SVGZ data and UTF-16 text are supported too.
+/// Both are detected from the content.
+pub fn parse_doc_from_bytes(data: &[u8], opt: &Options) -> Result<dom::Document> {
+    let text = decode_data(data.to_vec(), opt)?;
+    parse_doc_from_data(&text, opt)
+}
+
+/// Creates `Document` from a reader.
+///
+/// The whole content is read before parsing. See `parse_doc_from_bytes` for details.
+pub fn parse_doc_from_reader<R: Read>(mut reader: R, opt: &Options) -> Result<dom::Document> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let text = decode_data(data, opt)?;
+    parse_doc_from_data(&text, opt)
 }
 
 /// Creates `Document` from file.
 ///
-/// `.svg` and `.svgz` files are supported.
+/// SVG and SVGZ files are supported. The format is detected from the content,
+/// so the file extension doesn't matter.
 pub fn parse_doc_from_file<P: AsRef<Path>>(path: P, opt: &Options) -> Result<dom::Document> {
-    let text = load_file(path.as_ref())?;
-    let mut doc = parse_svg(&text)?;
-    prepare_doc(&mut doc, opt)?;
-    let re_doc = convert_doc(&doc, opt)?;
+    let text = load_file(path.as_ref(), opt)?;
+    parse_doc_from_data(&text, opt)
+}
+
+/// Creates `Document` from file and returns all the parsing diagnostics.
+///
+/// SVG and SVGZ files are supported.
+pub fn parse_doc_from_file_with_diagnostics<P: AsRef<Path>>(
+    path: P,
+    opt: &Options,
+) -> Result<(dom::Document, Vec<Diagnostic>)> {
+    let text = load_file(path.as_ref(), opt)?;
+    parse_doc_from_data_with_diagnostics(&text, opt)
+}
+
+// Diagnostics are reported to the current sink,
+// so SVG images are sharing it with the main document.
+fn parse_doc(text: &str, opt: &Options) -> Result<dom::Document> {
+    let mut doc = parse_svg(text)?;
+    progress::check_cancelled(opt)?;
+
+    let content_size = prepare_doc(&mut doc, opt)?;
+    progress::check_cancelled(opt)?;
+
+    let re_doc = convert_doc(&doc, content_size.as_ref(), opt)?;
+    progress::check_cancelled(opt)?;
 
     Ok(re_doc)
 }
 
-fn load_file(path: &Path) -> Result<String> {
+/// Creates `Document` for an SVG image.
+fn parse_image_doc(data: Vec<u8>, opt: &Options) -> Result<dom::Document> {
+    let text = decode_data(data, opt)?;
+    parse_doc(&text, opt)
+}
+
+fn load_file(path: &Path, opt: &Options) -> Result<String> {
     let mut file = fs::File::open(path)?;
     let length = file.metadata()?.len() as usize;
 
-    // 'unwrap' is safe because we already checked the extension via 'clap'.
-    let ext = Path::new(path).extension().unwrap().to_str().unwrap().to_lowercase();
+    let mut data = Vec::with_capacity(length + 1);
+    file.read_to_end(&mut data)?;
+
+    decode_data(data, opt)
+}
+
+// SVGZ is detected using the gzip signature and not the file extension,
+// because embedded images and readers don't have one.
+fn decode_data(data: Vec<u8>, opt: &Options) -> Result<String> {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let limit = opt.limits.max_decompressed_size;
 
-    let s = if ext == "svgz" {
-        let mut decoder = libflate::gzip::Decoder::new(&file)?;
+        // Read one byte more than allowed to detect an overflow.
+        let decoder = libflate::gzip::Decoder::new(&data[..])?;
         let mut decoded = Vec::new();
-        decoder.read_to_end(&mut decoded)?;
+        decoder.take(limit as u64 + 1).read_to_end(&mut decoded)?;
+
+        if decoded.len() > limit {
+            return Err(ErrorKind::DecompressedSizeTooLarge(limit).into());
+        }
 
-        String::from_utf8(decoded)?
+        decode_text(decoded)
     } else {
-        let mut s = String::with_capacity(length + 1);
-        file.read_to_string(&mut s)?;
-        s
+        decode_text(data)
+    }
+}
+
+/// Converts XML data into a string.
+///
+/// The encoding is detected using the byte order mark or the first characters
+/// of the XML declaration, like described in the XML spec, appendix F.
+/// Only UTF-8 and UTF-16 are supported.
+fn decode_text(mut data: Vec<u8>) -> Result<String> {
+    if data.starts_with(&[0xef, 0xbb, 0xbf]) {
+        data.drain(..3);
+        return Ok(String::from_utf8(data)?);
+    }
+
+    if data.starts_with(&[0xff, 0xfe]) {
+        return decode_utf16(&data[2..], false);
+    } else if data.starts_with(&[0xfe, 0xff]) {
+        return decode_utf16(&data[2..], true);
+    } else if data.starts_with(&[b'<', 0, b'?', 0]) {
+        return decode_utf16(&data, false);
+    } else if data.starts_with(&[0, b'<', 0, b'?']) {
+        return decode_utf16(&data, true);
+    }
+
+    Ok(String::from_utf8(data)?)
+}
+
+fn decode_utf16(data: &[u8], big_endian: bool) -> Result<String> {
+    let chunks = data.chunks_exact(2);
+    if !chunks.remainder().is_empty() {
+        return Err(ErrorKind::InvalidUtf16.into());
+    }
+
+    let units: Vec<u16> = chunks.map(|c| {
+        if big_endian {
+            (c[0] as u16) << 8 | c[1] as u16
+        } else {
+            (c[1] as u16) << 8 | c[0] as u16
+        }
+    }).collect();
+
+    let mut text = match String::from_utf16(&units) {
+        Ok(text) => text,
+        Err(_) => return Err(ErrorKind::InvalidUtf16.into()),
     };
 
-    Ok(s)
+    // A BOM can be encoded twice.
+    if text.starts_with('\u{feff}') {
+        text.remove(0);
+    }
+
+    Ok(text)
 }
 
 fn parse_svg(text: &str) -> Result<svgdom::Document> {
@@ -139,17 +298,116 @@ fn parse_svg(text: &str) -> Result<svgdom::Document> {
         skip_invalid_attributes: true,
         skip_invalid_css: true,
         skip_paint_fallback: true,
-        .. svgdom::ParseOptions::default()
+        // Classes are matched by `preproc::resolve_css`.
+        skip_unresolved_classes: false,
     };
 
-    let doc = svgdom::Document::from_str_with_opt(&text, &opt)?;
+    let mut doc = svgdom::Document::from_str_with_opt(text, &opt)?;
+    source::restore(text, &mut doc);
     Ok(doc)
 }
 
-fn prepare_doc(doc: &mut svgdom::Document, opt: &Options) -> Result<()> {
+fn prepare_doc(doc: &mut svgdom::Document, opt: &Options) -> Result<Option<preproc::ContentSize>> {
     preproc::prepare_doc(doc, opt)
 }
 
-fn convert_doc(doc: &svgdom::Document, opt: &Options) -> Result<dom::Document> {
-    convert::convert_doc(doc, opt)
+fn convert_doc(
+    doc: &svgdom::Document,
+    content_size: Option<&preproc::ContentSize>,
+    opt: &Options,
+) -> Result<dom::Document> {
+    convert::convert_doc(doc, content_size, opt)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::io::Write;
+
+    use super::*;
+    use test_utils::svg;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16().flat_map(|c| {
+            let [a, b] = if big_endian { c.to_be_bytes() } else { c.to_le_bytes() };
+            vec![a, b]
+        }).collect()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = libflate::gzip::Encoder::new(Vec::new()).unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().into_result().unwrap()
+    }
+
+    #[test]
+    fn utf8() {
+        assert_eq!(decode_text(b"<svg/>".to_vec()).unwrap(), "<svg/>");
+        assert_eq!(decode_text(b"\xEF\xBB\xBF<svg/>".to_vec()).unwrap(), "<svg/>");
+        assert!(decode_text(b"<svg>\xFF</svg>".to_vec()).is_err());
+    }
+
+    #[test]
+    fn utf16_bom() {
+        let mut data = vec![0xff, 0xfe];
+        data.extend(utf16("<svg/>", false));
+        assert_eq!(decode_text(data).unwrap(), "<svg/>");
+
+        let mut data = vec![0xfe, 0xff];
+        data.extend(utf16("<svg/>", true));
+        assert_eq!(decode_text(data).unwrap(), "<svg/>");
+    }
+
+    #[test]
+    fn utf16_declaration() {
+        let text = "<?xml version='1.0' encoding='UTF-16'?><svg/>";
+        assert_eq!(decode_text(utf16(text, false)).unwrap(), text);
+        assert_eq!(decode_text(utf16(text, true)).unwrap(), text);
+    }
+
+    #[test]
+    fn utf16_errors() {
+        // An odd length.
+        let mut data = vec![0xff, 0xfe];
+        data.extend(utf16("<svg/>", false));
+        data.push(0);
+        match decode_text(data) {
+            Err(Error(ErrorKind::InvalidUtf16, _)) => {}
+            _ => panic!("InvalidUtf16 expected"),
+        }
+
+        // An unpaired surrogate.
+        match decode_text(vec![0xff, 0xfe, 0x00, 0xd8, b'<', 0]) {
+            Err(Error(ErrorKind::InvalidUtf16, _)) => {}
+            _ => panic!("InvalidUtf16 expected"),
+        }
+    }
+
+    #[test]
+    fn from_bytes() {
+        let text = svg("width='10' height='20'", "");
+
+        let doc = parse_doc_from_bytes(text.as_bytes(), &Options::default()).unwrap();
+        assert_eq!(doc.size, Size::new(10.0, 20.0));
+
+        let doc = parse_doc_from_bytes(&gzip(text.as_bytes()), &Options::default()).unwrap();
+        assert_eq!(doc.size, Size::new(10.0, 20.0));
+
+        let mut data = vec![0xff, 0xfe];
+        data.extend(utf16(&text, false));
+        let doc = parse_doc_from_reader(&data[..], &Options::default()).unwrap();
+        assert_eq!(doc.size, Size::new(10.0, 20.0));
+    }
+
+    #[test]
+    fn file_without_extension() {
+        let text = svg("width='10' height='20'", "");
+        let path = env::temp_dir().join(format!("resvg-test-{}", ::std::process::id()));
+        fs::write(&path, gzip(text.as_bytes())).unwrap();
+        let res = parse_doc_from_file(&path, &Options::default());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(res.unwrap().size, Size::new(10.0, 20.0));
+    }
 }
@@ -0,0 +1,156 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{
+    Component,
+    Path,
+    PathBuf,
+};
+
+
+/// An external resources loader.
+///
+/// Resolves resources referenced by SVG into bytes.
+/// Can be used to load resources from a database or an archive instead of a filesystem.
+///
+/// Currently, only linked images are loaded this way.
+/// Embedded `data:` URLs are decoded without a loader.
+///
+/// A link is resolved into a path first, which is checked using `Options::policy`
+/// and is used to detect recursive SVG images. The resource is loaded afterwards.
+pub trait ResourceLoader: Send + Sync {
+    /// Resolves a link into a resource path.
+    ///
+    /// `href` is an original `xlink:href` value and `base` is the parent document path,
+    /// which is `Options::path` for the main document.
+    ///
+    /// The path is not required to exist on a filesystem.
+    fn resolve(&self, href: &str, base: Option<&Path>) -> PathBuf;
+
+    /// Loads a resource.
+    ///
+    /// `href` is an original `xlink:href` value and `path` is a path returned by `resolve`.
+    /// With `ResourcePolicy::Restricted`, it's the canonical path that passed the check.
+    ///
+    /// An error is reported as a warning and the resource is skipped.
+    fn load(&self, href: &str, path: &Path) -> io::Result<Vec<u8>>;
+}
+
+/// A filesystem resources loader.
+///
+/// Links are resolved relative to the parent document directory.
+///
+/// Used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileLoader;
+
+impl ResourceLoader for FileLoader {
+    fn resolve(&self, href: &str, base: Option<&Path>) -> PathBuf {
+        match base.and_then(|p| p.parent()) {
+            Some(dir) => dir.join(href),
+            None => PathBuf::from(href),
+        }
+    }
+
+    fn load(&self, _href: &str, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+}
+
+/// An external resources access policy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourcePolicy {
+    /// All external resources are allowed.
+    Allowed,
+    /// Only resources inside the specified directory are allowed.
+    ///
+    /// Paths are canonicalized before the check, so `..` and symlinks
+    /// can't be used to escape the directory.
+    Restricted(PathBuf),
+    /// External resources are disabled. Only embedded images are loaded.
+    Disabled,
+}
+
+impl ResourcePolicy {
+    /// Checks that the resource with the specified path can be loaded.
+    pub fn is_allowed(&self, path: &Path) -> bool {
+        self.check(path).is_some()
+    }
+
+    /// Checks that the resource with the specified path can be loaded
+    /// and returns a path that should be used for loading.
+    ///
+    /// For `Restricted`, it's the canonical path that passed the check,
+    /// so the original path can't be redirected after the check.
+    pub fn check(&self, path: &Path) -> Option<PathBuf> {
+        match *self {
+            ResourcePolicy::Allowed => Some(path.to_owned()),
+            ResourcePolicy::Restricted(ref root) => {
+                let root = resolve_path(root)?;
+                let path = resolve_path(path)?;
+                if path.starts_with(root) { Some(path) } else { None }
+            }
+            ResourcePolicy::Disabled => None,
+        }
+    }
+}
+
+// Paths that don't exist on a filesystem, like ones used by custom loaders,
+// are normalized lexically.
+fn resolve_path(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = fs::canonicalize(path) {
+        return Some(path);
+    }
+
+    let path = env::current_dir().ok()?.join(path);
+
+    let mut resolved = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            _ => resolved.push(c.as_os_str()),
+        }
+    }
+
+    Some(resolved)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restricted_returns_resolved_path() {
+        let policy = ResourcePolicy::Restricted(PathBuf::from("/resvg-missing/images"));
+
+        assert_eq!(policy.check(Path::new("/resvg-missing/images/icons/../a.png")),
+                   Some(PathBuf::from("/resvg-missing/images/a.png")));
+        assert_eq!(policy.check(Path::new("/resvg-missing/images/../a.png")), None);
+        assert_eq!(policy.check(Path::new("/resvg-missing/images-2/a.png")), None);
+    }
+
+    #[test]
+    fn file_loader_resolve() {
+        let base = Path::new("/docs/image.svg");
+        assert_eq!(FileLoader.resolve("img/a.png", Some(base)), PathBuf::from("/docs/img/a.png"));
+        assert_eq!(FileLoader.resolve("/abs/a.png", Some(base)), PathBuf::from("/abs/a.png"));
+        assert_eq!(FileLoader.resolve("a.png", None), PathBuf::from("a.png"));
+    }
+
+    #[test]
+    fn allowed_keeps_path() {
+        let path = Path::new("images/../a.png");
+        assert_eq!(ResourcePolicy::Allowed.check(path), Some(path.to_owned()));
+        assert_eq!(ResourcePolicy::Disabled.check(path), None);
+    }
+}
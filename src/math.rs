@@ -2,6 +2,10 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use svgdom::types::{
+    Transform,
+};
+
 /// Bounds `f64` number.
 #[inline]
 pub fn f64_bound(min: f64, val: f64, max: f64) -> f64 {
@@ -56,6 +60,7 @@ impl Line {
 
 /// Point representation.
 #[derive(Debug, PartialEq, Copy, Clone)]
+#[allow(missing_docs)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -71,6 +76,7 @@ impl Default for Point {
 }
 
 impl Point {
+    /// Creates a new `Point`.
     pub fn new(x: f64, y: f64) -> Point {
         Point {
             x,
@@ -153,4 +159,46 @@ impl Rect {
             h: self.h,
         }
     }
+
+    /// Returns the smallest rect that contains both rects.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x1 = self.x.min(other.x);
+        let y1 = self.y.min(other.y);
+        let x2 = (self.x + self.w).max(other.x + other.w);
+        let y2 = (self.y + self.h).max(other.y + other.h);
+
+        Rect::new(x1, y1, x2 - x1, y2 - y1)
+    }
+
+    /// Returns the rect extended by the specified value on each side.
+    pub fn expand(&self, n: f64) -> Rect {
+        Rect::new(self.x - n, self.y - n, self.w + n * 2.0, self.h + n * 2.0)
+    }
+
+    /// Returns the bounding box of the transformed rect.
+    pub fn transform(&self, ts: &Transform) -> Rect {
+        if ts.is_default() {
+            return *self;
+        }
+
+        let points = [
+            ts.apply(self.x, self.y),
+            ts.apply(self.x + self.w, self.y),
+            ts.apply(self.x + self.w, self.y + self.h),
+            ts.apply(self.x, self.y + self.h),
+        ];
+
+        let mut x1 = points[0].0;
+        let mut y1 = points[0].1;
+        let mut x2 = x1;
+        let mut y2 = y1;
+        for &(x, y) in &points[1..] {
+            x1 = x1.min(x);
+            y1 = y1.min(y);
+            x2 = x2.max(x);
+            y2 = y2.max(y);
+        }
+
+        Rect::new(x1, y1, x2 - x1, y2 - y1)
+    }
 }
\ No newline at end of file
@@ -3,11 +3,21 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use svgdom::types::{
     Color,
 };
 
+use progress::{
+    ProgressCallback,
+};
+use loader::{
+    FileLoader,
+    ResourceLoader,
+    ResourcePolicy,
+};
+
 
 /// Image fit options.
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -22,7 +32,52 @@ pub enum FitTo {
     Zoom(f32),
 }
 
+/// Resource limits.
+///
+/// Used to protect against malicious files.
+/// Parsing or rendering fails with a dedicated `ErrorKind` when a limit is reached.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Limits {
+    /// Maximum number of nodes after `use` elements resolving.
+    ///
+    /// Prevents an exponential growth via nested `use` elements.
+    ///
+    /// Default: 1 000 000
+    pub max_elements: usize,
+    /// Maximum nodes nesting depth.
+    ///
+    /// Default: 1024
+    pub max_depth: usize,
+    /// Maximum size of a decompressed SVGZ data in bytes.
+    ///
+    /// Default: 100 MiB
+    pub max_decompressed_size: usize,
+    /// Maximum size of a `data:` URL in bytes.
+    ///
+    /// Default: 50 MiB
+    pub max_data_url_size: usize,
+    /// Maximum number of pixels of a canvas created by a raster backend.
+    ///
+    /// Doesn't affect rendering to canvas.
+    ///
+    /// Default: 16384 x 16384
+    pub max_canvas_pixels: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_elements: 1_000_000,
+            max_depth: 1024,
+            max_decompressed_size: 100 * 1024 * 1024,
+            max_data_url_size: 50 * 1024 * 1024,
+            max_canvas_pixels: 16384 * 16384,
+        }
+    }
+}
+
 /// Rendering options.
+#[derive(Clone)]
 pub struct Options {
     /// SVG image path.
     ///
@@ -42,6 +97,64 @@ pub struct Options {
     ///
     /// `None` equals to transparent.
     pub background: Option<Color>,
+    /// Number of rendering threads.
+    ///
+    /// When bigger than 1, the image is split into horizontal bands,
+    /// which are rendered in parallel. The result is identical to a serial rendering.
+    ///
+    /// Affects only rendering to image and to buffer with raster backends.
+    pub threads: usize,
+    /// External resources loader.
+    ///
+    /// Default: `FileLoader`.
+    pub loader: Arc<dyn ResourceLoader>,
+    /// External resources access policy.
+    ///
+    /// Blocked resources are skipped with a warning.
+    ///
+    /// Default: `ResourcePolicy::Allowed`.
+    pub policy: ResourcePolicy,
+    /// Resource limits.
+    pub limits: Limits,
+    /// Passes parsing diagnostics to the `log` crate.
+    ///
+    /// Default: `true`
+    pub log_diagnostics: bool,
+    /// Progress and cancellation callback.
+    ///
+    /// Affects parsing and rendering to image, buffer and PDF.
+    /// Rendering to canvas can't be cancelled.
+    ///
+    /// Default: `None`
+    pub progress: Option<Arc<dyn ProgressCallback>>,
+    /// A user style sheet.
+    ///
+    /// Applied on top of document style sheets and `style` attributes,
+    /// unless a document declaration is marked as `!important`.
+    /// Isn't applied to SVG images.
+    ///
+    /// Default: `None`
+    pub style_sheet: Option<String>,
+    /// Overrides the `currentColor` value.
+    ///
+    /// By default, the `color` attribute of an element or its parent is used.
+    ///
+    /// Default: `None`
+    pub current_color: Option<Color>,
+    /// User languages in the BCP 47 format, like `en-US`.
+    ///
+    /// Used to evaluate the `systemLanguage` attribute.
+    /// A language matches an attribute language when it's equal to it
+    /// or to its prefix, so `en` matches `en-US`.
+    ///
+    /// Default: `["en"]`
+    pub languages: Vec<String>,
+    /// Supported extensions URIs.
+    ///
+    /// Used to evaluate the `requiredExtensions` attribute.
+    ///
+    /// Default: empty
+    pub extensions: Vec<String>,
 }
 
 impl Default for Options {
@@ -51,6 +164,16 @@ impl Default for Options {
             dpi: 96.0,
             fit_to: FitTo::Original,
             background: None,
+            threads: 1,
+            loader: Arc::new(FileLoader),
+            policy: ResourcePolicy::Allowed,
+            limits: Limits::default(),
+            log_diagnostics: true,
+            progress: None,
+            style_sheet: None,
+            current_color: None,
+            languages: vec!["en".to_string()],
+            extensions: Vec::new(),
         }
     }
 }
@@ -0,0 +1,156 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom::{
+    Document,
+    Node,
+    NodeEdge,
+};
+
+use short::{
+    AId,
+    AValue,
+};
+
+use {
+    ErrorKind,
+    Limits,
+    Result,
+};
+
+
+/// Checks that nodes nesting doesn't exceed the limit.
+///
+/// Most of the preprocessing steps are recursive, so a deep tree can overflow the stack.
+pub fn check_nesting(svg: &Node, limits: &Limits) -> Result<()> {
+    let mut depth = 0;
+    for edge in svg.traverse() {
+        match edge {
+            NodeEdge::Start(_) => {
+                depth += 1;
+                if depth > limits.max_depth {
+                    return Err(ErrorKind::NestingTooDeep(limits.max_depth).into());
+                }
+            }
+            NodeEdge::End(_) => {
+                depth -= 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that embedded images don't exceed the limit.
+pub fn check_data_urls(doc: &Document, limits: &Limits) -> Result<()> {
+    for node in doc.descendants() {
+        let attrs = node.attributes();
+        if let Some(AValue::String(href)) = attrs.get_value(AId::XlinkHref) {
+            if href.starts_with("data:") && href.len() > limits.max_data_url_size {
+                return Err(ErrorKind::DataUrlTooLarge(limits.max_data_url_size).into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    use libflate;
+
+    use test_utils::{
+        parse_with,
+        svg,
+    };
+    use {
+        parse_doc_from_file,
+        Error,
+        ErrorKind,
+        Limits,
+        Options,
+    };
+
+    fn opt(limits: Limits) -> Options {
+        Options {
+            limits,
+            .. Options::default()
+        }
+    }
+
+    #[test]
+    fn use_bomb() {
+        // Each level doubles the number of elements.
+        let mut content = String::from("<rect id='e0' width='1' height='1'/>");
+        for i in 1..20 {
+            content += &format!("<g id='e{}'><use xlink:href='#e{}'/><use xlink:href='#e{}'/></g>",
+                                i, i - 1, i - 1);
+        }
+
+        let limits = Limits { max_elements: 1000, .. Limits::default() };
+        match parse_with("width='10' height='10'", &content, &opt(limits)) {
+            Err(Error(ErrorKind::TooManyElements(1000), _)) => {}
+            _ => panic!("TooManyElements expected"),
+        }
+    }
+
+    #[test]
+    fn nesting() {
+        let content = format!("{}<rect width='1' height='1'/>{}", "<g>".repeat(20), "</g>".repeat(20));
+
+        let limits = Limits { max_depth: 10, .. Limits::default() };
+        match parse_with("width='10' height='10'", &content, &opt(limits)) {
+            Err(Error(ErrorKind::NestingTooDeep(10), _)) => {}
+            _ => panic!("NestingTooDeep expected"),
+        }
+
+        let limits = Limits { max_depth: 30, .. Limits::default() };
+        assert!(parse_with("width='10' height='10'", &content, &opt(limits)).is_ok());
+    }
+
+    #[test]
+    fn data_url() {
+        let href = format!("data:image/png;base64,{}", "A".repeat(100));
+        let content = format!("<image width='1' height='1' xlink:href='{}'/>", href);
+
+        let limits = Limits { max_data_url_size: 50, .. Limits::default() };
+        match parse_with("width='10' height='10'", &content, &opt(limits)) {
+            Err(Error(ErrorKind::DataUrlTooLarge(50), _)) => {}
+            _ => panic!("DataUrlTooLarge expected"),
+        }
+
+        let limits = Limits { max_data_url_size: href.len(), .. Limits::default() };
+        assert!(parse_with("width='10' height='10'", &content, &opt(limits)).is_ok());
+    }
+
+    #[test]
+    fn decompressed_size() {
+        let text = svg("width='10' height='10'", &" ".repeat(1000));
+        let mut encoder = libflate::gzip::Encoder::new(Vec::new()).unwrap();
+        encoder.write_all(text.as_bytes()).unwrap();
+        let data = encoder.finish().into_result().unwrap();
+        // Gzip bombs are much smaller than the decompressed data.
+        assert!(data.len() < 200);
+
+        let path = env::temp_dir().join(format!("resvg-limits-{}.svgz", ::std::process::id()));
+        fs::write(&path, data).unwrap();
+
+        let limits = Limits { max_decompressed_size: 500, .. Limits::default() };
+        let small = parse_doc_from_file(&path, &opt(limits));
+        let limits = Limits { max_decompressed_size: text.len(), .. Limits::default() };
+        let exact = parse_doc_from_file(&path, &opt(limits));
+        fs::remove_file(&path).unwrap();
+
+        match small {
+            Err(Error(ErrorKind::DecompressedSizeTooLarge(500), _)) => {}
+            _ => panic!("DecompressedSizeTooLarge expected"),
+        }
+        assert!(exact.is_ok());
+    }
+}
@@ -81,7 +81,8 @@ pub fn convert_units(svg: &mut Node, opt: &Options) {
         let font_size = match node.find_attribute(AId::FontSize) {
             Some(v) => v,
             None => {
-                warn!("'font-size' must be resolved before units conversion.");
+                diag!(Info, InvalidAttribute, node.id(),
+                      "'font-size' must be resolved before units conversion.");
                 DEFAULT_FONT_SIZE
             }
         };
@@ -125,16 +126,26 @@ pub fn convert_units(svg: &mut Node, opt: &Options) {
 
 fn convert_font_size(svg: &Node, dpi: f64) {
     for (_, mut node) in svg.descendants().svg() {
-        let mut attrs = node.attributes_mut();
-
-        if let Some(attr) = attrs.get_mut(AId::FontSize) {
-            if let AValue::Length(len) = attr.value {
-                let n = convert(len, 0.0, dpi);
-                attr.value = AValue::Number(n);
-            } else {
-                warn!("'font-size' should have a Length type.");
-                attr.value = AValue::Number(DEFAULT_FONT_SIZE);
+        let is_valid = {
+            let mut attrs = node.attributes_mut();
+
+            match attrs.get_mut(AId::FontSize) {
+                Some(attr) => {
+                    if let AValue::Length(len) = attr.value {
+                        let n = convert(len, 0.0, dpi);
+                        attr.value = AValue::Number(n);
+                        true
+                    } else {
+                        attr.value = AValue::Number(DEFAULT_FONT_SIZE);
+                        false
+                    }
+                }
+                None => true,
             }
+        };
+
+        if !is_valid {
+            diag!(Info, InvalidAttribute, node.id(), "'font-size' should have a Length type.");
         }
     }
 }
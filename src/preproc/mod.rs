@@ -10,10 +10,14 @@ use {
     Result,
 };
 
+use progress::check_cancelled;
+
+mod check_limits;
 mod conv_units;
 mod prepare_text_decoration;
 mod prepare_text_nodes;
 mod regroup;
+mod resolve_css;
 mod resolve_curr_color;
 mod resolve_font_size;
 mod resolve_gradient_attrs;
@@ -32,10 +36,12 @@ mod ungroup_groups;
 mod ungroup_switch;
 mod resolve_visibility;
 
+use self::check_limits::*;
 use self::conv_units::convert_units;
 use self::prepare_text_decoration::prepare_text_decoration;
 use self::prepare_text_nodes::prepare_text_nodes;
 use self::regroup::regroup_elements;
+use self::resolve_css::resolve_css;
 use self::resolve_curr_color::resolve_current_color;
 use self::resolve_font_size::resolve_font_size;
 use self::resolve_gradient_attrs::*;
@@ -54,13 +60,15 @@ use self::ungroup_groups::ungroup_groups;
 use self::ungroup_switch::ungroup_switch;
 use self::resolve_visibility::resolve_visibility;
 
+pub use self::resolve_svg_size::ContentSize;
+
 
 // Default font is user-agent dependent so we can use whatever we like.
 pub const DEFAULT_FONT_FAMILY: &str = "Times New Roman";
 pub const DEFAULT_FONT_SIZE: f64 = 12.0;
 
 
-pub fn prepare_doc(doc: &mut svgdom::Document, opt: &Options) -> Result<()> {
+pub fn prepare_doc(doc: &mut svgdom::Document, opt: &Options) -> Result<Option<ContentSize>> {
     let mut svg = if let Some(svg) = doc.svg_element() {
         svg
     } else {
@@ -71,15 +79,23 @@ pub fn prepare_doc(doc: &mut svgdom::Document, opt: &Options) -> Result<()> {
 
     let svg = &mut svg;
 
-    // Detect image size. If it failed there is no point in continuing.
-    if !resolve_svg_size(svg) {
-        return Err(ErrorKind::SizeDeterminationUnsupported.into());
-    }
+    check_data_urls(doc, &opt.limits)?;
+    check_cancelled(opt)?;
+
+    // Detect image size. If it failed, it will be detected after the conversion.
+    let content_size = resolve_svg_size(svg);
 
     // TODO: remove duplicated defs
 
+    // Style sheets override presentation attributes, so they should be resolved first.
+    resolve_css(doc, opt);
+
     resolve_inherit(doc);
-    resolve_current_color(doc);
+    resolve_current_color(doc, opt.current_color);
+
+    // `resolve_font_size` and `remove_unused_defs` are recursive,
+    // so a deep tree should be rejected before them.
+    check_nesting(svg, &opt.limits)?;
 
     resolve_font_size(doc);
 
@@ -94,7 +110,10 @@ pub fn prepare_doc(doc: &mut svgdom::Document, opt: &Options) -> Result<()> {
 
     // 'use' should be resolved before style attributes,
     // because 'use' can propagate own style.
-    resolve_use(doc);
+    resolve_use(doc, &opt.limits)?;
+    // `use` instances can make the tree deeper.
+    check_nesting(svg, &opt.limits)?;
+    check_cancelled(opt)?;
 
     ungroup_a(doc);
 
@@ -103,8 +122,9 @@ pub fn prepare_doc(doc: &mut svgdom::Document, opt: &Options) -> Result<()> {
     resolve_style_attributes(doc);
 
     resolve_tref(doc);
+    check_cancelled(opt)?;
 
-    ungroup_switch(doc);
+    ungroup_switch(doc, opt);
 
     remove_invalid_transform(doc);
     remove_invisible_elements(doc);
@@ -114,5 +134,5 @@ pub fn prepare_doc(doc: &mut svgdom::Document, opt: &Options) -> Result<()> {
 
     prepare_text_nodes(doc);
 
-    Ok(())
+    Ok(content_size)
 }
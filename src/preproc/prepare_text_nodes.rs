@@ -6,13 +6,19 @@ use svgdom::{
     Document,
     Node,
     NodeType,
+    ValueId,
 };
 
 use short::{
     AId,
+    AValue,
     EId,
 };
 
+use traits::{
+    GetValue,
+};
+
 
 pub fn prepare_text_nodes(doc: &mut Document) {
     let mut rm_nodes = Vec::new();
@@ -22,6 +28,8 @@ pub fn prepare_text_nodes(doc: &mut Document) {
             continue;
         }
 
+        process_whitespace(&node);
+
         let mut new_text_elem = doc.create_element(EId::Text);
         prepare_text_elem(doc, &node, &mut new_text_elem);
 
@@ -44,6 +52,93 @@ pub fn prepare_text_nodes(doc: &mut Document) {
     }
 }
 
+/// Processes whitespace of a `text` element.
+///
+/// 'When xml:space="default", the SVG user agent will do the following using a copy
+/// of the original character data content. First, it will remove all newline characters.
+/// Then it will convert all tab characters into space characters. Then, it will strip off
+/// all leading and trailing space characters. Then, all contiguous space characters
+/// will be consolidated.'
+///
+/// 'When xml:space="preserve", the SVG user agent will do the following using a copy
+/// of the original character data content. It will convert all newline and tab characters
+/// into space characters. Then, it will draw all space characters,
+/// including leading, trailing and multiple contiguous space characters.'
+///
+/// Newlines and tabs are already converted into spaces during parsing, so, like in browsers,
+/// newlines are treated as spaces in both modes. Spaces are consolidated across
+/// element boundaries, because the text content is processed as a whole.
+///
+/// https://www.w3.org/TR/SVG11/text.html#WhiteSpace
+fn process_whitespace(elem: &Node) {
+    // Only text nodes that will be rendered are processed.
+    let nodes: Vec<(Node, bool)> = elem.descendants()
+        .filter(|n| n.node_type() == NodeType::Text)
+        .filter(|n| {
+            match n.parent() {
+                Some(p) => p.is_tag_name(EId::Text) || p.is_tag_name(EId::Tspan),
+                None => false,
+            }
+        })
+        .map(|n| {
+            let preserve = is_preserve_space(&n);
+            (n, preserve)
+        })
+        .collect();
+
+    let mut list = Vec::with_capacity(nodes.len());
+
+    // Leading spaces are removed.
+    let mut prev_space = true;
+    for &(ref node, preserve) in &nodes {
+        let text = node.text();
+        let mut new_text = String::with_capacity(text.len());
+        for c in text.chars() {
+            if preserve || c != ' ' {
+                new_text.push(c);
+                prev_space = false;
+            } else if !prev_space {
+                new_text.push(c);
+                prev_space = true;
+            }
+        }
+
+        list.push(new_text);
+    }
+
+    // Trailing spaces are removed.
+    for (&(_, preserve), text) in nodes.iter().zip(list.iter_mut()).rev() {
+        if preserve {
+            break;
+        }
+
+        if text.ends_with(' ') {
+            text.pop();
+        }
+
+        if !text.is_empty() {
+            break;
+        }
+    }
+
+    for ((mut node, _), text) in nodes.into_iter().zip(list) {
+        node.set_text(&text);
+    }
+}
+
+/// Checks that the closest `xml:space` is set to `preserve`.
+///
+/// The `white-space` property is already converted into `xml:space` by `resolve_css`.
+fn is_preserve_space(node: &Node) -> bool {
+    for parent in node.parents() {
+        if let Some(AValue::String(value)) = parent.attributes().get_value(AId::XmlSpace) {
+            return value == "preserve";
+        }
+    }
+
+    false
+}
+
 fn prepare_text_elem(doc: &mut Document, elem: &Node, new_elem: &mut Node) {
     for node in elem.descendants().filter(|n| n.node_type() == NodeType::Text) {
         let text_parent = node.parent().unwrap();
@@ -54,12 +149,13 @@ fn prepare_text_elem(doc: &mut Document, elem: &Node, new_elem: &mut Node) {
 
         if let Some(id) = text_parent.tag_id() {
             if id != EId::Text && id != EId::Tspan {
-                warn!("Unsupported text child: {:?}.", id);
+                diag!(Warning, UnsupportedElement, text_parent.id(),
+                      "Unsupported text child: {:?}.", id);
                 continue;
             }
         } else {
             // Text node parent must be an SVG element.
-            warn!("Invalid text node parent.");
+            diag!(Warning, UnsupportedElement, text_parent.id(), "Invalid text node parent.");
             continue;
         }
 
@@ -80,8 +176,91 @@ fn prepare_text_elem(doc: &mut Document, elem: &Node, new_elem: &mut Node) {
                         }
                     }
                 }
+                // Stored in the decoration elements.
+                AId::TextDecoration => {}
                 _ => new_tspan.set_attribute(attr.clone()),
             }
         }
+
+        prepare_decoration(doc, &text_parent, &mut new_tspan);
     }
 }
+
+// <text fill="green" text-decoration="underline">
+//   <tspan fill="blue" text-decoration="overline">
+//     <tspan fill="red">Text</tspan>
+//   </tspan>
+// </text>
+//
+// 'The fill and stroke of the text decoration are given by the fill and stroke
+// of the text at the point where the text decoration is declared.'
+//
+// So in this example the underline is green, the overline is blue and the text is red.
+//
+// Nested 'tspan' elements are flattened, so each decoration is stored
+// as an empty 'tspan' child with the style of the element where it was declared:
+//
+// <tspan fill="red">
+//   Text
+//   <tspan fill="green" text-decoration="underline"/>
+//   <tspan fill="blue" text-decoration="overline"/>
+// </tspan>
+//
+// 'text-decoration' of the 'text' element is generated by 'prepare_text_decoration'.
+fn prepare_decoration(doc: &mut Document, text_parent: &Node, new_tspan: &mut Node) {
+    let list = [
+        (ValueId::Underline, "underline"),
+        (ValueId::Overline, "overline"),
+        (ValueId::LineThrough, "linethrough"),
+    ];
+
+    for &(id, name) in &list {
+        let source = match find_decoration_source(text_parent, id, name) {
+            Some(source) => source,
+            None => continue,
+        };
+
+        let mut deco_elem = doc.create_element(EId::Tspan);
+        new_tspan.append(&deco_elem);
+
+        deco_elem.set_attribute((AId::TextDecoration, id));
+
+        let attrs = source.attributes();
+        for (aid, attr) in attrs.iter_svg() {
+            match aid {
+                  AId::Fill
+                | AId::FillOpacity
+                | AId::FillRule
+                | AId::Stroke
+                | AId::StrokeDasharray
+                | AId::StrokeDashoffset
+                | AId::StrokeLinecap
+                | AId::StrokeLinejoin
+                | AId::StrokeMiterlimit
+                | AId::StrokeOpacity
+                | AId::StrokeWidth => deco_elem.set_attribute(attr.clone()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Returns the closest element with the specified decoration up to the 'text' element.
+fn find_decoration_source(text_parent: &Node, id: ValueId, name: &str) -> Option<Node> {
+    for node in text_parent.parents_with_self() {
+        let attrs = node.attributes();
+
+        if node.is_tag_name(EId::Text) {
+            return match attrs.get_string(AId::TextDecoration) {
+                Some(text) if text.contains(name) => Some(node.clone()),
+                _ => None,
+            };
+        }
+
+        if attrs.get_predef(AId::TextDecoration) == Some(id) {
+            return Some(node.clone());
+        }
+    }
+
+    None
+}
@@ -0,0 +1,325 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom::{
+    AttributeType,
+    Document,
+    Node,
+    NodeType,
+};
+use svgdom::types::{
+    Transform,
+};
+
+use css::{
+    self,
+    AttributeOperator,
+    Combinator,
+    Compound,
+    Declaration,
+    Selector,
+    Specificity,
+    StyleSheet,
+    SubSelector,
+};
+
+use short::{
+    AId,
+    AValue,
+    EId,
+};
+use source::{
+    self,
+    Ids,
+    Value,
+};
+
+use {
+    Options,
+};
+
+
+/// A declaration priority in the cascade.
+///
+/// Fields order matters.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Priority {
+    important: bool,
+    /// Declared in the user style sheet.
+    user: bool,
+    /// Declared in the `style` attribute.
+    inline: bool,
+    specificity: Specificity,
+    /// Source order.
+    order: usize,
+}
+
+/// Resolves `style` elements, the user style sheet and `style` attributes.
+///
+/// Matched declarations override presentation attributes in the cascade order.
+/// `style` elements and attributes are removed afterwards. `class` attributes are kept.
+pub fn resolve_css(doc: &mut Document, opt: &Options) {
+    let mut texts = Vec::new();
+    for node in doc.descendants().filter(|n| n.is_tag_name(EId::Style)) {
+        let mut text = String::new();
+        for child in node.children() {
+            text.push_str(&child.text());
+        }
+
+        texts.push(text);
+    }
+
+    let mut sheets = Vec::new();
+    for text in &texts {
+        sheets.push((parse_style_sheet(text), false));
+    }
+
+    // The user style sheet goes last, so it wins on the same priority.
+    if let Some(ref text) = opt.style_sheet {
+        sheets.push((parse_style_sheet(text), true));
+    }
+
+    doc.drain(|n| n.is_tag_name(EId::Style));
+
+    let ids = source::collect_ids(doc);
+
+    let nodes: Vec<Node> = doc.descendants().filter(|n| n.is_svg_element()).collect();
+    for mut node in nodes {
+        let style = match node.attributes().get_value(AId::Style) {
+            Some(AValue::String(s)) => Some(s.clone()),
+            _ => None,
+        };
+        node.remove_attribute(AId::Style);
+
+        let mut list = Vec::new();
+        let mut order = 0;
+        for &(ref sheet, user) in &sheets {
+            for rule in &sheet.rules {
+                if !matches(&node, &rule.selector) {
+                    continue;
+                }
+
+                for decl in &sheet.blocks[rule.block] {
+                    let priority = Priority {
+                        important: decl.important,
+                        user,
+                        inline: false,
+                        specificity: rule.specificity,
+                        order,
+                    };
+                    list.push((priority, *decl));
+                    order += 1;
+                }
+            }
+        }
+
+        if let Some(ref style) = style {
+            for decl in css::parse_declarations(style) {
+                let priority = Priority {
+                    important: decl.important,
+                    user: false,
+                    inline: true,
+                    specificity: [0; 3],
+                    order,
+                };
+                list.push((priority, decl));
+                order += 1;
+            }
+        }
+
+        // Only the declaration with the highest priority is applied for each property.
+        // Property names are case-insensitive.
+        let mut winners: Vec<(Priority, Declaration)> = Vec::new();
+        for item in list {
+            match winners.iter_mut().find(|w| w.1.name.eq_ignore_ascii_case(item.1.name)) {
+                Some(w) => {
+                    if item.0 > w.0 {
+                        *w = item;
+                    }
+                }
+                None => winners.push(item),
+            }
+        }
+
+        let mut white_space = match node.attributes().get_value("white-space") {
+            Some(AValue::String(s)) => Some(s.clone()),
+            _ => None,
+        };
+
+        for (_, decl) in winners {
+            if decl.name.eq_ignore_ascii_case("white-space") {
+                white_space = Some(decl.value.to_string());
+                continue;
+            }
+
+            apply_declaration(&mut node, &decl, &ids);
+        }
+
+        if let Some(ref value) = white_space {
+            resolve_white_space(&mut node, value);
+        }
+    }
+
+    // Non-SVG attributes are needed only for matching.
+    for mut node in doc.descendants().filter(|n| n.is_svg_element()) {
+        node.attributes_mut().retain(|a| a.is_svg());
+    }
+}
+
+fn parse_style_sheet<'a>(text: &'a str) -> StyleSheet<'a> {
+    StyleSheet::parse(text, |msg| {
+        diag!(Warning, InvalidCss, "", "{}. Skipped.", msg);
+    })
+}
+
+fn matches(node: &Node, selector: &Selector) -> bool {
+    matches_components(node, &selector.components)
+}
+
+fn matches_components(node: &Node, components: &[(Combinator, Compound)]) -> bool {
+    let (&(combinator, ref compound), rest) = match components.split_last() {
+        Some(v) => v,
+        None => return true,
+    };
+
+    if !matches_compound(node, compound) {
+        return false;
+    }
+
+    if rest.is_empty() {
+        return true;
+    }
+
+    match combinator {
+        Combinator::Descendant => {
+            node.parents()
+                .filter(|n| n.node_type() == NodeType::Element)
+                .any(|n| matches_components(&n, rest))
+        }
+        Combinator::Child => {
+            match node.parents().find(|n| n.node_type() == NodeType::Element) {
+                Some(parent) => matches_components(&parent, rest),
+                None => false,
+            }
+        }
+        Combinator::NextSibling => {
+            match prev_element(node) {
+                Some(prev) => matches_components(&prev, rest),
+                None => false,
+            }
+        }
+        Combinator::SubsequentSibling => {
+            let mut prev = prev_element(node);
+            while let Some(n) = prev {
+                if matches_components(&n, rest) {
+                    return true;
+                }
+
+                prev = prev_element(&n);
+            }
+
+            false
+        }
+    }
+}
+
+fn matches_compound(node: &Node, compound: &Compound) -> bool {
+    if let Some(name) = compound.tag_name {
+        match node.tag_id() {
+            Some(id) if id.name() == name => {}
+            _ => return false,
+        }
+    }
+
+    compound.sub_selectors.iter().all(|sub| {
+        match *sub {
+            SubSelector::Id(id) => *node.id() == id,
+            SubSelector::Class(class) => {
+                match node.attributes().get_value(AId::Class) {
+                    Some(AValue::String(value)) => value.split_whitespace().any(|c| c == class),
+                    _ => false,
+                }
+            }
+            SubSelector::Attribute(name, op) => matches_attribute(node, name, op),
+            SubSelector::FirstChild => is_first_child(node),
+        }
+    })
+}
+
+fn is_first_child(node: &Node) -> bool {
+    prev_element(node).is_none()
+}
+
+fn prev_element(node: &Node) -> Option<Node> {
+    let mut prev = node.previous_sibling();
+    while let Some(n) = prev {
+        if n.node_type() == NodeType::Element {
+            return Some(n);
+        }
+
+        prev = n.previous_sibling();
+    }
+
+    None
+}
+
+fn matches_attribute(node: &Node, name: &str, op: AttributeOperator) -> bool {
+    if name == "id" {
+        return node.has_id() && op.matches(&node.id());
+    }
+
+    let aid = match AId::from_name(name) {
+        Some(aid) => aid,
+        None => {
+            return match node.attributes().get_value(name) {
+                Some(AValue::String(value)) => op.matches(value),
+                _ => false,
+            };
+        }
+    };
+
+    let attrs = node.attributes();
+    let value = match attrs.get_value(aid) {
+        Some(value) => value,
+        None => return false,
+    };
+
+    match (value, op) {
+        (AValue::String(s), _) => op.matches(s),
+        // Parsed values are compared by value, so `scale(2)` matches `matrix(2 0 0 2 0 0)`.
+        (AValue::Transform(ts), AttributeOperator::Matches(v)) => {
+            v.parse::<Transform>().ok().as_ref() == Some(ts)
+        }
+        (value, AttributeOperator::Matches(v)) => {
+            match node.tag_id().and_then(|eid| source::parse_value(eid, aid, v)) {
+                Some(Value::Plain(ref v)) => v == value,
+                _ => false,
+            }
+        }
+        (value, _) => op.matches(&value.to_string()),
+    }
+}
+
+/// Converts the `white-space` property into `xml:space`.
+///
+/// The property has a higher priority than `xml:space` and, for the supported values,
+/// the same meaning. Unsupported values are ignored.
+fn resolve_white_space(node: &mut Node, value: &str) {
+    let space = match value.trim() {
+        "pre" | "pre-wrap" | "break-spaces" => "preserve",
+        "normal" | "nowrap" | "pre-line" => "default",
+        _ => return,
+    };
+
+    node.set_attribute((AId::XmlSpace, space));
+}
+
+/// Sets a declaration as a presentation attribute.
+fn apply_declaration(node: &mut Node, decl: &Declaration, ids: &Ids) {
+    match AId::from_name(&decl.name.to_ascii_lowercase()) {
+        Some(aid) if aid.is_presentation() => source::set_attribute_str(node, aid, decl.value, ids),
+        // Unknown and non-presentation attributes are ignored.
+        _ => {}
+    }
+}
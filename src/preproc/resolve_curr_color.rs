@@ -8,6 +8,9 @@ use svgdom::{
     Node,
     ValueId,
 };
+use svgdom::types::{
+    Color,
+};
 
 use short::{
     AId,
@@ -18,7 +21,8 @@ use short::{
 /// Resolve 'currentColor' attribute.
 ///
 /// The function will fallback to a default value when possible.
-pub fn resolve_current_color(doc: &Document) {
+/// `color` overrides the resolved value.
+pub fn resolve_current_color(doc: &Document, color: Option<Color>) {
     let mut ids = Vec::new();
 
     for (_, mut node) in doc.descendants().svg() {
@@ -36,6 +40,11 @@ pub fn resolve_current_color(doc: &Document) {
         }
 
         for id in &ids {
+            if let Some(color) = color {
+                node.set_attribute((*id, color));
+                continue;
+            }
+
             let av = node.attributes().get_value(AId::Color).cloned();
             if let Some(av) = av {
                 node.set_attribute((*id, av.clone()));
@@ -56,7 +65,8 @@ fn resolve_impl(node: &mut Node, curr_attr: AId, parent_attr: AId) {
         match Attribute::default(curr_attr) {
             Some(a) => node.set_attribute((curr_attr, a.value)),
             None => {
-                warn!("Failed to resolve attribute: {}. Removing it.",
+                diag!(Warning, InvalidAttribute, node.id(),
+                      "Failed to resolve attribute: {}. Removing it.",
                       node.attributes().get(curr_attr).unwrap());
                 node.remove_attribute(curr_attr);
             }
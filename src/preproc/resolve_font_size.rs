@@ -61,11 +61,11 @@ pub fn _resolve_font_size(parent: &Node) {
                 }
             }
             AValue::PredefValue(id) => {
-                process_named_font_size(id, &font_size)
+                process_named_font_size(&node, id, &font_size)
             }
             _ => {
                 // Technically unreachable, because 'svgparser' should validate it.
-                warn!("Invalid 'font-size' value: {}.", font_size);
+                diag!(Info, InvalidAttribute, node.id(), "Invalid 'font-size' value: {}.", font_size);
                 Length::new(DEFAULT_FONT_SIZE, Unit::None)
             }
         };
@@ -92,7 +92,7 @@ fn process_percent_font_size(parent: &Node, len: Length) -> Length {
     }
 }
 
-fn process_named_font_size(id: ValueId, font_size: &AValue) -> Length {
+fn process_named_font_size(node: &Node, id: ValueId, font_size: &AValue) -> Length {
     let factor = match id {
         ValueId::XxSmall => -3,
         ValueId::XSmall => -2,
@@ -105,7 +105,7 @@ fn process_named_font_size(id: ValueId, font_size: &AValue) -> Length {
         ValueId::Larger => 1,
         _ => {
             // Technically unreachable, because 'svgparser' should validate it.
-            warn!("Invalid 'font-size' value: {}.", font_size);
+            diag!(Info, InvalidAttribute, node.id(), "Invalid 'font-size' value: {}.", font_size);
             0
         }
     };
@@ -133,7 +133,8 @@ fn resolve_attribute(node: &Node, id: AId, def_value: Option<AValue>) -> Option<
             match *av {
                 AValue::Link(ref ref_node) => resolve_attribute(ref_node, id, def_value),
                 _ => {
-                    warn!("'xlink:href' attribute should have Link value type.");
+                    diag!(Warning, InvalidAttribute, node.id(),
+                          "'xlink:href' attribute should have Link value type.");
                     None
                 }
             }
@@ -51,8 +51,9 @@ fn resolve_impl(node: &mut Node, attr: AId) {
         match Attribute::default(attr) {
             Some(a) => node.set_attribute((attr, a.value)),
             None => {
-                warn!("Failed to resolve attribute: {}. Removing it.",
-                        node.attributes().get(attr).unwrap());
+                diag!(Warning, InvalidAttribute, node.id(),
+                      "Failed to resolve attribute: {}. Removing it.",
+                      node.attributes().get(attr).unwrap());
                 node.remove_attribute(attr);
             }
         }
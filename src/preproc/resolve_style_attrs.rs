@@ -116,7 +116,7 @@ fn resolve_default(node: &mut Node, aid: AId) {
         Some(v) => v,
         None => {
             // Technically unreachable.
-            warn!("'{:?}' doesn't have a default value.", aid);
+            diag!(Warning, MissingAttribute, node.id(), "'{:?}' doesn't have a default value.", aid);
             return;
         }
     };
@@ -141,7 +141,8 @@ fn resolve_font_family(node: &mut Node) {
         } else {
             // `font-family` depends on user agent, so we use our own font
             // TODO: maybe use a system font
-            warn!("'font-family' is not set. Fallback to '{}'.", DEFAULT_FONT_FAMILY);
+            diag!(Info, FontFallback, node.id(),
+                  "'font-family' is not set. Fallback to '{}'.", DEFAULT_FONT_FAMILY);
             node.set_attribute((aid, DEFAULT_FONT_FAMILY));
         }
     }
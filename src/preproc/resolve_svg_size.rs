@@ -20,9 +20,17 @@ use traits::{
 };
 
 
+/// An SVG size that should be resolved using the content bounding box.
+pub struct ContentSize {
+    /// Width factor. `None` if the width is absolute.
+    pub width: Option<f64>,
+    /// Height factor. `None` if the height is absolute.
+    pub height: Option<f64>,
+}
+
 /// Tested by:
 /// - struct-svg-*.svg
-pub fn resolve_svg_size(svg: &mut Node) -> bool {
+pub fn resolve_svg_size(svg: &mut Node) -> Option<ContentSize> {
     // We doesn't converted units yet, so operate on Length.
 
     let width = get_length(&svg, AId::Width);
@@ -31,9 +39,25 @@ pub fn resolve_svg_size(svg: &mut Node) -> bool {
     let view_box = svg.get_viewbox().ok();
 
     if (width.unit == Unit::Percent || height.unit == Unit::Percent) && view_box.is_none() {
-        // TODO: it this case we should detect the bounding box of all elements,
-        //       which is currently impossible
-        return false;
+        // In this case the size should be detected from the bounding box of all elements,
+        // which is possible only after the conversion.
+        // Until then, percent values are resolved relative to a 100x100 viewport.
+        let mut size = ContentSize {
+            width: None,
+            height: None,
+        };
+
+        if width.unit == Unit::Percent {
+            svg.set_attribute((AId::Width, Length::new_number(width.num)));
+            size.width = Some(width.num / 100.0);
+        }
+
+        if height.unit == Unit::Percent {
+            svg.set_attribute((AId::Height, Length::new_number(height.num)));
+            size.height = Some(height.num / 100.0);
+        }
+
+        return Some(size);
     }
 
     if let Some(vbox) = view_box {
@@ -48,7 +72,7 @@ pub fn resolve_svg_size(svg: &mut Node) -> bool {
         }
     }
 
-    true
+    None
 }
 
 fn get_length(node: &Node, aid: AId) -> Length {
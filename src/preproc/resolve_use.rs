@@ -20,12 +20,23 @@ use traits::{
     GetValue,
 };
 
+use {
+    ErrorKind,
+    Limits,
+    Result,
+};
+
 
 // Tested by:
 // - struct-use-*.svg
-pub fn resolve_use(doc: &Document) {
+pub fn resolve_use(doc: &Document, limits: &Limits) -> Result<()> {
     let mut nodes = Vec::new();
 
+    // Nodes are counted before copying, so a 'use' bomb will be stopped
+    // before it consumes all the memory.
+    let mut count = doc.descendants().count();
+    check_count(count, limits)?;
+
     // 'use' elements can be linked in any order,
     // so we have to process the tree until all 'use' are solved.
     let mut is_any_resolved = true;
@@ -52,6 +63,10 @@ pub fn resolve_use(doc: &Document) {
                     continue;
                 }
 
+                // 'use' itself will be removed.
+                count += link.descendants().count() - 1;
+                check_count(count, limits)?;
+
                 _resolve_use(&mut node, &link);
                 is_any_resolved = true;
             }
@@ -66,6 +81,16 @@ pub fn resolve_use(doc: &Document) {
             node.remove();
         }
     }
+
+    Ok(())
+}
+
+fn check_count(count: usize, limits: &Limits) -> Result<()> {
+    if count > limits.max_elements {
+        return Err(ErrorKind::TooManyElements(limits.max_elements).into());
+    }
+
+    Ok(())
 }
 
 fn _resolve_use(use_node: &mut Node, linked_node: &Node) {
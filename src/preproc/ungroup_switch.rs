@@ -4,6 +4,7 @@
 
 use svgdom::{
     Document,
+    ElementType,
     Node,
 };
 
@@ -13,6 +14,10 @@ use short::{
     AValue,
 };
 
+use {
+    Options,
+};
+
 
 static FEATURES: &[&str] = &[
     // "http://www.w3.org/TR/SVG11/feature#SVG", // not yet
@@ -21,7 +26,7 @@ static FEATURES: &[&str] = &[
     "http://www.w3.org/TR/SVG11/feature#Structure",
     "http://www.w3.org/TR/SVG11/feature#BasicStructure",
     // "http://www.w3.org/TR/SVG11/feature#ContainerAttribute", // not yet
-    "http://www.w3.org/TR/SVG11/feature#ConditionalProcessing", // no systemLanguage
+    "http://www.w3.org/TR/SVG11/feature#ConditionalProcessing",
     "http://www.w3.org/TR/SVG11/feature#Image",
     "http://www.w3.org/TR/SVG11/feature#Style",
     // "http://www.w3.org/TR/SVG11/feature#ViewportAttribute", // not yet
@@ -45,32 +50,73 @@ static FEATURES: &[&str] = &[
     "http://www.w3.org/TR/SVG11/feature#XlinkAttribute", // only xlink:href
 ];
 
-pub fn ungroup_switch(doc: &Document) {
+/// Ungroups `switch` elements and removes elements which conditional attributes
+/// are evaluated to `false`.
+pub fn ungroup_switch(doc: &mut Document, opt: &Options) {
     loop {
-        if let Some(mut node) = doc.descendants().find(|n| n.is_tag_name(EId::Switch)) {
-            for (_, mut child) in node.children().svg() {
-                if is_valid_child(&child) {
-                    child.detach();
-                    node.insert_after(&child);
-                    node.remove();
-
-                    break;
-                }
+        let mut node = match doc.descendants().find(|n| n.is_tag_name(EId::Switch)) {
+            Some(node) => node,
+            None => break,
+        };
+
+        // Only the first valid child is rendered.
+        if is_valid_child(&node, opt) {
+            let child = node.children().svg().map(|(_, n)| n).find(|n| is_valid_child(n, opt));
+            if let Some(mut child) = child {
+                child.detach();
+                node.insert_after(&child);
             }
-        } else {
-            break;
         }
+
+        node.remove();
     }
+
+    // Conditional attributes are evaluated on any rendered element, not only inside a `switch`.
+    let svg = doc.svg_element();
+    doc.drain(|n| Some(n) != svg.as_ref() && is_rendered(n) && !is_valid_child(n, opt));
 }
 
-fn is_valid_child(node: &Node) -> bool {
+/// Checks that an element is a graphics or a container element outside of `defs`.
+///
+/// Referenced elements, like gradients, are not rendered directly,
+/// so they are not affected by conditional attributes.
+fn is_rendered(node: &Node) -> bool {
+    let is_graphic = node.is_graphic() || node.is_tag_name(EId::G) || node.is_tag_name(EId::Svg);
+    is_graphic && !node.parents().any(|p| p.is_tag_name(EId::Defs))
+}
+
+fn is_valid_child(node: &Node, opt: &Options) -> bool {
     let attrs = node.attributes();
 
-    if attrs.contains(AId::RequiredExtensions) {
-        return false;
+    // 'The value is a list of URI references which identify the required extensions,
+    // with the individual values separated by white space.
+    // If a null string or empty string value is given, the attribute evaluates to "false".'
+    if let Some(AValue::String(extensions)) = attrs.get_value(AId::RequiredExtensions) {
+        if extensions.trim().is_empty() {
+            return false;
+        }
+
+        for ext in extensions.split_whitespace() {
+            if !opt.extensions.iter().any(|e| e == ext) {
+                return false;
+            }
+        }
     }
 
-    // TODO: systemLanguage
+    // 'Evaluates to "true" if one of the languages indicated by user preferences exactly equals
+    // one of the languages given in the value of this parameter, or if one of the languages
+    // indicated by user preferences exactly equals a prefix of one of the languages given
+    // in the value of this parameter such that the first tag character following
+    // the prefix is "-".'
+    if let Some(AValue::String(langs)) = attrs.get_value(AId::SystemLanguage) {
+        let is_matched = langs.split(',').any(|lang| {
+            opt.languages.iter().any(|user_lang| is_lang_matched(user_lang, lang.trim()))
+        });
+
+        if !is_matched {
+            return false;
+        }
+    }
 
     // 'The value is a list of feature strings, with the individual values separated by white space.
     // Determines whether all of the named features are supported by the user agent.
@@ -89,3 +135,104 @@ fn is_valid_child(node: &Node) -> bool {
 
     true
 }
+
+/// Checks that a user language matches a language tag using the prefix matching.
+///
+/// Tags are compared case-insensitively, so `en` matches `en`, `EN-us` and `en-GB`,
+/// but not `eng`.
+fn is_lang_matched(user_lang: &str, lang: &str) -> bool {
+    let user_lang = user_lang.trim();
+    if user_lang.is_empty() {
+        return false;
+    }
+
+    match (lang.get(..user_lang.len()), lang.get(user_lang.len()..)) {
+        (Some(prefix), Some(rest)) => {
+            prefix.eq_ignore_ascii_case(user_lang) && (rest.is_empty() || rest.starts_with('-'))
+        }
+        _ => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dom;
+    use test_utils::parse_with;
+
+    fn ids(content: &str, opt: &Options) -> Vec<String> {
+        let doc = parse_with("width='10' height='10'", content, opt).unwrap();
+        doc.descendants().map(|e: &dom::Element| e.id.clone()).collect()
+    }
+
+    fn languages(list: &[&str]) -> Options {
+        Options {
+            languages: list.iter().map(|s| s.to_string()).collect(),
+            .. Options::default()
+        }
+    }
+
+    const SWITCH: &str = "\
+        <switch>\
+            <rect id='ru' systemLanguage='ru-RU' width='1' height='1'/>\
+            <rect id='en' systemLanguage='de, en-US' width='1' height='1'/>\
+            <rect id='default' width='1' height='1'/>\
+        </switch>";
+
+    #[test]
+    fn lang_matching() {
+        assert!(is_lang_matched("en", "en"));
+        assert!(is_lang_matched("en", "en-US"));
+        assert!(is_lang_matched("EN", "en-us"));
+        assert!(is_lang_matched("en-US", "en-US"));
+        assert!(!is_lang_matched("en", "eng"));
+        assert!(!is_lang_matched("en-US", "en"));
+        assert!(!is_lang_matched("en-US", "en-GB"));
+        assert!(!is_lang_matched("", "en"));
+        assert!(!is_lang_matched("en", ""));
+    }
+
+    #[test]
+    fn switch_language() {
+        assert_eq!(ids(SWITCH, &languages(&["en"])), vec!["en"]);
+        assert_eq!(ids(SWITCH, &languages(&["fr", "ru"])), vec!["ru"]);
+        // The first matched child is used and not the first user language.
+        assert_eq!(ids(SWITCH, &languages(&["de", "ru"])), vec!["ru"]);
+        assert_eq!(ids(SWITCH, &languages(&["fr"])), vec!["default"]);
+        assert_eq!(ids(SWITCH, &languages(&[])), vec!["default"]);
+    }
+
+    #[test]
+    fn standalone_language() {
+        let text = "\
+            <rect id='en' systemLanguage='en' width='1' height='1'/>\
+            <rect id='ru' systemLanguage='ru' width='1' height='1'/>";
+
+        assert_eq!(ids(text, &languages(&["en-US", "ru"])), vec!["ru"]);
+        assert_eq!(ids(text, &languages(&["en"])), vec!["en"]);
+    }
+
+    #[test]
+    fn extensions() {
+        let text = "\
+            <switch>\
+                <rect id='ext' requiredExtensions='http://example.com/a http://example.com/b' \
+                      width='1' height='1'/>\
+                <rect id='empty' requiredExtensions='' width='1' height='1'/>\
+                <rect id='default' width='1' height='1'/>\
+            </switch>";
+
+        assert_eq!(ids(text, &Options::default()), vec!["default"]);
+
+        let mut opt = Options {
+            extensions: vec!["http://example.com/a".to_string()],
+            .. Options::default()
+        };
+        assert_eq!(ids(text, &opt), vec!["default"]);
+
+        opt.extensions.push("http://example.com/b".to_string());
+        assert_eq!(ids(text, &opt), vec!["ext"]);
+    }
+}
@@ -0,0 +1,354 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::sync::Arc;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+use std::sync::atomic::AtomicUsize;
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+use dom;
+
+use {
+    ErrorKind,
+    Options,
+    Result,
+};
+
+
+/// A progress and cancellation callback.
+///
+/// The parser checks for cancellation between preprocessing passes
+/// and the renderer checks for it after each rendered element.
+/// A cancelled operation fails with `ErrorKind::Cancelled`.
+///
+/// Can be called from multiple threads when `Options::threads` is bigger than 1.
+pub trait ProgressCallback: Send + Sync {
+    /// Reports a rendering progress.
+    ///
+    /// `value` is a fraction of rendered elements in a 0..1 range.
+    fn progress(&self, value: f64) {
+        let _ = value;
+    }
+
+    /// Checks that the current operation should be cancelled.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// A cancellation token.
+///
+/// Clones share the same state, so a token can be cancelled from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a new token.
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Cancels an operation that uses this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks that the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl ProgressCallback for CancellationToken {
+    fn is_cancelled(&self) -> bool {
+        CancellationToken::is_cancelled(self)
+    }
+}
+
+/// Returns `ErrorKind::Cancelled` when `Options::progress` was cancelled.
+pub fn check_cancelled(opt: &Options) -> Result<()> {
+    match opt.progress {
+        Some(ref callback) if callback.is_cancelled() => Err(ErrorKind::Cancelled.into()),
+        _ => Ok(()),
+    }
+}
+
+/// A rendering progress tracker.
+///
+/// Shared between all rendering threads.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+pub struct Progress<'a> {
+    callback: Option<&'a dyn ProgressCallback>,
+    total: AtomicUsize,
+    done: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+impl<'a> Progress<'a> {
+    /// Creates a new tracker for the specified elements.
+    pub fn new(opt: &'a Options, elements: &[dom::Element]) -> Self {
+        let callback = opt.progress.as_deref();
+
+        // There is no need to count elements when nothing will be reported.
+        let total = if callback.is_some() { count_elements(elements) } else { 0 };
+
+        Progress {
+            callback,
+            total: AtomicUsize::new(total),
+            done: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Creates a tracker that doesn't report anything.
+    #[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+    pub fn none() -> Self {
+        Progress {
+            callback: None,
+            total: AtomicUsize::new(0),
+            done: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the number of times elements will be rendered.
+    ///
+    /// Each band is rendered separately, so the elements are rendered once per band.
+    #[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+    pub fn set_passes(&self, passes: usize) {
+        let total = self.total.load(Ordering::Relaxed);
+        self.total.store(total * passes, Ordering::Relaxed);
+    }
+
+    /// Marks an element as rendered.
+    ///
+    /// Returns `false` when rendering should be stopped.
+    pub fn step(&self) -> bool {
+        let callback = match self.callback {
+            Some(callback) => callback,
+            None => return true,
+        };
+
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let total = self.total.load(Ordering::Relaxed);
+        if total != 0 {
+            callback.progress((done as f64 / total as f64).min(1.0));
+        }
+
+        if callback.is_cancelled() {
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+
+        !self.is_cancelled()
+    }
+
+    /// Checks that rendering was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns `ErrorKind::Cancelled` when rendering was cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(ErrorKind::Cancelled.into());
+        }
+
+        Ok(())
+    }
+
+    /// Checks that rendering was cancelled and reports the rendering completion.
+    ///
+    /// Children of invisible groups are never rendered,
+    /// so the completion should be reported separately.
+    pub fn finish(&self) -> Result<()> {
+        self.check()?;
+
+        if let Some(callback) = self.callback {
+            if self.done.load(Ordering::Relaxed) < self.total.load(Ordering::Relaxed) {
+                callback.progress(1.0);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts elements, including the elements of SVG images.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+fn count_elements(elements: &[dom::Element]) -> usize {
+    let mut count = 0;
+    for elem in elements {
+        count += 1;
+
+        match elem.data {
+            dom::Type::Group(ref g) => {
+                count += count_elements(&g.children);
+            }
+            dom::Type::Image(ref img) => {
+                if let dom::ImageData::Svg(ref sub_doc) = img.data {
+                    count += count_elements(&sub_doc.elements);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    count
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use test_utils;
+    use {
+        Error,
+    };
+
+    // A group and three rects.
+    const CONTENT: &str = "\
+        <g opacity='0.5'>\
+            <rect width='10' height='10'/>\
+            <rect x='20' width='10' height='10'/>\
+        </g>\
+        <rect x='40' width='10' height='10'/>";
+
+    fn parse() -> dom::Document {
+        test_utils::parse("width='100' height='100'", CONTENT)
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        values: Mutex<Vec<f64>>,
+        cancel_after: Option<usize>,
+    }
+
+    impl ProgressCallback for Recorder {
+        fn progress(&self, value: f64) {
+            self.values.lock().unwrap().push(value);
+        }
+
+        fn is_cancelled(&self) -> bool {
+            match self.cancel_after {
+                Some(n) => self.values.lock().unwrap().len() >= n,
+                None => false,
+            }
+        }
+    }
+
+    fn options(callback: Arc<dyn ProgressCallback>) -> Options {
+        Options {
+            progress: Some(callback),
+            .. Options::default()
+        }
+    }
+
+    #[test]
+    fn token() {
+        let token = CancellationToken::new();
+        let copy = token.clone();
+        assert!(!copy.is_cancelled());
+
+        token.cancel();
+        assert!(copy.is_cancelled());
+        assert!(ProgressCallback::is_cancelled(&copy));
+    }
+
+    #[test]
+    fn cancelled_parsing() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        match test_utils::parse_with("width='100' height='100'", CONTENT, &options(Arc::new(token))) {
+            Err(Error(ErrorKind::Cancelled, _)) => {}
+            _ => panic!("Cancelled expected"),
+        }
+    }
+
+    #[test]
+    fn fractions() {
+        let doc = parse();
+        let recorder = Arc::new(Recorder::default());
+        let opt = options(recorder.clone());
+
+        let progress = Progress::new(&opt, &doc.elements);
+        for _ in 0..4 {
+            assert!(progress.step());
+        }
+        progress.finish().unwrap();
+
+        assert_eq!(*recorder.values.lock().unwrap(), vec![0.25, 0.5, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn passes() {
+        let doc = parse();
+        let recorder = Arc::new(Recorder::default());
+        let opt = options(recorder.clone());
+
+        let progress = Progress::new(&opt, &doc.elements);
+        progress.set_passes(2);
+        for _ in 0..4 {
+            progress.step();
+        }
+
+        assert_eq!(*recorder.values.lock().unwrap(), vec![0.125, 0.25, 0.375, 0.5]);
+    }
+
+    #[test]
+    fn skipped_elements_are_finished() {
+        let doc = parse();
+        let recorder = Arc::new(Recorder::default());
+        let opt = options(recorder.clone());
+
+        let progress = Progress::new(&opt, &doc.elements);
+        progress.step();
+        progress.finish().unwrap();
+
+        assert_eq!(*recorder.values.lock().unwrap(), vec![0.25, 1.0]);
+    }
+
+    #[test]
+    fn cancellation() {
+        let doc = parse();
+        let recorder = Arc::new(Recorder { cancel_after: Some(2), .. Recorder::default() });
+        let opt = options(recorder.clone());
+
+        let progress = Progress::new(&opt, &doc.elements);
+        assert!(progress.step());
+        assert!(!progress.step());
+        assert!(progress.is_cancelled());
+
+        match progress.finish() {
+            Err(Error(ErrorKind::Cancelled, _)) => {}
+            _ => panic!("Cancelled expected"),
+        }
+
+        // The completion of a cancelled rendering is not reported.
+        assert_eq!(recorder.values.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "pdf-backend")]
+    fn cancelled_rendering() {
+        let doc = parse();
+        let recorder = Arc::new(Recorder { cancel_after: Some(1), .. Recorder::default() });
+
+        match ::render_pdf::render_to_pdf(&doc, &options(recorder.clone())) {
+            Err(Error(ErrorKind::Cancelled, _)) => {}
+            _ => panic!("Cancelled expected"),
+        }
+
+        assert_eq!(*recorder.values.lock().unwrap(), vec![0.25]);
+    }
+}
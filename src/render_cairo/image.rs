@@ -3,42 +3,42 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use cairo;
-use image;
 
 use dom;
+use render_utils;
+
+use super::ImageCache;
 
 
 pub fn draw(
     image: &dom::Image,
+    cache: &mut ImageCache,
     cr: &cairo::Context,
 ) {
-    let img = match image.data {
-        dom::ImageData::Path(ref path) => {
-            match image::open(path) {
-                Ok(v) => v,
-                Err(_) => {
-                    warn!("Failed to load an external image: {:?}.", path);
-                    return;
-                }
-            }
-        }
-        dom::ImageData::Raw(ref data, _) => {
-            match image::load_from_memory(data) {
-                Ok(v) => v,
-                Err(_) => {
-                    warn!("Failed to load an embedded image.");
-                    return;
-                }
-            }
-        }
+    let surface = match cache.get_or_load(&image.data, load) {
+        Some(v) => v,
+        None => return,
     };
 
-    let img = img.resize(
-        image.rect.w as u32,
-        image.rect.h as u32,
-        image::FilterType::Lanczos3
-    );
-    let img = img.to_rgba();
+    let w = surface.get_width() as f64;
+    let h = surface.get_height() as f64;
+    let r = image.rect;
+
+    // Let cairo scale the image, so it will be filtered according to the current transform.
+    cr.translate(r.x, r.y);
+    cr.scale(r.w / w, r.h / h);
+
+    cr.set_source_surface(surface, 0.0, 0.0);
+    cr.rectangle(0.0, 0.0, w, h);
+    cr.fill();
+}
+
+fn load(data: &dom::ImageData) -> Option<cairo::ImageSurface> {
+    let img = render_utils::load_raster_image(data)?.to_rgba();
+    if img.width() == 0 || img.height() == 0 {
+        diag!(Warning, InvalidImage, "", "An image has a zero size.");
+        return None;
+    }
 
     let surface = cairo::ImageSurface::create(
         cairo::Format::ARgb32,
@@ -49,34 +49,37 @@ pub fn draw(
     let mut surface = match surface {
         Ok(v) => v,
         Err(_) => {
-            warn!("Failed to create a surface for bitmap image.");
-            return;
+            diag!(Warning, InvalidImage, "", "Failed to create a surface for bitmap image.");
+            return None;
         }
     };
 
     {
+        let stride = surface.get_stride() as usize;
+        let row_len = img.width() as usize * 4;
         let mut surface_data = surface.get_data().unwrap();
 
-        let mut i = 0;
-        for p in img.chunks(4) {
-            let r = p[0] as u32;
-            let g = p[1] as u32;
-            let b = p[2] as u32;
-            let a = p[3] as u32;
-
-            // https://www.cairographics.org/manual/cairo-Image-Surfaces.html#cairo-format-t
-            let tr = a * r + 0x80;
-            let tg = a * g + 0x80;
-            let tb = a * b + 0x80;
-            surface_data[i + 0] = (((tb >> 8) + tb) >> 8) as u8;
-            surface_data[i + 1] = (((tg >> 8) + tg) >> 8) as u8;
-            surface_data[i + 2] = (((tr >> 8) + tr) >> 8) as u8;
-            surface_data[i + 3] = a as u8;
-
-            i += 4;
+        for (y, row) in img.chunks(row_len).enumerate() {
+            let mut i = y * stride;
+            for p in row.chunks(4) {
+                let r = p[0] as u32;
+                let g = p[1] as u32;
+                let b = p[2] as u32;
+                let a = p[3] as u32;
+
+                // https://www.cairographics.org/manual/cairo-Image-Surfaces.html#cairo-format-t
+                let tr = a * r + 0x80;
+                let tg = a * g + 0x80;
+                let tb = a * b + 0x80;
+                surface_data[i + 0] = (((tb >> 8) + tb) >> 8) as u8;
+                surface_data[i + 1] = (((tg >> 8) + tg) >> 8) as u8;
+                surface_data[i + 2] = (((tr >> 8) + tr) >> 8) as u8;
+                surface_data[i + 3] = a as u8;
+
+                i += 4;
+            }
         }
     }
 
-    cr.set_source_surface(&surface, image.rect.x, image.rect.y);
-    cr.paint();
+    Some(surface)
 }
@@ -8,6 +8,11 @@ use cairo::{
     self,
     MatrixTrait,
 };
+use cairo::prelude::SurfaceExt;
+
+use svgdom::types::{
+    Transform,
+};
 
 use dom;
 
@@ -20,8 +25,11 @@ use {
     ErrorKind,
     Options,
     Result,
+    Tile,
 };
 
+use buffer;
+use progress::Progress;
 use render_utils;
 
 
@@ -36,11 +44,175 @@ mod text;
 use self::ext::*;
 
 
+type ImageCache = render_utils::ImageCache<cairo::ImageSurface>;
+
+
 /// Renders SVG to image.
 pub fn render_to_image(doc: &dom::Document, opt: &Options) -> Result<cairo::ImageSurface> {
-    let img_size = render_utils::fit_to(&doc.size, opt.fit_to);
+    render(doc, &doc.elements, &doc.view_box, &doc.size, opt)
+}
+
+/// Renders a single element to image.
+///
+/// The image is cropped by the element bounding box.
+/// When `only` is set, all other elements are hidden,
+/// otherwise everything inside the bounding box is rendered.
+pub fn render_node_to_image(
+    doc: &dom::Document,
+    id: &str,
+    only: bool,
+    opt: &Options,
+) -> Result<cairo::ImageSurface> {
+    let metrics = TextMetrics::new(doc.dpi);
+    let node = render_utils::prepare_node(doc, id, only, Some(&metrics))?;
+    render(doc, node.elements(doc), &node.view_box, &node.size, opt)
+}
+
+/// Renders an area of the document to image.
+///
+/// `area` is in the user coordinates, aka `viewBox`.
+/// The image size is the area size at the document scale, adjusted by `Options::fit_to`.
+pub fn render_area_to_image(
+    doc: &dom::Document,
+    area: Rect,
+    opt: &Options,
+) -> Result<cairo::ImageSurface> {
+    let size = render_utils::prepare_area(doc, &area)?;
+    render(doc, &doc.elements, &area, &size, opt)
+}
+
+/// Renders a single tile to image.
+///
+/// The image has the tile size, so only the tile area is allocated.
+pub fn render_tile_to_image(
+    doc: &dom::Document,
+    tile: &Tile,
+    opt: &Options,
+) -> Result<cairo::ImageSurface> {
+    let progress = Progress::new(opt, &doc.elements);
+    let img = render_view(doc, &doc.elements, &doc.view_box, tile.rect.size(), tile.img_view,
+                          &progress, opt)?;
+    progress.finish()?;
+
+    Ok(img)
+}
+
+/// Renders a tile into a premultiplied BGRA buffer band.
+pub(crate) fn render_band(
+    doc: &dom::Document,
+    opt: &Options,
+    tile: &Tile,
+    band: &mut [u8],
+    stride: usize,
+    progress: &Progress,
+) -> Result<()> {
+    render_into(doc, &doc.elements, &doc.view_box, tile, band, stride, progress, opt)
+}
+
+fn render(
+    doc: &dom::Document,
+    elements: &[dom::Element],
+    view_box: &Rect,
+    size: &Size,
+    opt: &Options,
+) -> Result<cairo::ImageSurface> {
+    let img_size = render_utils::fit_to(size, opt.fit_to);
+    let progress = Progress::new(opt, elements);
+
+    let img = if opt.threads > 1 {
+        render_parallel(doc, elements, view_box, img_size, &progress, opt)?
+    } else {
+        let img_view = Rect::new(0.0, 0.0, img_size.w, img_size.h);
+        render_view(doc, elements, view_box, img_size, img_view, &progress, opt)?
+    };
+    progress.finish()?;
+
+    Ok(img)
+}
+
+// Bands are rendered into separate surfaces and then copied into the main one,
+// because cairo objects can't be shared between threads.
+fn render_parallel(
+    doc: &dom::Document,
+    elements: &[dom::Element],
+    view_box: &Rect,
+    img_size: Size,
+    progress: &Progress,
+    opt: &Options,
+) -> Result<cairo::ImageSurface> {
+    render_utils::check_canvas_size(img_size, opt)?;
+
+    let surface = cairo::ImageSurface::create(
+        cairo::Format::ARgb32,
+        img_size.w as i32,
+        img_size.h as i32
+    );
+
+    let mut surface = match surface {
+        Ok(v) => v,
+        Err(_) => {
+            return Err(ErrorKind::NoCanvas.into());
+        }
+    };
+
+    {
+        let stride = surface.get_stride() as usize;
+        let mut data = match surface.get_data() {
+            Ok(data) => data,
+            Err(_) => return Err(ErrorKind::NoCanvas.into()),
+        };
+
+        let threads = opt.threads;
+        render_utils::render_bands(&mut data, img_size, stride, threads as u32, threads, progress,
+                                   |tile, band| {
+            render_into(doc, elements, view_box, tile, band, stride, progress, opt)
+        })?;
+    }
+
+    Ok(surface)
+}
+
+/// Renders a tile into a foreign memory.
+///
+/// Cairo can't render into a borrowed memory, so the tile is rendered
+/// into a separate surface and then copied.
+fn render_into(
+    doc: &dom::Document,
+    elements: &[dom::Element],
+    view_box: &Rect,
+    tile: &Tile,
+    data: &mut [u8],
+    stride: usize,
+    progress: &Progress,
+    opt: &Options,
+) -> Result<()> {
+    let mut surface = render_view(doc, elements, view_box, tile.rect.size(), tile.img_view,
+                                  progress, opt)?;
+
+    let surface_stride = surface.get_stride() as usize;
+    let surface_data = match surface.get_data() {
+        Ok(data) => data,
+        Err(_) => return Err(ErrorKind::NoCanvas.into()),
+    };
 
-    debug_assert!(img_size.w as i32 > 0 && img_size.h as i32 > 0);
+    buffer::copy_rows(&surface_data, surface_stride, data, stride,
+                      tile.rect.w as u32, tile.rect.h as u32)
+}
+
+fn render_view(
+    doc: &dom::Document,
+    elements: &[dom::Element],
+    view_box: &Rect,
+    img_size: Size,
+    img_view: Rect,
+    progress: &Progress,
+    opt: &Options,
+) -> Result<cairo::ImageSurface> {
+    if !(img_size.w as i32 > 0 && img_size.h as i32 > 0) {
+        return Err(ErrorKind::NoCanvas.into());
+    }
+
+    render_utils::check_canvas_size(img_size, opt)?;
 
     let surface = cairo::ImageSurface::create(
         cairo::Format::ARgb32,
@@ -55,40 +227,91 @@ pub fn render_to_image(doc: &dom::Document, opt: &Options) -> Result<cairo::Imag
         }
     };
 
-    let img_view = Rect::new(0.0, 0.0, img_size.w, img_size.h);
-    let cr = cairo::Context::new(&surface);
+    {
+        let cr = cairo::Context::new(&surface);
+        draw_view(doc, elements, view_box, img_size, img_view, &cr, progress, opt);
+    }
+    progress.check()?;
 
+    Ok(surface)
+}
+
+fn draw_view(
+    doc: &dom::Document,
+    elements: &[dom::Element],
+    view_box: &Rect,
+    img_size: Size,
+    img_view: Rect,
+    cr: &cairo::Context,
+    progress: &Progress,
+    opt: &Options,
+) {
     // Fill background.
     if let Some(color) = opt.background {
         cr.set_source_color(&color, 1.0);
         cr.paint();
     }
 
-    render_to_canvas(&cr, img_view, doc);
+    apply_view_box(cr, view_box, &img_view);
+    render_group(doc, elements, cr, &cr.get_matrix(), img_size, &mut ImageCache::new(), progress);
+}
 
-    Ok(surface)
+/// Cairo based text metrics.
+///
+/// Can be used to calculate a text bounding box via `Document::calc_bbox`.
+pub struct TextMetrics {
+    dpi: f64,
+}
+
+impl TextMetrics {
+    /// Creates a new `TextMetrics` with the specified DPI.
+    ///
+    /// Should be the same as `Document::dpi`.
+    pub fn new(dpi: f64) -> Self {
+        TextMetrics {
+            dpi,
+        }
+    }
+}
+
+impl dom::TextMetrics for TextMetrics {
+    fn text_bbox(&self, text: &dom::Text) -> Option<Rect> {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1).ok()?;
+        let cr = cairo::Context::new(&surface);
+        text::calc_bbox(text, self.dpi, &cr)
+    }
 }
 
 /// Renders SVG to canvas.
 pub fn render_to_canvas(cr: &cairo::Context, img_view: Rect, doc: &dom::Document) {
-    // Apply viewBox.
+    apply_view_box(cr, &doc.view_box, &img_view);
+    let img_size = render_utils::canvas_size(&img_view);
+    render_group(doc, &doc.elements, &cr, &cr.get_matrix(), img_size, &mut ImageCache::new(),
+                 &Progress::none());
+}
+
+fn apply_view_box(cr: &cairo::Context, view_box: &Rect, img_view: &Rect) {
     let ts = {
-        let (dx, dy, sx, sy) = render_utils::view_box_transform(&doc.view_box, &img_view);
+        let (dx, dy, sx, sy) = render_utils::view_box_transform(view_box, img_view);
         cairo::Matrix::new(sx, 0.0, 0.0, sy, dx, dy)
     };
     cr.set_matrix(ts);
-
-    render_group(doc, &doc.elements, &cr, &cr.get_matrix(), img_view.size());
 }
 
-fn render_group(
+fn render_group<'a>(
     doc: &dom::Document,
-    elements: &[dom::Element],
+    elements: &'a [dom::Element],
     cr: &cairo::Context,
     matrix: &cairo::Matrix,
     img_size: Size,
+    cache: &mut ImageCache,
+    progress: &Progress,
 ) {
     for elem in elements {
+        if !progress.step() {
+            return;
+        }
+
         cr.apply_transform(&elem.transform);
 
         match elem.data {
@@ -99,43 +322,101 @@ fn render_group(
                 text::draw(doc, text, cr);
             }
             dom::Type::Image(ref img) => {
-                image::draw(img, cr);
+                if let dom::ImageData::Svg(ref sub_doc) = img.data {
+                    render_svg_image(sub_doc, &img.rect, cr, img_size, cache, progress);
+                } else {
+                    image::draw(img, cache, cr);
+                }
             }
             dom::Type::Group(ref g) => {
-                let sub_surface = cairo::ImageSurface::create(
-                    cairo::Format::ARgb32,
-                    img_size.w as i32,
-                    img_size.h as i32
-                );
+                if render_utils::is_invisible(g) {
+                    // Nothing to render.
+                } else if render_utils::needs_layer(g) {
+                    render_layer(doc, elem, g, cr, matrix, img_size, cache, progress);
+                } else {
+                    render_group(doc, &g.children, cr, &cr.get_matrix(), img_size, cache, progress);
+                }
+            }
+        }
 
-                let sub_surface = match sub_surface {
-                    Ok(surf) => surf,
-                    Err(_) => {
-                        warn!("Subsurface creation failed.");
-                        continue;
-                    }
-                };
+        cr.set_matrix(*matrix);
+    }
+}
 
-                let sub_cr = cairo::Context::new(&sub_surface);
-                sub_cr.set_matrix(cr.get_matrix());
+fn render_svg_image<'a>(
+    sub_doc: &'a dom::Document,
+    rect: &Rect,
+    cr: &cairo::Context,
+    img_size: Size,
+    cache: &mut ImageCache,
+    progress: &Progress,
+) {
+    cr.save();
 
-                render_group(doc, &g.children, &sub_cr, &cr.get_matrix(), img_size);
+    cr.rectangle(rect.x, rect.y, rect.w, rect.h);
+    cr.clip();
 
-                let curr_matrix = cr.get_matrix();
-                cr.set_matrix(cairo::Matrix::identity());
+    let (dx, dy, sx, sy) = render_utils::view_box_transform(&sub_doc.view_box, rect);
+    cr.transform(cairo::Matrix::new(sx, 0.0, 0.0, sy, dx, dy));
 
-                cr.set_source_surface(&sub_surface, 0.0, 0.0);
+    render_group(sub_doc, &sub_doc.elements, cr, &cr.get_matrix(), img_size, cache, progress);
 
-                if let Some(opacity) = g.opacity {
-                    cr.paint_with_alpha(opacity);
-                } else {
-                    cr.paint();
-                }
+    cr.restore();
+}
 
-                cr.set_matrix(curr_matrix);
-            }
+// The layer is allocated only for the group area on the canvas.
+#[allow(clippy::too_many_arguments)]
+fn render_layer<'a>(
+    doc: &dom::Document,
+    elem: &dom::Element,
+    g: &'a dom::Group,
+    cr: &cairo::Context,
+    matrix: &cairo::Matrix,
+    img_size: Size,
+    cache: &mut ImageCache,
+    progress: &Progress,
+) {
+    let metrics = TextMetrics::new(doc.dpi);
+    let ts = Transform::new(matrix.xx, matrix.yx, matrix.xy, matrix.yy, matrix.x0, matrix.y0);
+    let rect = match render_utils::layer_rect(elem, &ts, img_size, &metrics) {
+        Some(rect) => rect,
+        None => return,
+    };
+
+    let sub_surface = cairo::ImageSurface::create(
+        cairo::Format::ARgb32,
+        rect.w as i32,
+        rect.h as i32
+    );
+
+    let sub_surface = match sub_surface {
+        Ok(surf) => surf,
+        Err(_) => {
+            diag!(Warning, LayerFailed, elem.id, "Subsurface creation failed.");
+            return;
         }
+    };
 
-        cr.set_matrix(*matrix);
+    // Move the layer area to the subsurface origin.
+    let mut sub_matrix = cr.get_matrix();
+    sub_matrix.x0 -= rect.x;
+    sub_matrix.y0 -= rect.y;
+
+    let sub_cr = cairo::Context::new(&sub_surface);
+    sub_cr.set_matrix(sub_matrix);
+
+    render_group(doc, &g.children, &sub_cr, &sub_matrix, rect.size(), cache, progress);
+
+    let curr_matrix = cr.get_matrix();
+    cr.set_matrix(cairo::Matrix::identity());
+
+    cr.set_source_surface(&sub_surface, rect.x, rect.y);
+
+    if let Some(opacity) = g.opacity {
+        cr.paint_with_alpha(opacity);
+    } else {
+        cr.paint();
     }
+
+    cr.set_matrix(curr_matrix);
 }
@@ -65,6 +65,50 @@ pub fn draw(
     }
 }
 
+pub fn calc_bbox(elem: &dom::Text, dpi: f64, cr: &cairo::Context) -> Option<Rect> {
+    let mut bbox: Option<Rect> = None;
+
+    for chunk in &elem.children {
+        let mut layouts = Vec::with_capacity(chunk.children.len());
+        let mut chunk_width = 0.0;
+
+        for tspan in &chunk.children {
+            let pango_context = pc::create_context(cr).unwrap();
+            pc::context_set_resolution(&pango_context, dpi);
+
+            let font = init_font(&tspan.font, dpi);
+
+            let layout = pango::Layout::new(&pango_context);
+            layout.set_font_description(Some(&font));
+            layout.set_text(&tspan.text);
+
+            chunk_width += layout.get_size().0 as f64 / PANGO_SCALE_64;
+            layouts.push(layout);
+        }
+
+        let mut x = process_text_anchor(chunk.x, chunk.anchor, chunk_width);
+
+        for layout in &layouts {
+            let mut layout_iter = layout.get_iter().unwrap();
+            let baseline_offset = (layout_iter.get_baseline() / pango::SCALE) as f64;
+
+            let r = calc_layout_bbox(layout, x, chunk.y - baseline_offset);
+
+            // Layouts with spaces only doesn't have an ink rect.
+            if r.w > 0.0 && r.h > 0.0 {
+                bbox = Some(match bbox {
+                    Some(prev) => prev.union(&r),
+                    None => r,
+                });
+            }
+
+            x += layout.get_size().0 as f64 / PANGO_SCALE_64;
+        }
+    }
+
+    bbox
+}
+
 fn draw_tspan(
     doc: &dom::Document,
     tspan: &dom::TSpan,
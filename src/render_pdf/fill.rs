@@ -0,0 +1,64 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom::types::{
+    FuzzyEq,
+    Transform,
+};
+
+use dom;
+
+use math::{
+    Rect,
+};
+
+use {
+    Result,
+};
+
+use super::{
+    gradient,
+    writer,
+    Context,
+};
+
+
+/// Writes fill paint operators.
+///
+/// Returns `false` when the fill should not be rendered.
+pub fn apply(
+    ctx: &mut Context,
+    fill: &dom::Fill,
+    bbox: &Rect,
+    ts: &Transform,
+    content: &mut String,
+) -> Result<bool> {
+    match fill.paint {
+        dom::Paint::Color(c) => {
+            if fill.opacity.fuzzy_ne(&1.0) {
+                let gs = ctx.ext_gstate(&format!("/ca {}", writer::num(fill.opacity)));
+                content.push_str(&format!("/{} gs\n", gs));
+            }
+
+            content.push_str(&format!("{} {} {} rg\n",
+                                      writer::num(c.red as f64 / 255.0),
+                                      writer::num(c.green as f64 / 255.0),
+                                      writer::num(c.blue as f64 / 255.0)));
+            Ok(true)
+        }
+        dom::Paint::Link(id) => {
+            let doc = ctx.doc;
+            let ops = gradient::prepare(ctx, &doc.get_defs(id).data, fill.opacity,
+                                        bbox, bbox, ts, gradient::Target::Fill)?;
+
+            match ops {
+                Some(ops) => {
+                    content.push_str(&ops);
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+    }
+}
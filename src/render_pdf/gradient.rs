@@ -0,0 +1,341 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::f64;
+
+use svgdom::types::{
+    FuzzyEq,
+    Transform,
+};
+
+use dom::{
+    self,
+    GradientUnits,
+    SpreadMethod,
+};
+
+use math::{
+    Rect,
+};
+
+use {
+    Result,
+};
+
+use super::{
+    writer,
+    Context,
+};
+
+
+/// The maximum number of gradient repetitions for `reflect` and `repeat` spread methods.
+const MAX_REPEATS: f64 = 1000.0;
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Target {
+    Fill,
+    Stroke,
+}
+
+struct Shading {
+    kind: u8,
+    coords: Vec<f64>,
+    t0: f64,
+    t1: f64,
+}
+
+/// Writes gradient paint operators.
+///
+/// `bbox` is an object bounding box, used by the `objectBoundingBox` units.
+/// `area` is a region that will be painted, used to emulate spread methods.
+///
+/// Returns `None` when the gradient should not be rendered.
+pub fn prepare(
+    ctx: &mut Context,
+    g: &dom::RefType,
+    opacity: f64,
+    bbox: &Rect,
+    area: &Rect,
+    ts: &Transform,
+    target: Target,
+) -> Result<Option<String>> {
+    let base = match *g {
+        dom::RefType::LinearGradient(ref lg) => &lg.d,
+        dom::RefType::RadialGradient(ref rg) => &rg.d,
+    };
+
+    // 'objectBoundingBox' units on a zero-sized element should not be rendered.
+    if base.units == GradientUnits::ObjectBoundingBox
+        && (bbox.w.fuzzy_eq(&0.0) || bbox.h.fuzzy_eq(&0.0)) {
+        return Ok(None);
+    }
+
+    // Gradient space to user space transform.
+    let mut grad_ts = Transform::default();
+    if base.units == GradientUnits::ObjectBoundingBox {
+        grad_ts = Transform::new(bbox.w, 0.0, 0.0, bbox.h, bbox.x, bbox.y);
+    }
+    grad_ts.append(&base.transform);
+
+    // Painted area corners in the gradient space.
+    let corners = match invert(&grad_ts) {
+        Some(inv) => {
+            [
+                inv.apply(area.x, area.y),
+                inv.apply(area.x + area.w, area.y),
+                inv.apply(area.x, area.y + area.h),
+                inv.apply(area.x + area.w, area.y + area.h),
+            ]
+        }
+        None => return Ok(None),
+    };
+
+    let shading = match *g {
+        dom::RefType::LinearGradient(ref lg) => {
+            prepare_linear(lg, &corners)
+        }
+        dom::RefType::RadialGradient(ref rg) => {
+            prepare_radial(rg, &corners)
+        }
+    };
+
+    let mut pattern_ts = *ts;
+    pattern_ts.append(&grad_ts);
+
+    let color_fn = stops_function(ctx, &base.stops, |s| {
+        vec![s.color.red as f64 / 255.0, s.color.green as f64 / 255.0, s.color.blue as f64 / 255.0]
+    });
+    let color_pattern = add_pattern(ctx, &shading, base.spread_method, color_fn,
+                                    "DeviceRGB", &pattern_ts)?;
+
+    let mut content = String::new();
+
+    let first_opacity = base.stops[0].opacity;
+    let is_solid_opacity = base.stops.iter().all(|s| s.opacity.fuzzy_eq(&first_opacity));
+
+    if is_solid_opacity {
+        let alpha = first_opacity * opacity;
+        if alpha.fuzzy_ne(&1.0) {
+            let key = if target == Target::Fill { "ca" } else { "CA" };
+            let gs = ctx.ext_gstate(&format!("/{} {}", key, writer::num(alpha)));
+            content.push_str(&format!("/{} gs\n", gs));
+        }
+    } else {
+        // PDF shadings doesn't support an alpha channel,
+        // so we have to use a soft mask with a grayscale version of the gradient.
+        let alpha_fn = stops_function(ctx, &base.stops, |s| vec![s.opacity * opacity]);
+        let alpha_pattern = add_pattern(ctx, &shading, base.spread_method, alpha_fn,
+                                        "DeviceGray", &pattern_ts)?;
+
+        let page = ctx.page;
+        let mask_content = format!("/Pattern cs /{} scn {} {} {} {} re f\n",
+                                   alpha_pattern,
+                                   writer::num(page.x), writer::num(page.y),
+                                   writer::num(page.w), writer::num(page.h));
+        let mask_id = ctx.add_group_form(&mask_content, "DeviceGray")?;
+
+        let gs = ctx.ext_gstate(&format!("/SMask << /Type /Mask /S /Luminosity /G {} 0 R >>",
+                                         mask_id));
+        content.push_str(&format!("/{} gs\n", gs));
+    }
+
+    match target {
+        Target::Fill => content.push_str(&format!("/Pattern cs /{} scn\n", color_pattern)),
+        Target::Stroke => content.push_str(&format!("/Pattern CS /{} SCN\n", color_pattern)),
+    }
+
+    Ok(Some(content))
+}
+
+fn prepare_linear(g: &dom::LinearGradient, corners: &[(f64, f64); 4]) -> Shading {
+    let dx = g.x2 - g.x1;
+    let dy = g.y2 - g.y1;
+    let len2 = dx * dx + dy * dy;
+
+    let (t0, t1) = if g.d.spread_method == SpreadMethod::Pad || len2.fuzzy_eq(&0.0) {
+        (0.0, 1.0)
+    } else {
+        // Project the painted area onto the gradient vector.
+        let mut min_t = f64::MAX;
+        let mut max_t = f64::MIN;
+        for &(x, y) in corners {
+            let t = ((x - g.x1) * dx + (y - g.y1) * dy) / len2;
+            min_t = min_t.min(t);
+            max_t = max_t.max(t);
+        }
+
+        clamp_range(min_t.min(0.0).floor(), max_t.max(1.0).ceil())
+    };
+
+    Shading {
+        kind: 2,
+        coords: vec![g.x1 + dx * t0, g.y1 + dy * t0, g.x1 + dx * t1, g.y1 + dy * t1],
+        t0,
+        t1,
+    }
+}
+
+fn prepare_radial(g: &dom::RadialGradient, corners: &[(f64, f64); 4]) -> Shading {
+    // SVG radial gradient is a set of circles from the focal point with zero radius
+    // to the center with 'r' radius. Circle 't' has a center at 'f + t * (c - f)'
+    // and a 't * r' radius, so we can extend it to any 't'.
+    let dx = g.cx - g.fx;
+    let dy = g.cy - g.fy;
+    let focal_dist = (dx * dx + dy * dy).sqrt();
+
+    let mut t1 = 1.0;
+    if g.d.spread_method != SpreadMethod::Pad && g.r > focal_dist {
+        // A point is inside the circle 't' if '|p - f| <= t * (r - |c - f|)'.
+        let mut max_dist: f64 = 0.0;
+        for &(x, y) in corners {
+            let d = ((x - g.fx) * (x - g.fx) + (y - g.fy) * (y - g.fy)).sqrt();
+            max_dist = max_dist.max(d);
+        }
+
+        t1 = clamp_range(0.0, (max_dist / (g.r - focal_dist)).max(1.0).ceil()).1;
+    }
+
+    Shading {
+        kind: 3,
+        coords: vec![g.fx, g.fy, 0.0, g.fx + dx * t1, g.fy + dy * t1, g.r * t1],
+        t0: 0.0,
+        t1,
+    }
+}
+
+fn clamp_range(t0: f64, t1: f64) -> (f64, f64) {
+    if t1 - t0 > MAX_REPEATS {
+        diag!(Info, UnsupportedAttribute, "", "Gradient requires too many repetitions. Clamped.");
+        let t0 = t0.max(-MAX_REPEATS / 2.0);
+        return (t0, t0 + MAX_REPEATS);
+    }
+
+    (t0, t1)
+}
+
+fn add_pattern(
+    ctx: &mut Context,
+    shading: &Shading,
+    spread: SpreadMethod,
+    stops_fn: usize,
+    color_space: &str,
+    ts: &Transform,
+) -> Result<String> {
+    let func = spread_function(spread, stops_fn, shading.t0, shading.t1);
+    let coords: Vec<String> = shading.coords.iter().map(|n| writer::num(*n)).collect();
+
+    let shading_id = ctx.writer.add(format!(
+        "<< /ShadingType {} /ColorSpace /{} /Coords [{}] /Domain [{} {}] \
+         /Function {} /Extend [true true] >>",
+        shading.kind, color_space, coords.join(" "),
+        writer::num(shading.t0), writer::num(shading.t1), func
+    ));
+
+    let pattern_id = ctx.writer.add(format!(
+        "<< /Type /Pattern /PatternType 2 /Shading {} 0 R /Matrix [{}] >>",
+        shading_id, writer::matrix(ts)
+    ));
+
+    Ok(ctx.res.add_pattern(pattern_id))
+}
+
+// Emulates 'reflect' and 'repeat' spread methods by stitching
+// the stops function over the whole shading domain.
+fn spread_function(spread: SpreadMethod, stops_fn: usize, t0: f64, t1: f64) -> String {
+    if spread == SpreadMethod::Pad {
+        return format!("{} 0 R", stops_fn);
+    }
+
+    let mut funcs = Vec::new();
+    let mut bounds = Vec::new();
+    let mut encode = Vec::new();
+
+    let mut t = t0;
+    while t < t1 {
+        funcs.push(format!("{} 0 R", stops_fn));
+
+        if t + 1.0 < t1 {
+            bounds.push(writer::num(t + 1.0));
+        }
+
+        let is_odd = (t as i64) % 2 != 0;
+        if spread == SpreadMethod::Reflect && is_odd {
+            encode.push("1 0");
+        } else {
+            encode.push("0 1");
+        }
+
+        t += 1.0;
+    }
+
+    format!("<< /FunctionType 3 /Domain [{} {}] /Functions [{}] /Bounds [{}] /Encode [{}] >>",
+            writer::num(t0), writer::num(t1), funcs.join(" "), bounds.join(" "), encode.join(" "))
+}
+
+// Creates a function object that maps the [0..1] range to stops values.
+fn stops_function<F>(ctx: &mut Context, stops: &[dom::Stop], value: F) -> usize
+    where F: Fn(&dom::Stop) -> Vec<f64>
+{
+    let mut list: Vec<(f64, Vec<f64>)> = stops.iter().map(|s| (s.offset, value(s))).collect();
+
+    // Make sure that the whole range is covered.
+    if list[0].0 > 0.0 {
+        let v = list[0].1.clone();
+        list.insert(0, (0.0, v));
+    }
+
+    if list[list.len() - 1].0 < 1.0 {
+        let v = list[list.len() - 1].1.clone();
+        list.push((1.0, v));
+    }
+
+    let fmt_values = |v: &[f64]| -> String {
+        v.iter().map(|n| writer::num(*n)).collect::<Vec<_>>().join(" ")
+    };
+
+    let mut funcs = Vec::new();
+    let mut bounds = Vec::new();
+    for pair in list.windows(2) {
+        let (start, end) = (&pair[0], &pair[1]);
+
+        // Skip zero-length segments. They are produced by stops with the same offset.
+        if end.0.fuzzy_eq(&start.0) {
+            continue;
+        }
+
+        if !funcs.is_empty() {
+            bounds.push(writer::num(start.0));
+        }
+
+        funcs.push(format!("<< /FunctionType 2 /Domain [0 1] /C0 [{}] /C1 [{}] /N 1 >>",
+                           fmt_values(&start.1), fmt_values(&end.1)));
+    }
+
+    if funcs.len() == 1 {
+        return ctx.writer.add(funcs.remove(0));
+    }
+
+    let encode: Vec<&str> = funcs.iter().map(|_| "0 1").collect();
+    ctx.writer.add(format!(
+        "<< /FunctionType 3 /Domain [0 1] /Functions [{}] /Bounds [{}] /Encode [{}] >>",
+        funcs.join(" "), bounds.join(" "), encode.join(" ")
+    ))
+}
+
+fn invert(ts: &Transform) -> Option<Transform> {
+    let det = ts.a * ts.d - ts.b * ts.c;
+    if det.fuzzy_eq(&0.0) {
+        return None;
+    }
+
+    Some(Transform::new(
+        ts.d / det,
+        -ts.b / det,
+        -ts.c / det,
+        ts.a / det,
+        (ts.c * ts.f - ts.d * ts.e) / det,
+        (ts.b * ts.e - ts.a * ts.f) / det,
+    ))
+}
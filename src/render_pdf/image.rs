@@ -0,0 +1,206 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom::types::{
+    Transform,
+};
+
+use dom;
+use render_utils;
+
+use {
+    Result,
+};
+
+use super::{
+    writer,
+    Context,
+};
+
+
+pub fn draw<'a>(
+    ctx: &mut Context<'a>,
+    image: &'a dom::Image,
+    ts: &Transform,
+    content: &mut String,
+) -> Result<()> {
+    let key = match render_utils::ImageKey::new(&image.data) {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+
+    // The same image can be referenced multiple times via 'use',
+    // so we embed it only once.
+    let name = match ctx.images.get(&key).cloned() {
+        Some(name) => name,
+        None => {
+            let name = embed(ctx, &image.data)?;
+            ctx.images.insert(key, name.clone());
+            name
+        }
+    };
+
+    let name = match name {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+
+    let r = image.rect;
+
+    // Image space is an unit square with a bottom-left origin.
+    let img_ts = Transform::new(r.w, 0.0, 0.0, -r.h, r.x, r.y + r.h);
+
+    content.push_str(&format!("q {} cm {} cm /{} Do Q\n",
+                              writer::matrix(ts), writer::matrix(&img_ts), name));
+
+    Ok(())
+}
+
+fn embed(ctx: &mut Context, data: &dom::ImageData) -> Result<Option<String>> {
+    // JPEG images are supported by PDF as is.
+    if let dom::ImageData::Raw(ref data, dom::ImageDataKind::JPEG) = *data {
+        if let Some(id) = embed_jpeg(ctx, data) {
+            return Ok(Some(ctx.res.add_xobject(id)));
+        }
+    }
+
+    let img = match render_utils::load_raster_image(data) {
+        Some(v) => v.to_rgba(),
+        None => return Ok(None),
+    };
+    let (w, h) = (img.width(), img.height());
+
+    let mut rgb = Vec::with_capacity((w * h * 3) as usize);
+    let mut alpha = Vec::with_capacity((w * h) as usize);
+    for p in img.chunks(4) {
+        rgb.extend_from_slice(&p[0..3]);
+        alpha.push(p[3]);
+    }
+
+    let mut dict = format!("/Type /XObject /Subtype /Image /Width {} /Height {} \
+                            /ColorSpace /DeviceRGB /BitsPerComponent 8", w, h);
+
+    if alpha.iter().any(|a| *a != 255) {
+        let mask_id = ctx.writer.add_stream(
+            &format!("/Type /XObject /Subtype /Image /Width {} /Height {} \
+                      /ColorSpace /DeviceGray /BitsPerComponent 8", w, h),
+            &alpha
+        )?;
+
+        dict.push_str(&format!(" /SMask {} 0 R", mask_id));
+    }
+
+    let id = ctx.writer.add_stream(&dict, &rgb)?;
+    Ok(Some(ctx.res.add_xobject(id)))
+}
+
+/// Embeds a JPEG image without decoding.
+///
+/// Returns `None` when the image can't be passed through, like a CMYK one.
+fn embed_jpeg(ctx: &mut Context, data: &[u8]) -> Option<usize> {
+    let (w, h, components) = jpeg_info(data)?;
+
+    let color_space = match components {
+        1 => "DeviceGray",
+        3 => "DeviceRGB",
+        _ => return None,
+    };
+
+    let dict = format!("/Type /XObject /Subtype /Image /Width {} /Height {} \
+                        /ColorSpace /{} /BitsPerComponent 8 /Filter /DCTDecode",
+                       w, h, color_space);
+
+    Some(ctx.writer.add_encoded_stream(&dict, data))
+}
+
+/// Returns the size and the number of color components of a JPEG image.
+///
+/// Only 8-bit images are supported, because it's the only precision supported by PDF.
+fn jpeg_info(data: &[u8]) -> Option<(u16, u16, u8)> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+
+    let mut i = 2;
+    while i + 4 <= data.len() {
+        if data[i] != 0xff {
+            return None;
+        }
+
+        let marker = data[i + 1];
+
+        // Markers can be padded with 0xFF.
+        if marker == 0xff {
+            i += 1;
+            continue;
+        }
+
+        let len = (data[i + 2] as usize) << 8 | data[i + 3] as usize;
+
+        match marker {
+            // SOFn markers, except DHT, JPG and DAC.
+            0xc0..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+                // Precision, height, width and the number of components.
+                let frame = data.get((i + 4)..(i + 10))?;
+                let h = (frame[1] as u16) << 8 | frame[2] as u16;
+                let w = (frame[3] as u16) << 8 | frame[4] as u16;
+
+                if frame[0] != 8 || w == 0 || h == 0 {
+                    return None;
+                }
+
+                return Some((w, h, frame[5]));
+            }
+            // Start of scan. The frame header must precede it.
+            0xda => return None,
+            _ => {}
+        }
+
+        i += 2 + len;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::jpeg_info;
+
+    // SOI, APP0 with a 2 bytes payload, SOF0 for a 3x2 RGB image.
+    const HEADER: &[u8] = &[
+        0xff, 0xd8,
+        0xff, 0xe0, 0x00, 0x04, 0x00, 0x00,
+        0xff, 0xc0, 0x00, 0x11, 0x08, 0x00, 0x02, 0x00, 0x03, 0x03,
+    ];
+
+    #[test]
+    fn jpeg_info_rgb() {
+        assert_eq!(jpeg_info(HEADER), Some((3, 2, 3)));
+    }
+
+    #[test]
+    fn jpeg_info_fill_bytes() {
+        let mut data = HEADER[..8].to_vec();
+        data.extend_from_slice(&[0xff, 0xff]);
+        data.extend_from_slice(&HEADER[8..]);
+        let last = data.len() - 1;
+        data[last] = 1;
+        assert_eq!(jpeg_info(&data), Some((3, 2, 1)));
+    }
+
+    #[test]
+    fn jpeg_info_12_bit() {
+        let mut data = HEADER.to_vec();
+        data[12] = 12;
+        assert_eq!(jpeg_info(&data), None);
+    }
+
+    #[test]
+    fn jpeg_info_invalid() {
+        assert_eq!(jpeg_info(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(jpeg_info(&HEADER[..14]), None);
+        // Start of scan before the frame header.
+        assert_eq!(jpeg_info(&[0xff, 0xd8, 0xff, 0xda, 0x00, 0x02]), None);
+    }
+}
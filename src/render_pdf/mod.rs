@@ -0,0 +1,349 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! PDF backend implementation.
+//!
+//! Unlike other backends, it doesn't rasterize anything,
+//! but writes the render tree as PDF content streams.
+//! No C libraries are required.
+//!
+//! Text is not supported yet.
+
+use std::collections::HashMap;
+
+use svgdom::types::{
+    FuzzyEq,
+    Transform,
+};
+
+use dom;
+
+use math::{
+    Rect,
+    Size,
+};
+
+use {
+    ErrorKind,
+    Options,
+    Result,
+};
+
+use progress::Progress;
+use render_utils;
+
+
+mod fill;
+mod gradient;
+mod image;
+mod path;
+mod stroke;
+mod writer;
+
+use self::writer::{
+    Resources,
+    Writer,
+};
+
+
+/// Rendering context.
+struct Context<'a> {
+    doc: &'a dom::Document,
+    writer: Writer,
+    res: Resources,
+    /// Resources dictionary object number.
+    res_id: usize,
+    /// Page rect in the default PDF coordinates.
+    page: Rect,
+    /// Already created `ExtGState` objects by their content.
+    ext_gstates: HashMap<String, String>,
+    /// Already embedded images. `None` for images that can't be decoded.
+    images: HashMap<render_utils::ImageKey, Option<String>>,
+    progress: Progress<'a>,
+}
+
+impl<'a> Context<'a> {
+    /// Returns a resource name of an `ExtGState` with the specified dictionary entries.
+    fn ext_gstate(&mut self, entries: &str) -> String {
+        if let Some(name) = self.ext_gstates.get(entries) {
+            return name.clone();
+        }
+
+        let id = self.writer.add(format!("<< /Type /ExtGState {} >>", entries));
+        let name = self.res.add_ext_gstate(id);
+        self.ext_gstates.insert(entries.to_string(), name.clone());
+        name
+    }
+
+    /// Creates a transparency group form XObject and returns its object number.
+    fn add_group_form(&mut self, content: &str, color_space: &str) -> Result<usize> {
+        let dict = format!(
+            "/Type /XObject /Subtype /Form /BBox [{} {} {} {}] \
+             /Group << /Type /Group /S /Transparency /CS /{} >> /Resources {} 0 R",
+            writer::num(self.page.x), writer::num(self.page.y),
+            writer::num(self.page.x + self.page.w), writer::num(self.page.y + self.page.h),
+            color_space, self.res_id
+        );
+
+        self.writer.add_stream(&dict, content.as_bytes())
+    }
+}
+
+
+/// Renders SVG to PDF.
+///
+/// The page size is calculated from the image size using `Options::fit_to`
+/// and `Options::dpi`, so an image rendered at 96 DPI has the same physical
+/// size as a raster one.
+pub fn render_to_pdf(doc: &dom::Document, opt: &Options) -> Result<Vec<u8>> {
+    render(doc, &doc.elements, &doc.view_box, &doc.size, opt)
+}
+
+/// Renders a single element to PDF.
+///
+/// The page is cropped by the element bounding box.
+/// When `only` is set, all other elements are hidden,
+/// otherwise everything inside the bounding box is rendered.
+///
+/// Text is not supported, so it's ignored during the bounding box calculation.
+pub fn render_node_to_pdf(
+    doc: &dom::Document,
+    id: &str,
+    only: bool,
+    opt: &Options,
+) -> Result<Vec<u8>> {
+    let node = render_utils::prepare_node(doc, id, only, None)?;
+    render(doc, node.elements(doc), &node.view_box, &node.size, opt)
+}
+
+/// Renders an area of the document to PDF.
+///
+/// `area` is in the user coordinates, aka `viewBox`.
+/// The page size is the area size at the document scale, adjusted by `Options::fit_to`.
+pub fn render_area_to_pdf(doc: &dom::Document, area: Rect, opt: &Options) -> Result<Vec<u8>> {
+    let size = render_utils::prepare_area(doc, &area)?;
+    render(doc, &doc.elements, &area, &size, opt)
+}
+
+fn render<'a>(
+    doc: &'a dom::Document,
+    elements: &'a [dom::Element],
+    view_box: &Rect,
+    size: &Size,
+    opt: &'a Options,
+) -> Result<Vec<u8>> {
+    let img_size = render_utils::fit_to(size, opt.fit_to);
+    let scale = 72.0 / opt.dpi;
+    let page = Rect::new(0.0, 0.0, img_size.w * scale, img_size.h * scale);
+
+    let mut ctx = Context {
+        doc,
+        writer: Writer::new(),
+        res: Resources::new(),
+        res_id: 0,
+        page,
+        ext_gstates: HashMap::new(),
+        images: HashMap::new(),
+        progress: Progress::new(opt, elements),
+    };
+
+    let catalog_id = ctx.writer.alloc();
+    let pages_id = ctx.writer.alloc();
+    let page_id = ctx.writer.alloc();
+    let content_id = ctx.writer.alloc();
+    ctx.res_id = ctx.writer.alloc();
+
+    // PDF has a bottom-left origin, so we have to flip the Y axis.
+    let mut ts = Transform::new(scale, 0.0, 0.0, -scale, 0.0, page.h);
+
+    // Apply viewBox.
+    let img_view = Rect::new(0.0, 0.0, img_size.w, img_size.h);
+    {
+        let (dx, dy, sx, sy) = render_utils::view_box_transform(view_box, &img_view);
+        ts.append(&Transform::new(sx, 0.0, 0.0, sy, dx, dy));
+    }
+
+    let mut content = String::new();
+
+    // Fill background.
+    if let Some(c) = opt.background {
+        content.push_str(&format!("{} {} {} rg 0 0 {} {} re f\n",
+                                  color_comp(c.red), color_comp(c.green), color_comp(c.blue),
+                                  writer::num(page.w), writer::num(page.h)));
+    }
+
+    render_group(&mut ctx, elements, &ts, &mut content)?;
+    ctx.progress.finish()?;
+
+    ctx.writer.set_stream(content_id, "", content.as_bytes())?;
+
+    let res_dict = ctx.res.to_dict();
+    ctx.writer.set(ctx.res_id, res_dict);
+
+    let page_dict = format!(
+        "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources {} 0 R /Contents {} 0 R \
+         /Group << /Type /Group /S /Transparency /CS /DeviceRGB >> >>",
+        pages_id, writer::num(page.w), writer::num(page.h), ctx.res_id, content_id
+    );
+    ctx.writer.set(page_id, page_dict);
+    ctx.writer.set(pages_id, format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>", page_id));
+    ctx.writer.set(catalog_id, format!("<< /Type /Catalog /Pages {} 0 R >>", pages_id));
+
+    Ok(ctx.writer.finish(catalog_id))
+}
+
+// Each element is rendered inside a 'q'/'Q' pair with an absolute transform,
+// so the current transformation matrix between elements is always the page one.
+// This way gradient patterns and group XObjects can be defined in the page coordinates.
+fn render_group<'a>(
+    ctx: &mut Context<'a>,
+    elements: &'a [dom::Element],
+    ts: &Transform,
+    content: &mut String,
+) -> Result<()> {
+    for elem in elements {
+        if !ctx.progress.step() {
+            return Err(ErrorKind::Cancelled.into());
+        }
+
+        let mut elem_ts = *ts;
+        elem_ts.append(&elem.transform);
+
+        match elem.data {
+            dom::Type::Path(ref path) => {
+                path::draw(ctx, path, &elem_ts, content)?;
+            }
+            dom::Type::Text(_) => {
+                diag!(Warning, UnsupportedElement, elem.id,
+                      "Text is not supported by the PDF backend. Skipped.");
+            }
+            dom::Type::Image(ref img) => {
+                if let dom::ImageData::Svg(ref sub_doc) = img.data {
+                    render_svg_image(ctx, sub_doc, &img.rect, &elem_ts, content)?;
+                } else {
+                    image::draw(ctx, img, &elem_ts, content)?;
+                }
+            }
+            dom::Type::Group(ref g) => {
+                let opacity = g.opacity.unwrap_or(1.0);
+
+                if opacity.fuzzy_eq(&1.0) {
+                    render_group(ctx, &g.children, &elem_ts, content)?;
+                    continue;
+                }
+
+                let mut sub_content = String::new();
+                render_group(ctx, &g.children, &elem_ts, &mut sub_content)?;
+
+                let form_id = ctx.add_group_form(&sub_content, "DeviceRGB")?;
+                let form_name = ctx.res.add_xobject(form_id);
+
+                let op = writer::num(opacity);
+                let gs = ctx.ext_gstate(&format!("/ca {} /CA {}", op, op));
+
+                content.push_str(&format!("q /{} gs /{} Do Q\n", gs, form_name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Fill and stroke are referencing gradients by index,
+// so the image document should be the current one while rendering it.
+fn render_svg_image<'a>(
+    ctx: &mut Context<'a>,
+    sub_doc: &'a dom::Document,
+    rect: &Rect,
+    ts: &Transform,
+    content: &mut String,
+) -> Result<()> {
+    // Clip by the image rectangle. The clip path is in the page coordinates,
+    // just like the content.
+    let (x1, y1) = ts.apply(rect.x, rect.y);
+    let (x2, y2) = ts.apply(rect.x + rect.w, rect.y);
+    let (x3, y3) = ts.apply(rect.x + rect.w, rect.y + rect.h);
+    let (x4, y4) = ts.apply(rect.x, rect.y + rect.h);
+    content.push_str(&format!("q {} {} m {} {} l {} {} l {} {} l h W n\n",
+                              writer::num(x1), writer::num(y1), writer::num(x2), writer::num(y2),
+                              writer::num(x3), writer::num(y3), writer::num(x4), writer::num(y4)));
+
+    let mut sub_ts = *ts;
+    {
+        let (dx, dy, sx, sy) = render_utils::view_box_transform(&sub_doc.view_box, rect);
+        sub_ts.append(&Transform::new(sx, 0.0, 0.0, sy, dx, dy));
+    }
+
+    let doc = ctx.doc;
+    ctx.doc = sub_doc;
+    let res = render_group(ctx, &sub_doc.elements, &sub_ts, content);
+    ctx.doc = doc;
+    res?;
+
+    content.push_str("Q\n");
+
+    Ok(())
+}
+
+fn color_comp(c: u8) -> String {
+    writer::num(c as f64 / 255.0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::str;
+
+    use super::*;
+    use test_utils;
+
+    fn rfind(data: &[u8], needle: &[u8]) -> Option<usize> {
+        data.windows(needle.len()).rposition(|w| w == needle)
+    }
+
+    fn line(data: &[u8], pos: usize) -> &str {
+        let end = data[pos..].iter().position(|c| *c == b'\n').unwrap();
+        str::from_utf8(&data[pos..pos + end]).unwrap()
+    }
+
+    #[test]
+    fn structure() {
+        let doc = test_utils::parse("width='100' height='100'", "\
+            <linearGradient id='lg'>\
+                <stop offset='0' stop-color='green'/>\
+                <stop offset='1' stop-color='blue' stop-opacity='0.5'/>\
+            </linearGradient>\
+            <rect width='50' height='50' fill='url(#lg)'/>\
+            <rect x='50' width='50' height='50' fill='green' stroke='black' opacity='0.5'/>");
+        let data = render_to_pdf(&doc, &Options::default()).unwrap();
+
+        assert!(data.starts_with(b"%PDF-1.4\n"));
+        assert!(data.ends_with(b"%%EOF\n"));
+
+        // `startxref` points to the cross-reference table.
+        let startxref = rfind(&data, b"startxref\n").unwrap();
+        let xref_pos: usize = line(&data, startxref + 10).parse().unwrap();
+        assert_eq!(line(&data, xref_pos), "xref");
+
+        let header = line(&data, xref_pos + 5);
+        let count: usize = header.trim_start_matches("0 ").parse().unwrap();
+        assert!(count > 1);
+
+        // Each entry is exactly 20 bytes long and points to its object.
+        let entries = xref_pos + 5 + header.len() + 1;
+        assert_eq!(&data[entries..entries + 20], b"0000000000 65535 f \n");
+        for i in 1..count {
+            let entry = line(&data, entries + i * 20);
+            assert_eq!(entry.len(), 19);
+            assert!(entry.ends_with(" 00000 n "));
+            let offset: usize = entry[..10].parse().unwrap();
+            assert_eq!(line(&data, offset), format!("{} 0 obj", i));
+        }
+
+        let trailer = entries + count * 20;
+        assert_eq!(line(&data, trailer), "trailer");
+        assert!(line(&data, trailer + 8).starts_with(&format!("<< /Size {} /Root ", count)));
+        assert_eq!(trailer + 8 + line(&data, trailer + 8).len() + 1, startxref);
+    }
+}
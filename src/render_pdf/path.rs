@@ -0,0 +1,86 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom::types::{
+    Transform,
+};
+
+use dom;
+
+use {
+    Result,
+};
+
+use super::{
+    fill,
+    stroke,
+    writer,
+    Context,
+};
+
+
+pub fn draw(
+    ctx: &mut Context,
+    elem: &dom::Path,
+    ts: &Transform,
+    content: &mut String,
+) -> Result<()> {
+    let bbox = match elem.fill_bbox() {
+        Some(bbox) => bbox,
+        None => return Ok(()),
+    };
+
+    let path = conv_path(&elem.d);
+
+    if let Some(ref f) = elem.fill {
+        let mut paint = String::new();
+        if fill::apply(ctx, f, &bbox, ts, &mut paint)? {
+            let op = match f.rule {
+                dom::FillRule::NonZero => "f",
+                dom::FillRule::EvenOdd => "f*",
+            };
+
+            content.push_str("q\n");
+            content.push_str(&paint);
+            content.push_str(&format!("{} cm\n{}{}\nQ\n", writer::matrix(ts), path, op));
+        }
+    }
+
+    if let Some(ref s) = elem.stroke {
+        let mut paint = String::new();
+        if stroke::apply(ctx, s, &bbox, ts, &mut paint)? {
+            content.push_str("q\n");
+            content.push_str(&paint);
+            content.push_str(&format!("{} cm\n{}S\nQ\n", writer::matrix(ts), path));
+        }
+    }
+
+    Ok(())
+}
+
+fn conv_path(d: &[dom::PathSegment]) -> String {
+    let mut s = String::new();
+
+    for seg in d {
+        match *seg {
+            dom::PathSegment::MoveTo { x, y } => {
+                s.push_str(&format!("{} {} m\n", writer::num(x), writer::num(y)));
+            }
+            dom::PathSegment::LineTo { x, y } => {
+                s.push_str(&format!("{} {} l\n", writer::num(x), writer::num(y)));
+            }
+            dom::PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                s.push_str(&format!("{} {} {} {} {} {} c\n",
+                                    writer::num(x1), writer::num(y1),
+                                    writer::num(x2), writer::num(y2),
+                                    writer::num(x), writer::num(y)));
+            }
+            dom::PathSegment::ClosePath => {
+                s.push_str("h\n");
+            }
+        }
+    }
+
+    s
+}
@@ -0,0 +1,87 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use svgdom::types::{
+    FuzzyEq,
+    Transform,
+};
+
+use dom;
+
+use math::{
+    Rect,
+};
+
+use {
+    Result,
+};
+
+use super::{
+    gradient,
+    writer,
+    Context,
+};
+
+
+/// Writes stroke paint and style operators.
+///
+/// Returns `false` when the stroke should not be rendered.
+pub fn apply(
+    ctx: &mut Context,
+    stroke: &dom::Stroke,
+    bbox: &Rect,
+    ts: &Transform,
+    content: &mut String,
+) -> Result<bool> {
+    match stroke.paint {
+        dom::Paint::Color(c) => {
+            if stroke.opacity.fuzzy_ne(&1.0) {
+                let gs = ctx.ext_gstate(&format!("/CA {}", writer::num(stroke.opacity)));
+                content.push_str(&format!("/{} gs\n", gs));
+            }
+
+            content.push_str(&format!("{} {} {} RG\n",
+                                      writer::num(c.red as f64 / 255.0),
+                                      writer::num(c.green as f64 / 255.0),
+                                      writer::num(c.blue as f64 / 255.0)));
+        }
+        dom::Paint::Link(id) => {
+            // The painted area is bigger than the object bounding box.
+            let hw = stroke.width / 2.0;
+            let area = Rect::new(bbox.x - hw, bbox.y - hw, bbox.w + stroke.width, bbox.h + stroke.width);
+
+            let doc = ctx.doc;
+            let ops = gradient::prepare(ctx, &doc.get_defs(id).data, stroke.opacity,
+                                        bbox, &area, ts, gradient::Target::Stroke)?;
+
+            match ops {
+                Some(ops) => content.push_str(&ops),
+                None => return Ok(false),
+            }
+        }
+    }
+
+    let linecap = match stroke.linecap {
+        dom::LineCap::Butt => 0,
+        dom::LineCap::Round => 1,
+        dom::LineCap::Square => 2,
+    };
+
+    let linejoin = match stroke.linejoin {
+        dom::LineJoin::Miter => 0,
+        dom::LineJoin::Round => 1,
+        dom::LineJoin::Bevel => 2,
+    };
+
+    content.push_str(&format!("{} w {} J {} j {} M\n",
+                              writer::num(stroke.width), linecap, linejoin,
+                              writer::num(stroke.miterlimit)));
+
+    if let Some(ref list) = stroke.dasharray {
+        let list: Vec<String> = list.iter().map(|n| writer::num(*n)).collect();
+        content.push_str(&format!("[{}] {} d\n", list.join(" "), writer::num(stroke.dashoffset)));
+    }
+
+    Ok(true)
+}
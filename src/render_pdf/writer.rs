@@ -0,0 +1,227 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+
+use libflate::zlib;
+
+use svgdom::types::{
+    Transform,
+};
+
+use {
+    Result,
+};
+
+
+/// A minimal PDF objects writer.
+///
+/// Objects are stored in memory and serialized all at once,
+/// because cross-reference table requires objects offsets.
+pub struct Writer {
+    objects: Vec<Vec<u8>>,
+}
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer {
+            objects: Vec::new(),
+        }
+    }
+
+    /// Reserves an object number, so it can be referenced before it was written.
+    pub fn alloc(&mut self) -> usize {
+        self.objects.push(Vec::new());
+        self.objects.len()
+    }
+
+    /// Sets the object content.
+    pub fn set(&mut self, id: usize, data: String) {
+        self.objects[id - 1] = data.into_bytes();
+    }
+
+    pub fn add(&mut self, data: String) -> usize {
+        let id = self.alloc();
+        self.set(id, data);
+        id
+    }
+
+    /// Sets a compressed stream object content.
+    ///
+    /// `dict` should contain dictionary entries without the `Length` and `Filter` keys.
+    pub fn set_stream(&mut self, id: usize, dict: &str, data: &[u8]) -> Result<()> {
+        let data = compress(data)?;
+
+        let mut dict = dict.to_string();
+        if !dict.is_empty() {
+            dict.push(' ');
+        }
+        dict.push_str("/Filter /FlateDecode");
+
+        self.objects[id - 1] = stream_object(&dict, &data);
+        Ok(())
+    }
+
+    pub fn add_stream(&mut self, dict: &str, data: &[u8]) -> Result<usize> {
+        let id = self.alloc();
+        self.set_stream(id, dict, data)?;
+        Ok(id)
+    }
+
+    /// Adds an already encoded stream object.
+    ///
+    /// `dict` should contain dictionary entries without the `Length` key,
+    /// but with the `Filter` one.
+    pub fn add_encoded_stream(&mut self, dict: &str, data: &[u8]) -> usize {
+        let id = self.alloc();
+        self.objects[id - 1] = stream_object(dict, data);
+        id
+    }
+
+    /// Serializes all objects into a PDF file.
+    ///
+    /// `root` is the catalog object number.
+    pub fn finish(self, root: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        // Binary comment marks the file as binary for transfer tools.
+        out.extend_from_slice(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
+
+        let mut offsets = Vec::with_capacity(self.objects.len());
+        for (i, obj) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            out.extend_from_slice(obj);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref_pos = out.len();
+        out.extend_from_slice(format!("xref\n0 {}\n", self.objects.len() + 1).as_bytes());
+        out.extend_from_slice(b"0000000000 65535 f \n");
+        for offset in offsets {
+            out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+
+        out.extend_from_slice(format!(
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.objects.len() + 1, root, xref_pos
+        ).as_bytes());
+
+        out
+    }
+}
+
+fn stream_object(dict: &str, data: &[u8]) -> Vec<u8> {
+    let mut obj = String::from("<< ");
+    if !dict.is_empty() {
+        obj.push_str(dict);
+        obj.push(' ');
+    }
+    obj.push_str(&format!("/Length {} >>\nstream\n", data.len()));
+
+    let mut obj = obj.into_bytes();
+    obj.extend_from_slice(data);
+    obj.extend_from_slice(b"\nendstream");
+    obj
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = zlib::Encoder::new(Vec::new())?;
+    encoder.write_all(data)?;
+    let data = encoder.finish().into_result()?;
+    Ok(data)
+}
+
+
+/// Resources shared by the page and all form XObjects.
+///
+/// PDF allows to reference the same resources dictionary from multiple
+/// content streams, so we keep a single one.
+pub struct Resources {
+    pub ext_gstates: Vec<usize>,
+    pub patterns: Vec<usize>,
+    pub xobjects: Vec<usize>,
+}
+
+impl Resources {
+    pub fn new() -> Resources {
+        Resources {
+            ext_gstates: Vec::new(),
+            patterns: Vec::new(),
+            xobjects: Vec::new(),
+        }
+    }
+
+    /// Registers an `ExtGState` object and returns its resource name.
+    pub fn add_ext_gstate(&mut self, id: usize) -> String {
+        self.ext_gstates.push(id);
+        format!("GS{}", self.ext_gstates.len() - 1)
+    }
+
+    /// Registers a pattern object and returns its resource name.
+    pub fn add_pattern(&mut self, id: usize) -> String {
+        self.patterns.push(id);
+        format!("P{}", self.patterns.len() - 1)
+    }
+
+    /// Registers an image or a form XObject and returns its resource name.
+    pub fn add_xobject(&mut self, id: usize) -> String {
+        self.xobjects.push(id);
+        format!("X{}", self.xobjects.len() - 1)
+    }
+
+    pub fn to_dict(&self) -> String {
+        let mut s = String::from("<< /ProcSet [/PDF /ImageB /ImageC]");
+
+        let write_list = |s: &mut String, name: &str, prefix: &str, list: &[usize]| {
+            if list.is_empty() {
+                return;
+            }
+
+            write!(s, " /{} <<", name).unwrap();
+            for (i, id) in list.iter().enumerate() {
+                write!(s, " /{}{} {} 0 R", prefix, i, id).unwrap();
+            }
+            s.push_str(" >>");
+        };
+
+        write_list(&mut s, "ExtGState", "GS", &self.ext_gstates);
+        write_list(&mut s, "Pattern", "P", &self.patterns);
+        write_list(&mut s, "XObject", "X", &self.xobjects);
+
+        s.push_str(" >>");
+        s
+    }
+}
+
+
+/// Formats a number in a PDF compatible way.
+///
+/// PDF doesn't support an exponent notation.
+pub fn num(n: f64) -> String {
+    if !n.is_finite() {
+        return "0".to_string();
+    }
+
+    let mut s = format!("{:.6}", n);
+    while s.ends_with('0') {
+        s.pop();
+    }
+
+    if s.ends_with('.') {
+        s.pop();
+    }
+
+    if s == "-0" {
+        s = "0".to_string();
+    }
+
+    s
+}
+
+/// Formats a transform as a PDF matrix.
+pub fn matrix(ts: &Transform) -> String {
+    format!("{} {} {} {} {} {}",
+            num(ts.a), num(ts.b), num(ts.c), num(ts.d), num(ts.e), num(ts.f))
+}
@@ -5,39 +5,48 @@
 use qt;
 use dom;
 
+use super::ImageCache;
+
 
 pub fn draw(
     image: &dom::Image,
+    cache: &mut ImageCache,
     p: &qt::Painter,
 ) {
-    let img = match image.data {
-        dom::ImageData::Path(ref path) => {
-            match qt::Image::from_file(path) {
-                Some(v) => v,
-                None => {
-                    warn!("Failed to load an external image: {:?}.", path);
-                    return;
-                }
-            }
-        }
+    let img = match cache.get_or_load(&image.data, load) {
+        Some(v) => v,
+        None => return,
+    };
+
+    let r = image.rect;
+
+    // Let Qt scale the image, so it will be filtered according to the current transform.
+    let sx = r.w / img.width() as f64;
+    let sy = r.h / img.height() as f64;
+    p.apply_transform(&qt::Transform::new(sx, 0.0, 0.0, sy, r.x, r.y));
+
+    p.draw_image(0.0, 0.0, img);
+}
+
+fn load(data: &dom::ImageData) -> Option<qt::Image> {
+    let img = match *data {
         dom::ImageData::Raw(ref data, _) => {
             match qt::Image::from_data(data) {
                 Some(v) => v,
                 None => {
-                    warn!("Failed to load an embedded image.");
-                    return;
+                    diag!(Warning, InvalidImage, "", "Failed to load an embedded image.");
+                    return None;
                 }
             }
         }
+        // Rendered by the caller.
+        dom::ImageData::Svg(_) => return None,
     };
 
-    let img = match img.resize(image.rect.w as i32, image.rect.h as i32) {
-        Some(v) => v,
-        None => {
-            warn!("Failed to scale an image.");
-            return;
-        }
-    };
+    if img.width() == 0 || img.height() == 0 {
+        diag!(Warning, InvalidImage, "", "An image has a zero size.");
+        return None;
+    }
 
-    p.draw_image(image.rect.x, image.rect.y, &img);
+    Some(img)
 }
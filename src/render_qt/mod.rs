@@ -6,12 +6,17 @@
 
 use qt;
 
+use svgdom::types::{
+    Transform,
+};
+
 use dom;
 
 use {
     ErrorKind,
     Options,
     Result,
+    Tile,
 };
 
 use math::{
@@ -19,6 +24,8 @@ use math::{
     Rect,
 };
 
+use buffer;
+use progress::Progress;
 use render_utils;
 
 
@@ -35,13 +42,168 @@ use self::ext::{
 };
 
 
+type ImageCache = render_utils::ImageCache<qt::Image>;
+
+
 /// Renders SVG to image.
 pub fn render_to_image(doc: &dom::Document, opt: &Options) -> Result<qt::Image> {
     let _app = qt::GuiApp::new("resvg");
 
-    let img_size = render_utils::fit_to(&doc.size, opt.fit_to);
+    render(doc, &doc.elements, &doc.view_box, &doc.size, opt)
+}
+
+/// Renders a single element to image.
+///
+/// The image is cropped by the element bounding box.
+/// When `only` is set, all other elements are hidden,
+/// otherwise everything inside the bounding box is rendered.
+pub fn render_node_to_image(
+    doc: &dom::Document,
+    id: &str,
+    only: bool,
+    opt: &Options,
+) -> Result<qt::Image> {
+    let _app = qt::GuiApp::new("resvg");
+
+    let metrics = TextMetrics::new(doc.dpi);
+    let node = render_utils::prepare_node(doc, id, only, Some(&metrics))?;
+    render(doc, node.elements(doc), &node.view_box, &node.size, opt)
+}
+
+/// Renders an area of the document to image.
+///
+/// `area` is in the user coordinates, aka `viewBox`.
+/// The image size is the area size at the document scale, adjusted by `Options::fit_to`.
+pub fn render_area_to_image(
+    doc: &dom::Document,
+    area: Rect,
+    opt: &Options,
+) -> Result<qt::Image> {
+    let _app = qt::GuiApp::new("resvg");
+
+    let size = render_utils::prepare_area(doc, &area)?;
+    render(doc, &doc.elements, &area, &size, opt)
+}
+
+/// Renders a single tile to image.
+///
+/// The image has the tile size, so only the tile area is allocated.
+pub fn render_tile_to_image(
+    doc: &dom::Document,
+    tile: &Tile,
+    opt: &Options,
+) -> Result<qt::Image> {
+    let _app = qt::GuiApp::new("resvg");
+
+    let progress = Progress::new(opt, &doc.elements);
+    let img = render_view(doc, &doc.elements, &doc.view_box, tile.rect.size(), tile.img_view,
+                          &progress, opt)?;
+    progress.finish()?;
+
+    Ok(img)
+}
+
+/// Renders a tile into a premultiplied BGRA buffer band.
+///
+/// Qt can't render into a foreign memory, so the tile is rendered
+/// into a temporary image and then copied.
+///
+/// Requires an existing `qt::GuiApp`.
+pub(crate) fn render_band(
+    doc: &dom::Document,
+    opt: &Options,
+    tile: &Tile,
+    band: &mut [u8],
+    stride: usize,
+    progress: &Progress,
+) -> Result<()> {
+    let img = render_view(doc, &doc.elements, &doc.view_box, tile.rect.size(), tile.img_view,
+                          progress, opt)?;
+    copy_image(&img, tile, band, stride)
+}
+
+fn render(
+    doc: &dom::Document,
+    elements: &[dom::Element],
+    view_box: &Rect,
+    size: &Size,
+    opt: &Options,
+) -> Result<qt::Image> {
+    let img_size = render_utils::fit_to(size, opt.fit_to);
+    let progress = Progress::new(opt, elements);
+
+    let img = if opt.threads > 1 {
+        render_parallel(doc, elements, view_box, img_size, &progress, opt)?
+    } else {
+        let img_view = Rect::new(0.0, 0.0, img_size.w, img_size.h);
+        render_view(doc, elements, view_box, img_size, img_view, &progress, opt)?
+    };
+    progress.finish()?;
+
+    Ok(img)
+}
+
+// Bands are rendered into separate images and then copied into the main one,
+// because Qt objects can't be shared between threads.
+fn render_parallel(
+    doc: &dom::Document,
+    elements: &[dom::Element],
+    view_box: &Rect,
+    img_size: Size,
+    progress: &Progress,
+    opt: &Options,
+) -> Result<qt::Image> {
+    render_utils::check_canvas_size(img_size, opt)?;
+
+    let img = qt::Image::new(img_size.w as u32, img_size.h as u32);
+
+    let mut img = match img {
+        Some(v) => v,
+        None => {
+            return Err(ErrorKind::NoCanvas.into());
+        }
+    };
+    img.set_dpi(opt.dpi);
+
+    {
+        // The image has an ARGB32 premultiplied format, so rows are never padded.
+        let stride = img_size.w as usize * 4;
+        let mut data = img.data_mut();
+
+        let threads = opt.threads;
+        render_utils::render_bands(&mut data, img_size, stride, threads as u32, threads, progress,
+                                   |tile, band| {
+            let band_img = render_view(doc, elements, view_box, tile.rect.size(),
+                                       tile.img_view, progress, opt)?;
+            copy_image(&band_img, tile, band, stride)
+        })?;
+    }
+
+    Ok(img)
+}
+
+fn copy_image(img: &qt::Image, tile: &Tile, buffer: &mut [u8], stride: usize) -> Result<()> {
+    let width = tile.rect.w as u32;
+    let height = tile.rect.h as u32;
 
-    debug_assert!(img_size.w as i32 > 0 && img_size.h as i32 > 0);
+    // The image has an ARGB32 premultiplied format, so rows are never padded.
+    buffer::copy_rows(&img.data(), width as usize * 4, buffer, stride, width, height)
+}
+
+fn render_view(
+    doc: &dom::Document,
+    elements: &[dom::Element],
+    view_box: &Rect,
+    img_size: Size,
+    img_view: Rect,
+    progress: &Progress,
+    opt: &Options,
+) -> Result<qt::Image> {
+    if !(img_size.w as i32 > 0 && img_size.h as i32 > 0) {
+        return Err(ErrorKind::NoCanvas.into());
+    }
+
+    render_utils::check_canvas_size(img_size, opt)?;
 
     let img = qt::Image::new(img_size.w as u32, img_size.h as u32);
 
@@ -60,38 +222,84 @@ pub fn render_to_image(doc: &dom::Document, opt: &Options) -> Result<qt::Image>
     }
     img.set_dpi(opt.dpi);
 
-    let img_view = Rect::new(0.0, 0.0, img_size.w, img_size.h);
     let painter = qt::Painter::new(&img);
 
-    render_to_canvas(&painter, img_view, doc);
+    let ts = apply_view_box(&painter, view_box, &img_view);
+    render_group(doc, elements, &painter, &ts, img_size, &mut ImageCache::new(), progress);
 
     painter.end();
+    progress.check()?;
 
     Ok(img)
 }
 
+/// Qt based text metrics.
+///
+/// Can be used to calculate a text bounding box via `Document::calc_bbox`.
+///
+/// Requires an existing `qt::GuiApp`.
+pub struct TextMetrics {
+    dpi: f64,
+}
+
+impl TextMetrics {
+    /// Creates a new `TextMetrics` with the specified DPI.
+    ///
+    /// Should be the same as `Document::dpi`.
+    pub fn new(dpi: f64) -> Self {
+        TextMetrics {
+            dpi,
+        }
+    }
+}
+
+impl dom::TextMetrics for TextMetrics {
+    fn text_bbox(&self, text: &dom::Text) -> Option<Rect> {
+        let mut img = qt::Image::new(1, 1)?;
+        img.set_dpi(self.dpi);
+
+        let painter = qt::Painter::new(&img);
+        let bbox = text::calc_bbox(text, &painter);
+        painter.end();
+
+        bbox
+    }
+}
+
 /// Renders SVG to canvas.
 pub fn render_to_canvas(painter: &qt::Painter, img_view: Rect, doc: &dom::Document) {
-    // Apply viewBox.
+    let ts = apply_view_box(painter, &doc.view_box, &img_view);
+    let img_size = render_utils::canvas_size(&img_view);
+    render_group(doc, &doc.elements, &painter, &ts, img_size, &mut ImageCache::new(),
+                 &Progress::none());
+}
+
+fn apply_view_box(painter: &qt::Painter, view_box: &Rect, img_view: &Rect) -> Transform {
     let ts = {
-        let (dx, dy, sx, sy) = render_utils::view_box_transform(&doc.view_box, &img_view);
-        qt::Transform::new(sx, 0.0, 0.0, sy, dx, dy)
+        let (dx, dy, sx, sy) = render_utils::view_box_transform(view_box, img_view);
+        Transform::new(sx, 0.0, 0.0, sy, dx, dy)
     };
-    painter.set_transform(&ts);
+    painter.set_transform(&ts.to_qtransform());
 
-    render_group(doc, &doc.elements, &painter, &painter.get_transform(), img_view.size());
+    ts
 }
 
 // TODO: render groups backward to reduce memory usage
 //       current implementation keeps parent canvas until all children are rendered
-fn render_group(
+fn render_group<'a>(
     doc: &dom::Document,
-    elements: &[dom::Element],
+    elements: &'a [dom::Element],
     p: &qt::Painter,
-    ts: &qt::Transform,
+    ts: &Transform,
     img_size: Size,
+    cache: &mut ImageCache,
+    progress: &Progress,
 ) {
     for elem in elements {
+        if !progress.step() {
+            return;
+        }
+
         // Apply transform.
         p.apply_transform(&elem.transform.to_qtransform());
 
@@ -103,46 +311,112 @@ fn render_group(
                 text::draw(doc, text, p);
             }
             dom::Type::Image(ref img) => {
-                image::draw(img, p);
+                if let dom::ImageData::Svg(ref sub_doc) = img.data {
+                    let mut sub_ts = *ts;
+                    sub_ts.append(&elem.transform);
+                    render_svg_image(sub_doc, &img.rect, p, &sub_ts, img_size, cache, progress);
+                } else {
+                    image::draw(img, cache, p);
+                }
             }
             dom::Type::Group(ref g) => {
-                let sub_img = qt::Image::new(
-                    img_size.w as u32,
-                    img_size.h as u32
-                );
+                if render_utils::is_invisible(g) {
+                    // Nothing to render.
+                } else if render_utils::needs_layer(g) {
+                    render_layer(doc, elem, g, p, ts, img_size, cache, progress);
+                } else {
+                    let mut sub_ts = *ts;
+                    sub_ts.append(&elem.transform);
+                    render_group(doc, &g.children, p, &sub_ts, img_size, cache, progress);
+                }
+            }
+        }
 
-                let mut sub_img = match sub_img {
-                    Some(img) => img,
-                    None => {
-                        warn!("Subimage creation failed.");
-                        continue;
-                    }
-                };
+        // Revert transform.
+        p.set_transform(&ts.to_qtransform());
+    }
+}
 
-                sub_img.fill(0, 0, 0, 0);
-                sub_img.set_dpi(doc.dpi);
+fn render_svg_image<'a>(
+    sub_doc: &'a dom::Document,
+    rect: &Rect,
+    p: &qt::Painter,
+    ts: &Transform,
+    img_size: Size,
+    cache: &mut ImageCache,
+    progress: &Progress,
+) {
+    p.save();
 
-                let sub_p = qt::Painter::new(&sub_img);
-                sub_p.set_transform(&p.get_transform());
+    p.set_transform(&ts.to_qtransform());
+    p.set_clip_rect(rect.x, rect.y, rect.w, rect.h);
 
-                render_group(doc, &g.children, &sub_p, &p.get_transform(), img_size);
+    let mut sub_ts = *ts;
+    {
+        let (dx, dy, sx, sy) = render_utils::view_box_transform(&sub_doc.view_box, rect);
+        sub_ts.append(&Transform::new(sx, 0.0, 0.0, sy, dx, dy));
+    }
 
-                sub_p.end();
+    p.set_transform(&sub_ts.to_qtransform());
+    render_group(sub_doc, &sub_doc.elements, p, &sub_ts, img_size, cache, progress);
 
-                let curr_ts = p.get_transform();
-                p.set_transform(&qt::Transform::default());
-                if let Some(opacity) = g.opacity {
-                    p.set_opacity(opacity);
-                }
+    p.restore();
+}
+
+// The layer is allocated only for the group area on the canvas.
+#[allow(clippy::too_many_arguments)]
+fn render_layer<'a>(
+    doc: &dom::Document,
+    elem: &dom::Element,
+    g: &'a dom::Group,
+    p: &qt::Painter,
+    ts: &Transform,
+    img_size: Size,
+    cache: &mut ImageCache,
+    progress: &Progress,
+) {
+    let metrics = TextMetrics::new(doc.dpi);
+    let rect = match render_utils::layer_rect(elem, ts, img_size, &metrics) {
+        Some(rect) => rect,
+        None => return,
+    };
 
-                p.draw_image(0.0, 0.0, &sub_img);
+    let sub_img = qt::Image::new(
+        rect.w as u32,
+        rect.h as u32
+    );
 
-                p.set_opacity(1.0);
-                p.set_transform(&curr_ts);
-            }
+    let mut sub_img = match sub_img {
+        Some(img) => img,
+        None => {
+            diag!(Warning, LayerFailed, elem.id, "Subimage creation failed.");
+            return;
         }
+    };
 
-        // Revert transform.
-        p.set_transform(ts);
+    sub_img.fill(0, 0, 0, 0);
+    sub_img.set_dpi(doc.dpi);
+
+    // Move the layer area to the subimage origin.
+    let mut sub_ts = Transform::new(1.0, 0.0, 0.0, 1.0, -rect.x, -rect.y);
+    sub_ts.append(ts);
+    sub_ts.append(&elem.transform);
+
+    let sub_p = qt::Painter::new(&sub_img);
+    sub_p.set_transform(&sub_ts.to_qtransform());
+
+    render_group(doc, &g.children, &sub_p, &sub_ts, rect.size(), cache, progress);
+
+    sub_p.end();
+
+    let curr_ts = p.get_transform();
+    p.set_transform(&qt::Transform::default());
+    if let Some(opacity) = g.opacity {
+        p.set_opacity(opacity);
     }
+
+    p.draw_image(rect.x, rect.y, &sub_img);
+
+    p.set_opacity(1.0);
+    p.set_transform(&curr_ts);
 }
@@ -46,6 +46,45 @@ pub fn draw(
     }
 }
 
+pub fn calc_bbox(elem: &dom::Text, p: &qt::Painter) -> Option<Rect> {
+    let mut bbox: Option<Rect> = None;
+
+    for chunk in &elem.children {
+        let mut chunk_width = 0.0;
+
+        for tspan in &chunk.children {
+            let font = init_font(&tspan.font);
+            p.set_font(&font);
+            let font_metrics = p.font_metrics();
+            chunk_width += font_metrics.width(&tspan.text);
+        }
+
+        let mut x = process_text_anchor(chunk.x, chunk.anchor, chunk_width);
+
+        for tspan in &chunk.children {
+            let font = init_font(&tspan.font);
+            p.set_font(&font);
+            let font_metrics = p.font_metrics();
+
+            let r = Rect::new(
+                x,
+                chunk.y - font_metrics.ascent(),
+                font_metrics.width(&tspan.text),
+                font_metrics.height(),
+            );
+
+            bbox = Some(match bbox {
+                Some(prev) => prev.union(&r),
+                None => r,
+            });
+
+            x += r.w;
+        }
+    }
+
+    bbox
+}
+
 fn draw_tspan(
     doc: &dom::Document,
     tspan: &dom::TSpan,
@@ -2,6 +2,30 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+use std::cmp;
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+use std::collections::HashMap;
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend"))]
+use std::hash;
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend"))]
+use std::sync::Arc;
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+use std::thread;
+
+use svgdom::types::{
+    FuzzyEq,
+};
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+use svgdom::types::{
+    Transform,
+};
+
+#[cfg(any(feature = "cairo-backend", feature = "pdf-backend"))]
+use image;
+
+use dom;
+
 use math::{
     Size,
     Rect,
@@ -10,7 +34,359 @@ use math::{
 use {
     FitTo,
 };
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+use {
+    ErrorKind,
+    Result,
+};
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+use {
+    Options,
+    Tile,
+    Tiles,
+};
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+use progress::Progress;
+
+
+/// A single element rendering parameters.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+pub struct NodeView {
+    /// Element bounding box in the user coordinates.
+    ///
+    /// Should be used instead of the document viewbox.
+    pub view_box: Rect,
+    /// Image size.
+    pub size: Size,
+    /// The element with its parents only.
+    ///
+    /// `None` when all elements should be rendered.
+    pub elements: Option<Vec<dom::Element>>,
+}
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+impl NodeView {
+    /// Returns a list of elements that should be rendered.
+    pub fn elements<'a>(&'a self, doc: &'a dom::Document) -> &'a [dom::Element] {
+        match self.elements {
+            Some(ref elements) => elements,
+            None => &doc.elements,
+        }
+    }
+}
+
+/// Prepares a single element rendering.
+///
+/// When `only` is set, all other elements will be hidden,
+/// otherwise everything inside the element bounding box will be rendered.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+pub fn prepare_node(
+    doc: &dom::Document,
+    id: &str,
+    only: bool,
+    metrics: Option<&dyn dom::TextMetrics>,
+) -> Result<NodeView> {
+    let elem = match doc.element_by_id(id) {
+        Some(elem) => elem,
+        None => return Err(ErrorKind::ElementNotFound(id.to_string()).into()),
+    };
+
+    let bbox = match doc.calc_element_bbox(elem, true, metrics) {
+        Some(bbox) => bbox,
+        None => return Err(ErrorKind::EmptyElement(id.to_string()).into()),
+    };
+
+    let size = match area_size(doc, &bbox) {
+        Some(size) => size,
+        None => return Err(ErrorKind::EmptyElement(id.to_string()).into()),
+    };
+
+    let elements = if only {
+        Some(vec![node_subtree(doc, elem)])
+    } else {
+        None
+    };
+
+    Ok(NodeView {
+        view_box: bbox,
+        size,
+        elements,
+    })
+}
+
+/// Prepares an area rendering.
+///
+/// Returns an image size for the area in the user coordinates.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+pub fn prepare_area(doc: &dom::Document, area: &Rect) -> Result<Size> {
+    match area_size(doc, area) {
+        Some(size) => Ok(size),
+        None => Err(ErrorKind::EmptyArea.into()),
+    }
+}
+
+/// Returns an image size for the area in the user coordinates.
+///
+/// The document scale is preserved.
+///
+/// Returns `None` when the image is empty.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+pub fn area_size(doc: &dom::Document, area: &Rect) -> Option<Size> {
+    let (_, _, sx, sy) = view_box_transform(&doc.view_box, &Rect::new(0.0, 0.0, doc.size.w, doc.size.h));
+    let w = (area.w * sx).ceil();
+    let h = (area.h * sy).ceil();
+
+    if w > 0.0 && h > 0.0 {
+        Some(Size::new(w, h))
+    } else {
+        None
+    }
+}
+
+/// Wraps an element copy into copies of its parents.
+///
+/// Parents transform and opacity are preserved, but all siblings are removed.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend", test))]
+fn node_subtree(doc: &dom::Document, elem: &dom::Element) -> dom::Element {
+    let mut node = elem.clone();
+
+    for parent in doc.parents(elem) {
+        let opacity = match parent.data {
+            dom::Type::Group(ref g) => g.opacity,
+            _ => None,
+        };
+
+        node = dom::Element {
+            id: parent.id.clone(),
+            data: dom::Type::Group(dom::Group {
+                opacity,
+                children: vec![node],
+            }),
+            transform: parent.transform,
+        };
+    }
+
+    node
+}
+
+
+/// An image cache key.
+///
+/// Images are compared by the data identity, because copies of the same image
+/// share the data. The key holds the data, so its address can't be reused
+/// by another image while the key is alive.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend"))]
+pub struct ImageKey(Arc<Vec<u8>>);
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend"))]
+impl ImageKey {
+    /// Creates a key for a raster image.
+    ///
+    /// Returns `None` for SVG images, because they are rendered as vectors.
+    pub fn new(data: &dom::ImageData) -> Option<Self> {
+        match *data {
+            dom::ImageData::Raw(ref data, _) => Some(ImageKey(data.clone())),
+            dom::ImageData::Svg(_) => None,
+        }
+    }
+}
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend"))]
+impl PartialEq for ImageKey {
+    fn eq(&self, other: &ImageKey) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend"))]
+impl Eq for ImageKey {}
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend"))]
+impl hash::Hash for ImageKey {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// A decoded images cache.
+///
+/// The same image can be referenced multiple times, so it should be decoded only once.
+/// Failed images are cached too, so they will be reported only once.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+pub struct ImageCache<T> {
+    images: HashMap<ImageKey, Option<T>>,
+}
+
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+impl<T> ImageCache<T> {
+    /// Creates a new cache.
+    pub fn new() -> Self {
+        ImageCache {
+            images: HashMap::new(),
+        }
+    }
+
+    /// Returns a cached image or loads it using `load`.
+    ///
+    /// SVG images are rendered as vectors, so they are never cached.
+    pub fn get_or_load<F>(&mut self, data: &dom::ImageData, load: F) -> Option<&T>
+        where F: FnOnce(&dom::ImageData) -> Option<T>
+    {
+        let key = ImageKey::new(data)?;
+
+        self.images.entry(key).or_insert_with(|| load(data)).as_ref()
+    }
+}
+
+/// Decodes a raster image.
+///
+/// The format is detected from the content, so linked files with a wrong extension
+/// are supported too. Only the first frame of an animated image is decoded.
+#[cfg(any(feature = "cairo-backend", feature = "pdf-backend"))]
+pub fn load_raster_image(data: &dom::ImageData) -> Option<image::DynamicImage> {
+    match *data {
+        dom::ImageData::Raw(ref data, _) => {
+            let img = image::load_from_memory(data).ok();
+            if img.is_none() {
+                diag!(Warning, InvalidImage, "", "Failed to load an embedded image.");
+            }
+
+            img
+        }
+        dom::ImageData::Svg(_) => None,
+    }
+}
+
+/// Checks that the group should be rendered on a separate layer.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+pub fn needs_layer(g: &dom::Group) -> bool {
+    match g.opacity {
+        Some(opacity) => opacity.fuzzy_ne(&1.0),
+        None => false,
+    }
+}
+
+/// Checks that the group is fully transparent, so it can be skipped.
+pub fn is_invisible(g: &dom::Group) -> bool {
+    match g.opacity {
+        Some(opacity) => opacity.fuzzy_eq(&0.0),
+        None => false,
+    }
+}
+
+/// Renders an image in horizontal bands.
+///
+/// `buffer` is split into `bands` bands and `render` should fill
+/// the passed band using the tile `img_view`. Bands are distributed
+/// between `threads` threads, so each thread renders a contiguous range of bands.
+/// A single thread renders on the current one.
+///
+/// `progress` is shared between bands, so it's adjusted to the number of bands.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+pub fn render_bands<F>(
+    buffer: &mut [u8],
+    img_size: Size,
+    stride: usize,
+    bands: u32,
+    threads: usize,
+    progress: &Progress,
+    render: F,
+) -> Result<()>
+    where F: Fn(&Tile, &mut [u8]) -> Result<()> + Sync
+{
+    let tiles: Vec<Tile> = Tiles::from_size(img_size, 1, bands).collect();
+    progress.set_passes(tiles.len());
+
+    let mut parts = Vec::with_capacity(tiles.len());
+    let mut rest = buffer;
+    for tile in &tiles {
+        // The last row may not have a padding.
+        let len = cmp::min(tile.rect.h as usize * stride, rest.len());
+        let (band, tail) = { rest }.split_at_mut(len);
+        parts.push((tile, band));
+        rest = tail;
+    }
+
+    let threads = cmp::min(cmp::max(threads, 1), parts.len());
+    if threads == 1 {
+        return parts.into_iter().try_for_each(|(tile, band)| render(tile, band));
+    }
+
+    let per_thread = parts.len().div_ceil(threads);
+    let mut groups = Vec::with_capacity(threads);
+    let mut parts = parts.into_iter();
+    loop {
+        let group: Vec<_> = parts.by_ref().take(per_thread).collect();
+        if group.is_empty() {
+            break;
+        }
+
+        groups.push(group);
+    }
+
+    let render = &render;
+    thread::scope(|s| {
+        let handles: Vec<_> = groups.into_iter()
+            .map(|group| s.spawn(move || {
+                group.into_iter().try_for_each(|(tile, band)| render(tile, band))
+            }))
+            .collect();
 
+        // A panic in a rendering thread is a bug, so we can propagate it.
+        handles.into_iter().try_for_each(|h| h.join().unwrap())
+    })
+}
+
+/// Checks that a canvas with the specified size doesn't exceed `Limits::max_canvas_pixels`.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend", test))]
+pub fn check_canvas_size(img_size: Size, opt: &Options) -> Result<()> {
+    let limit = opt.limits.max_canvas_pixels;
+    if img_size.w as u64 * img_size.h as u64 > limit {
+        return Err(ErrorKind::CanvasTooLarge(limit).into());
+    }
+
+    Ok(())
+}
+
+/// Returns a canvas area that can be affected by rendering into `img_view`.
+///
+/// Used when the actual canvas size is unknown.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+pub fn canvas_size(img_view: &Rect) -> Size {
+    Size::new((img_view.x + img_view.w).max(0.0), (img_view.y + img_view.h).max(0.0))
+}
+
+/// Calculates a group layer rect in the canvas coordinates.
+///
+/// `ts` is the current canvas transform, without the group transform.
+///
+/// The rect has an integer position and size and is clipped by the canvas.
+/// Returns `None` when the group is outside the canvas.
+#[cfg(any(feature = "cairo-backend", feature = "qt-backend"))]
+pub fn layer_rect(
+    elem: &dom::Element,
+    ts: &Transform,
+    canvas_size: Size,
+    metrics: &dyn dom::TextMetrics,
+) -> Option<Rect> {
+    let bbox = elem.calc_transformed_bbox(ts, true, Some(metrics))?;
+
+    // Antialiasing can affect neighbor pixels, and the text metrics are not exact.
+    let bbox = bbox.expand(1.0);
+
+    let x1 = bbox.x.floor().max(0.0);
+    let y1 = bbox.y.floor().max(0.0);
+    let x2 = (bbox.x + bbox.w).ceil().min(canvas_size.w.ceil());
+    let y2 = (bbox.y + bbox.h).ceil().min(canvas_size.h.ceil());
+
+    if x2 > x1 && y2 > y1 {
+        Some(Rect::new(x1, y1, x2 - x1, y2 - y1))
+    } else {
+        None
+    }
+}
 
 pub fn fit_to(size: &Size, fit: FitTo) -> Size {
     match fit {
@@ -43,3 +419,177 @@ pub fn view_box_transform(view_box: &Rect, img_view: &Rect) -> (f64, f64, f64, f
 
     (dx, dy, s, s)
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils;
+
+    // The document scale is 2.
+    fn parse(content: &str) -> dom::Document {
+        test_utils::parse("width='200' height='200' viewBox='0 0 100 100'", content)
+    }
+
+    const NODES: &str = "\
+        <g id='g' opacity='0.5' transform='translate(10 20)'>\
+            <rect id='r1' width='10' height='5' stroke='black' stroke-width='2' \
+                  stroke-linejoin='round'/>\
+            <rect id='r2' x='50' width='10' height='10'/>\
+        </g>\
+        <rect id='r3' width='100' height='100'/>";
+
+    #[test]
+    fn node_view() {
+        let doc = parse(NODES);
+
+        let view = prepare_node(&doc, "r1", false, None).unwrap();
+        // The stroke is included and the parent transform is applied.
+        assert_eq!(view.view_box, Rect::new(9.0, 19.0, 12.0, 7.0));
+        // The document scale is preserved.
+        assert_eq!(view.size, Size::new(24.0, 14.0));
+        assert!(view.elements.is_none());
+        assert_eq!(view.elements(&doc).len(), 2);
+    }
+
+    #[test]
+    fn node_view_only() {
+        let doc = parse(NODES);
+
+        let view = prepare_node(&doc, "r1", true, None).unwrap();
+        let elements = view.elements(&doc);
+        assert_eq!(elements.len(), 1);
+
+        // Parents are preserved, but siblings are removed.
+        let g = &elements[0];
+        assert_eq!(g.id, "g");
+        assert_eq!(g.transform, doc.element_by_id("g").unwrap().transform);
+        match g.data {
+            dom::Type::Group(ref g) => {
+                assert_eq!(g.opacity, Some(0.5));
+                assert_eq!(g.children.len(), 1);
+                assert_eq!(g.children[0].id, "r1");
+            }
+            _ => panic!("a group expected"),
+        }
+    }
+
+    #[test]
+    fn node_view_errors() {
+        let doc = parse("<rect id='r' width='10' height='10'/>");
+
+        match prepare_node(&doc, "missing", false, None) {
+            Err(::Error(ErrorKind::ElementNotFound(ref id), _)) => assert_eq!(id, "missing"),
+            _ => panic!("ElementNotFound expected"),
+        }
+
+        assert!(prepare_node(&doc, "r", false, None).is_ok());
+    }
+
+    #[test]
+    fn area() {
+        let doc = parse("");
+
+        assert_eq!(area_size(&doc, &Rect::new(10.0, 10.0, 20.0, 30.0)), Some(Size::new(40.0, 60.0)));
+        // The size is rounded up.
+        assert_eq!(area_size(&doc, &Rect::new(0.0, 0.0, 0.2, 10.25)), Some(Size::new(1.0, 21.0)));
+
+        assert_eq!(area_size(&doc, &Rect::new(0.0, 0.0, 0.0, 10.0)), None);
+
+        match prepare_area(&doc, &Rect::new(0.0, 0.0, 0.0, 0.0)) {
+            Err(::Error(ErrorKind::EmptyArea, _)) => {}
+            _ => panic!("EmptyArea expected"),
+        }
+    }
+
+    // Fills each row with its index in the image.
+    fn render_rows(img_size: Size, stride: usize, bands: u32, threads: usize) -> Vec<u8> {
+        let opt = Options::default();
+        let progress = Progress::new(&opt, &[]);
+        let mut buffer = vec![0; img_size.h as usize * stride];
+        render_bands(&mut buffer, img_size, stride, bands, threads, &progress, |tile, band| {
+            assert_eq!(band.len(), tile.rect.h as usize * stride);
+            assert_eq!(tile.img_view.y, -tile.rect.y);
+            for (i, row) in band.chunks_mut(stride).enumerate() {
+                for p in row.iter_mut() {
+                    *p = tile.rect.y as u8 + i as u8;
+                }
+            }
+
+            Ok(())
+        }).unwrap();
+
+        buffer
+    }
+
+    #[test]
+    fn bands() {
+        let img_size = Size::new(3.0, 10.0);
+        let stride = 12;
+
+        let expected: Vec<u8> = (0..10).flat_map(|y| vec![y; stride]).collect();
+        assert_eq!(render_rows(img_size, stride, 1, 1), expected);
+        assert_eq!(render_rows(img_size, stride, 4, 1), expected);
+        // Threaded rendering produces the same output.
+        assert_eq!(render_rows(img_size, stride, 4, 3), expected);
+        assert_eq!(render_rows(img_size, stride, 10, 16), expected);
+        // Bands are clamped to the image height.
+        assert_eq!(render_rows(img_size, stride, 100, 4), expected);
+    }
+
+    #[test]
+    fn bands_error() {
+        let opt = Options::default();
+        let progress = Progress::new(&opt, &[]);
+        let mut buffer = vec![0; 40];
+        let res = render_bands(&mut buffer, Size::new(1.0, 10.0), 4, 5, 2, &progress, |tile, _| {
+            if tile.row == 3 {
+                return Err(ErrorKind::EmptyArea.into());
+            }
+
+            Ok(())
+        });
+
+        match res {
+            Err(::Error(ErrorKind::EmptyArea, _)) => {}
+            _ => panic!("an error expected"),
+        }
+    }
+
+    #[test]
+    fn canvas_limit() {
+        let opt = Options {
+            limits: ::Limits { max_canvas_pixels: 100, .. ::Limits::default() },
+            .. Options::default()
+        };
+
+        assert!(check_canvas_size(Size::new(10.0, 10.0), &opt).is_ok());
+        // Sizes are truncated, like in backends.
+        assert!(check_canvas_size(Size::new(10.9, 10.9), &opt).is_ok());
+
+        match check_canvas_size(Size::new(10.0, 11.0), &opt) {
+            Err(::Error(ErrorKind::CanvasTooLarge(100), _)) => {}
+            _ => panic!("CanvasTooLarge expected"),
+        }
+    }
+
+    #[cfg(any(feature = "cairo-backend", feature = "qt-backend", feature = "pdf-backend"))]
+    #[test]
+    fn image_key() {
+        let data = Arc::new(vec![1, 2, 3]);
+        let img = dom::ImageData::Raw(data.clone(), dom::ImageDataKind::PNG);
+        let copy = img.clone();
+        let other = dom::ImageData::Raw(Arc::new(vec![1, 2, 3]), dom::ImageDataKind::PNG);
+
+        let key = ImageKey::new(&img).unwrap();
+        assert!(key == ImageKey::new(&copy).unwrap());
+        // The same content is not enough.
+        assert!(key != ImageKey::new(&other).unwrap());
+
+        // The key keeps the data alive.
+        drop(img);
+        drop(copy);
+        drop(data);
+        assert_eq!(Arc::strong_count(&key.0), 1);
+    }
+}
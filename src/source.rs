@@ -0,0 +1,457 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Restores the source data that `svgdom` drops during parsing.
+//!
+//! `svgdom` removes `style` elements, resolves only simple selectors (and not always
+//! correctly) and merges `style` attributes into presentation attributes,
+//! so the cascade can't be evaluated on the parsed tree.
+//! It also processes whitespace per text node, so spaces can't be collapsed
+//! across `tspan` boundaries.
+//!
+//! The source is tokenized the same way as `svgdom` does it, so elements
+//! can be matched with the tree nodes in the document order. Nothing is rewritten:
+//! `style` elements, `class`, `style` and non-SVG attributes and the text content
+//! are copied into the tree as is and resolved by the `preproc` passes.
+//!
+//! The tree is modified only after the whole source was matched.
+
+use std::collections::HashMap;
+
+use svgdom::{
+    Attribute,
+    AttributeType,
+    Document,
+    Node,
+    NodeType,
+    ValueId,
+};
+use svgdom::types::{
+    Color,
+    Length,
+};
+use svgparser::svg::{
+    ElementEnd,
+    Name,
+    Token,
+    Tokenizer,
+};
+use svgparser::{
+    self,
+    FromSpan,
+    PaintFallback,
+    TextUnescape,
+    XmlSpace,
+};
+
+use css::StyleSheet;
+use short::{
+    AId,
+    AValue,
+    EId,
+    Unit,
+};
+
+
+/// Elements with an `id`, used to resolve links.
+pub type Ids = HashMap<String, Node>;
+
+/// A deferred tree modification.
+enum Edit {
+    SetAttribute(Node, Attribute),
+    /// Removes text nodes processed by `svgdom`.
+    RemoveText(Node),
+    InsertAfter(Node, Node),
+    Prepend(Node, Node),
+}
+
+struct OpenElement {
+    /// A tree node. `None` when the element was removed by `svgdom`.
+    node: Option<Node>,
+    /// The last child that is present in the tree.
+    last_child: Option<Node>,
+    /// A restored `style` element.
+    is_style: bool,
+    /// A `text` element or its descendant.
+    in_text: bool,
+}
+
+struct CurrElement<'a> {
+    node: Option<Node>,
+    /// `Some` for a `style` element, which parent is present in the tree.
+    /// Contains `true` when the element has a CSS content.
+    style: Option<bool>,
+    /// Presentation attributes.
+    attributes: Vec<(AId, &'a str)>,
+}
+
+/// Restores `style` elements, `class`, `style` and non-SVG attributes.
+///
+/// Text nodes inside `text` elements are replaced with unprocessed ones.
+/// Only entities are resolved and newlines and tabs are converted into spaces.
+///
+/// Presentation attributes that were overridden by `svgdom` using style sheets
+/// are restored too.
+///
+/// Does nothing when the text can't be matched with the document.
+pub fn restore(text: &str, doc: &mut Document) {
+    // A quick check to skip tokenizing.
+    if !text.contains("style") && !text.contains("class") && !text.contains("text") {
+        return;
+    }
+
+    // Restored nodes are inserted during tokenizing, so the list must be collected beforehand.
+    let nodes: Vec<Node> = doc.descendants().filter(|n| n.node_type() == NodeType::Element).collect();
+    let mut nodes = nodes.into_iter();
+
+    let mut stack: Vec<OpenElement> = Vec::new();
+    let mut curr: Option<CurrElement> = None;
+    let mut elements = Vec::new();
+    let mut sheets = Vec::new();
+    let mut edits = Vec::new();
+
+    for token in Tokenizer::from_str(text) {
+        let token = match token {
+            Ok(token) => token,
+            Err(_) => return,
+        };
+
+        match token {
+            Token::ElementStart(name) => {
+                let parent_kept = stack.last().map(|e| e.node.is_some() && !e.is_style).unwrap_or(true);
+
+                let mut elem = CurrElement { node: None, style: None, attributes: Vec::new() };
+                match name {
+                    Name::Svg(EId::Style) if parent_kept && !stack.is_empty() => {
+                        elem.style = Some(true);
+                    }
+                    // `style` elements and unknown elements are removed with all children.
+                    Name::Svg(EId::Style) | Name::Xml(_) => {}
+                    Name::Svg(eid) if parent_kept => {
+                        let node = match nodes.next() {
+                            Some(node) => node,
+                            None => return,
+                        };
+
+                        if node.tag_id() != Some(eid) {
+                            return;
+                        }
+
+                        elem.node = Some(node);
+                    }
+                    Name::Svg(_) => {}
+                }
+
+                curr = Some(elem);
+            }
+            Token::Attribute(name, value) => {
+                let elem = match curr {
+                    Some(ref mut elem) => elem,
+                    None => return,
+                };
+
+                match name {
+                    Name::Svg(aid @ AId::Class) | Name::Svg(aid @ AId::Style) => {
+                        if let Some(ref node) = elem.node {
+                            let attr = Attribute::new(aid, value.to_str());
+                            edits.push(Edit::SetAttribute(node.clone(), attr));
+                        }
+                    }
+                    Name::Svg(AId::Type) => {
+                        if let Some(ref mut is_css) = elem.style {
+                            let ty = value.to_str().trim();
+                            *is_css = ty == "text/css" || ty.is_empty();
+                        }
+                    }
+                    Name::Svg(aid) if aid.is_presentation() => {
+                        elem.attributes.push((aid, value.to_str()));
+                    }
+                    Name::Svg(_) => {}
+                    // Non-SVG attributes can be used by attribute selectors.
+                    Name::Xml(name) => {
+                        if let Some(ref node) = elem.node {
+                            let attr = Attribute::new(name, value.to_str());
+                            edits.push(Edit::SetAttribute(node.clone(), attr));
+                        }
+                    }
+                }
+            }
+            Token::ElementEnd(ElementEnd::Close(_)) => {
+                stack.pop();
+            }
+            Token::ElementEnd(end) => {
+                let elem = match curr.take() {
+                    Some(elem) => elem,
+                    None => return,
+                };
+
+                let mut node = elem.node;
+                let is_style = elem.style == Some(true);
+                let mut in_text = false;
+                if is_style {
+                    // A detached node, so the tree is not modified yet.
+                    let style = doc.create_element(EId::Style);
+                    if let Some(parent) = stack.last_mut() {
+                        insert_child(parent, &style, &mut edits);
+                    }
+                    node = Some(style);
+                } else if let Some(ref node) = node {
+                    if let Some(parent) = stack.last_mut() {
+                        parent.last_child = Some(node.clone());
+                    }
+
+                    elements.push((node.clone(), elem.attributes));
+
+                    let parent_in_text = stack.last().map(|e| e.in_text).unwrap_or(false);
+                    in_text = parent_in_text || node.is_tag_name(EId::Text);
+                }
+
+                if end == ElementEnd::Open {
+                    if in_text {
+                        // Text nodes processed by `svgdom` will be replaced.
+                        if let Some(ref node) = node {
+                            edits.push(Edit::RemoveText(node.clone()));
+                        }
+                    }
+
+                    stack.push(OpenElement { node, last_child: None, is_style, in_text });
+                }
+            }
+            Token::Text(span) => {
+                // Text outside of CDATA sections can contain entities, like `&gt;`.
+                let s = TextUnescape::unescape(span.to_str(), XmlSpace::Preserve);
+                append_text(doc, &mut stack, &s, &mut sheets, &mut edits);
+            }
+            Token::Whitespaces(s) => {
+                // Like in `svgdom`, whitespaces are kept only inside these elements.
+                let is_text_elem = match stack.last().and_then(|e| e.node.as_ref()) {
+                    Some(node) => {
+                        node.is_tag_name(EId::Text) || node.is_tag_name(EId::Tspan)
+                        || node.is_tag_name(EId::Tref)
+                    }
+                    None => false,
+                };
+
+                if is_text_elem {
+                    let s = TextUnescape::unescape(s, XmlSpace::Preserve);
+                    append_text(doc, &mut stack, &s, &mut sheets, &mut edits);
+                }
+            }
+            // CDATA sections outside `style` are kept by `svgdom`.
+            Token::Cdata(span) if stack.last().map(|e| e.is_style).unwrap_or(false) => {
+                append_text(doc, &mut stack, span.to_str(), &mut sheets, &mut edits);
+            }
+            _ => {}
+        }
+    }
+
+    apply_edits(edits);
+
+    if !sheets.is_empty() {
+        restore_presentation_attributes(doc, &sheets, elements);
+    }
+}
+
+fn apply_edits(edits: Vec<Edit>) {
+    for edit in edits {
+        match edit {
+            Edit::SetAttribute(mut node, attr) => node.set_attribute(attr),
+            Edit::RemoveText(node) => {
+                let texts: Vec<Node> = node.children()
+                    .filter(|n| n.node_type() == NodeType::Text).collect();
+                for mut text_node in texts {
+                    text_node.remove();
+                }
+            }
+            Edit::InsertAfter(mut prev, node) => prev.insert_after(&node),
+            Edit::Prepend(mut parent, node) => parent.prepend(&node),
+        }
+    }
+}
+
+/// Appends a text node to a `style` element or to a text element.
+fn append_text(
+    doc: &mut Document,
+    stack: &mut [OpenElement],
+    text: &str,
+    sheets: &mut Vec<String>,
+    edits: &mut Vec<Edit>,
+) {
+    if let Some(parent) = stack.last_mut() {
+        if parent.is_style || parent.in_text {
+            let node = doc.create_node(NodeType::Text, text);
+            insert_child(parent, &node, edits);
+        }
+
+        if parent.is_style {
+            sheets.push(text.to_string());
+        }
+    }
+}
+
+fn insert_child(parent: &mut OpenElement, node: &Node, edits: &mut Vec<Edit>) {
+    match parent.last_child {
+        Some(ref prev) => edits.push(Edit::InsertAfter(prev.clone(), node.clone())),
+        None => {
+            if let Some(ref parent_node) = parent.node {
+                edits.push(Edit::Prepend(parent_node.clone(), node.clone()));
+            }
+        }
+    }
+
+    parent.last_child = Some(node.clone());
+}
+
+/// Resets attributes that can be set by style sheets to the source values.
+fn restore_presentation_attributes(doc: &Document, sheets: &[String], elements: Vec<(Node, Vec<(AId, &str)>)>) {
+    let mut ids = Vec::new();
+    for text in sheets {
+        // Errors are reported by `preproc::resolve_css`.
+        let sheet = StyleSheet::parse(text, |_| {});
+        for decl in sheet.blocks.iter().flat_map(|b| b.iter()) {
+            if let Some(aid) = AId::from_name(&decl.name.to_ascii_lowercase()) {
+                if aid.is_presentation() && !ids.contains(&aid) {
+                    ids.push(aid);
+                }
+            }
+        }
+    }
+
+    if ids.is_empty() {
+        return;
+    }
+
+    let links = collect_ids(doc);
+    for (mut node, attributes) in elements {
+        for aid in &ids {
+            match attributes.iter().rev().find(|a| a.0 == *aid) {
+                Some(&(_, value)) => set_attribute_str(&mut node, *aid, value, &links),
+                None => node.remove_attribute(*aid),
+            }
+        }
+    }
+}
+
+/// Collects elements with an `id`.
+pub fn collect_ids(doc: &Document) -> Ids {
+    let mut ids = HashMap::new();
+    for node in doc.descendants().filter(|n| n.has_id()) {
+        ids.insert(node.id().clone(), node.clone());
+    }
+
+    ids
+}
+
+/// A parsed attribute value.
+pub enum Value<'a> {
+    Plain(AValue),
+    /// A link with an optional fallback value.
+    Link(&'a str, Option<AValue>),
+}
+
+/// Parses an attribute value the same way as `svgdom` does it.
+///
+/// Returns `None` for invalid values.
+pub fn parse_value<'a>(eid: EId, aid: AId, text: &'a str) -> Option<Value<'a>> {
+    use svgparser::AttributeValue as ParserValue;
+
+    let value = match svgparser::AttributeValue::from_str(eid, aid, text).ok()? {
+        ParserValue::String(v) => AValue::String(v.to_string()),
+        ParserValue::Number(v) => AValue::Number(v),
+        ParserValue::NumberList(list) => {
+            let list = list.collect::<Result<Vec<_>, _>>().ok()?;
+            if list.is_empty() {
+                return None;
+            }
+
+            AValue::NumberList(list)
+        }
+        ParserValue::Length(v) => AValue::Length(Length::new(v.num, prepare_length_unit(v.unit))),
+        ParserValue::LengthList(list) => {
+            let list = list.collect::<Result<Vec<_>, _>>().ok()?;
+            if list.is_empty() {
+                return None;
+            }
+
+            let list = list.iter().map(|v| Length::new(v.num, prepare_length_unit(v.unit)));
+            AValue::LengthList(list.collect())
+        }
+        ParserValue::Color(c) => AValue::Color(Color::new(c.red, c.green, c.blue)),
+        ParserValue::PredefValue(v) => AValue::PredefValue(v),
+        ParserValue::IRI(link) | ParserValue::FuncIRI(link) => {
+            return Some(Value::Link(link, None));
+        }
+        ParserValue::FuncIRIWithFallback(link, fallback) => {
+            let fallback = match fallback {
+                PaintFallback::PredefValue(v) => AValue::PredefValue(v),
+                PaintFallback::Color(c) => AValue::Color(Color::new(c.red, c.green, c.blue)),
+            };
+
+            return Some(Value::Link(link, Some(fallback)));
+        }
+        // Entity references are resolved only by `svgdom`.
+        ParserValue::EntityRef(_) => return None,
+    };
+
+    Some(Value::Plain(value))
+}
+
+/// Parses an attribute value and sets it.
+///
+/// Invalid values are skipped.
+pub fn set_attribute_str(node: &mut Node, aid: AId, text: &str, ids: &Ids) {
+    let value = match node.tag_id().and_then(|eid| parse_value(eid, aid, text)) {
+        Some(value) => value,
+        None => {
+            diag!(Warning, InvalidAttribute, node.id(),
+                  "Attribute '{}' has an invalid value: '{}'. Skipped.", aid, text);
+            return;
+        }
+    };
+
+    match value {
+        Value::Plain(value) => node.set_attribute((aid, value)),
+        Value::Link(link, fallback) => {
+            match (ids.get(link), fallback) {
+                // Like in `svgdom`, the fallback is ignored when the link is valid.
+                (Some(link), _) => set_link(node, aid, link),
+                (None, Some(fallback)) => node.set_attribute((aid, fallback)),
+                (None, None) => resolve_fallback(node, aid, link),
+            }
+        }
+    }
+}
+
+fn set_link(node: &mut Node, aid: AId, link: &Node) {
+    if node.set_attribute_checked((aid, link.clone())).is_err() {
+        diag!(Warning, InvalidAttribute, node.id(),
+              "Attribute '{}' has an invalid link to '{}'. Skipped.", aid, *link.id());
+    }
+}
+
+/// Resolves a link to a non-existing element.
+fn resolve_fallback(node: &mut Node, aid: AId, link: &str) {
+    match aid {
+        AId::Fill => {
+            diag!(Info, InvalidAttribute, node.id(),
+                  "Could not resolve the 'fill' IRI reference: {}. Fallback to 'none'.", link);
+            node.set_attribute((AId::Fill, ValueId::None));
+        }
+        AId::Filter => {
+            // An element with an invalid filter should not be rendered.
+            diag!(Warning, InvalidAttribute, node.id(),
+                  "Unresolved 'filter' IRI reference: {}. Marking the element as invisible.", link);
+            node.set_attribute((AId::Visibility, ValueId::Hidden));
+        }
+        _ => {
+            diag!(Warning, InvalidAttribute, node.id(),
+                  "Could not resolve IRI reference: {}.", link);
+        }
+    }
+}
+
+/// `px` is the same as no units, because `parse_px_unit` is disabled.
+fn prepare_length_unit(unit: Unit) -> Unit {
+    if unit == Unit::Px { Unit::None } else { unit }
+}
@@ -0,0 +1,34 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Helpers shared by unit tests.
+//!
+//! Note that the preprocessor ungroups groups without opacity and groups
+//! with a single child, so fixtures that need nested groups set `opacity`
+//! and add at least two children.
+
+use dom;
+
+use {
+    parse_doc_from_data,
+    Options,
+    Result,
+};
+
+
+/// Creates an SVG document with the specified root attributes and content.
+pub fn svg(attrs: &str, content: &str) -> String {
+    format!("<svg xmlns='http://www.w3.org/2000/svg' xmlns:xlink='http://www.w3.org/1999/xlink' \
+             {}>{}</svg>", attrs, content)
+}
+
+/// Parses an SVG document created by `svg`.
+pub fn parse(attrs: &str, content: &str) -> dom::Document {
+    parse_with(attrs, content, &Options::default()).unwrap()
+}
+
+/// Parses an SVG document created by `svg` using the specified options.
+pub fn parse_with(attrs: &str, content: &str, opt: &Options) -> Result<dom::Document> {
+    parse_doc_from_data(&svg(attrs, content), opt)
+}
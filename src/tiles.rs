@@ -0,0 +1,172 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::cmp;
+
+use dom;
+use render_utils;
+
+use math::{
+    Rect,
+    Size,
+};
+
+use {
+    Options,
+};
+
+
+/// An image tile.
+#[derive(Debug, Clone, Copy)]
+pub struct Tile {
+    /// Tile column.
+    pub column: u32,
+    /// Tile row.
+    pub row: u32,
+    /// Tile position and size in the image coordinates.
+    ///
+    /// Always has an integer position and size.
+    pub rect: Rect,
+    /// An image view that should be passed to a backend `render_to_canvas`.
+    ///
+    /// The canvas should have the same size as `rect`.
+    ///
+    /// Note that `render_to_canvas` doesn't know the canvas size, so group layers
+    /// are clipped by the image view instead. A backend `render_tile_to_image`
+    /// should be preferred for huge images.
+    pub img_view: Rect,
+}
+
+/// An iterator over image tiles.
+///
+/// Tiles are iterated row by row.
+#[derive(Debug, Clone)]
+pub struct Tiles {
+    img_size: Size,
+    width: u32,
+    height: u32,
+    columns: u32,
+    rows: u32,
+    index: u32,
+}
+
+impl Tiles {
+    /// Splits a document image into `columns` x `rows` tiles.
+    ///
+    /// The image size is calculated from the document size using `Options::fit_to`,
+    /// just like in a backend `render_to_image`.
+    pub fn new(doc: &dom::Document, opt: &Options, columns: u32, rows: u32) -> Self {
+        Self::from_size(render_utils::fit_to(&doc.size, opt.fit_to), columns, rows)
+    }
+
+    /// Splits an image with the specified size into `columns` x `rows` tiles.
+    ///
+    /// The number of columns and rows is clamped to the image size,
+    /// so there are no empty tiles. Tiles can differ in size by one pixel.
+    pub fn from_size(img_size: Size, columns: u32, rows: u32) -> Self {
+        // Backends truncate the image size.
+        let width = img_size.w as u32;
+        let height = img_size.h as u32;
+
+        Tiles {
+            img_size,
+            width,
+            height,
+            columns: cmp::max(1, cmp::min(columns, width)),
+            rows: cmp::max(1, cmp::min(rows, height)),
+            index: 0,
+        }
+    }
+
+    /// Returns the number of columns.
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    /// Returns the number of rows.
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Returns the whole image size.
+    pub fn image_size(&self) -> Size {
+        self.img_size
+    }
+
+    fn tile(&self, column: u32, row: u32) -> Tile {
+        let x1 = split(self.width, self.columns, column);
+        let x2 = split(self.width, self.columns, column + 1);
+        let y1 = split(self.height, self.rows, row);
+        let y2 = split(self.height, self.rows, row + 1);
+
+        Tile {
+            column,
+            row,
+            rect: Rect::new(x1 as f64, y1 as f64, (x2 - x1) as f64, (y2 - y1) as f64),
+            img_view: Rect::new(-(x1 as f64), -(y1 as f64), self.img_size.w, self.img_size.h),
+        }
+    }
+}
+
+impl Iterator for Tiles {
+    type Item = Tile;
+
+    fn next(&mut self) -> Option<Tile> {
+        if self.width == 0 || self.height == 0 || self.index >= self.columns * self.rows {
+            return None;
+        }
+
+        let tile = self.tile(self.index % self.columns, self.index / self.columns);
+        self.index += 1;
+
+        Some(tile)
+    }
+}
+
+// Use u64 to prevent an overflow on huge images.
+fn split(len: u32, count: u32, idx: u32) -> u32 {
+    (len as u64 * idx as u64 / count as u64) as u32
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_rects() {
+        let tiles: Vec<_> = Tiles::from_size(Size::new(10.0, 7.0), 3, 2).collect();
+        assert_eq!(tiles.len(), 6);
+
+        // Row by row.
+        let order: Vec<_> = tiles.iter().map(|t| (t.column, t.row)).collect();
+        assert_eq!(order, vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]);
+
+        assert_eq!(tiles[0].rect, Rect::new(0.0, 0.0, 3.0, 3.0));
+        assert_eq!(tiles[1].rect, Rect::new(3.0, 0.0, 3.0, 3.0));
+        assert_eq!(tiles[2].rect, Rect::new(6.0, 0.0, 4.0, 3.0));
+        assert_eq!(tiles[5].rect, Rect::new(6.0, 3.0, 4.0, 4.0));
+
+        // Tiles cover the whole image without gaps.
+        let area: f64 = tiles.iter().map(|t| t.rect.w * t.rect.h).sum();
+        assert_eq!(area, 70.0);
+    }
+
+    #[test]
+    fn img_view() {
+        let tiles: Vec<_> = Tiles::from_size(Size::new(10.5, 8.0), 2, 2).collect();
+        assert_eq!(tiles[0].img_view, Rect::new(0.0, 0.0, 10.5, 8.0));
+        assert_eq!(tiles[3].img_view, Rect::new(-5.0, -4.0, 10.5, 8.0));
+    }
+
+    #[test]
+    fn clamping() {
+        let tiles = Tiles::from_size(Size::new(2.0, 3.0), 5, 0);
+        assert_eq!(tiles.columns(), 2);
+        assert_eq!(tiles.rows(), 1);
+        assert_eq!(tiles.count(), 2);
+
+        assert_eq!(Tiles::from_size(Size::new(0.5, 10.0), 2, 2).count(), 0);
+    }
+}
@@ -10,6 +10,14 @@ class Result(Enum):
     Error = 1
     Crashed = 2
     OutOfScope = 3
+    Unknown = 4
+
+
+# An empty cell means that the renderer wasn't checked yet.
+def parse_result(value):
+    if not value:
+        return Result.Unknown
+    return Result(int(value))
 
 
 def csv_file(value):
@@ -55,7 +63,7 @@ if __name__ == '__main__':
         f.write("</tr>\n")
 
         for row in rows[1:]:
-            is_out_of_scope = int(row[0]) == Result.OutOfScope.value
+            is_out_of_scope = parse_result(row[0]) == Result.OutOfScope
             if not args.with_out_of_scope and is_out_of_scope:
                 continue
 
@@ -63,7 +71,7 @@ if __name__ == '__main__':
             f.write("<td align=\"right\">{}&nbsp;</td>\n".format(row[-1]))
 
             for item in row[0:-1]:
-                res = Result(int(item))
+                res = parse_result(item)
 
                 f.write("<td class=\"{}\"></td>\n".format(res.name))
             f.write("</tr>\n")
@@ -78,11 +86,12 @@ if __name__ == '__main__':
 
         for row in rows[1:]:
             for i, cell in enumerate(row[0:-1]):
-                if int(cell) == Result.Ok.value:
+                res = parse_result(cell)
+                if res == Result.Ok:
                     table[i][0] += 1
-                elif int(cell) == Result.Error.value:
+                elif res == Result.Error:
                     table[i][1] += 1
-                elif int(cell) == Result.Crashed.value:
+                elif res == Result.Crashed:
                     table[i][2] += 1
 
 
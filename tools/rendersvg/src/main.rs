@@ -32,6 +32,7 @@ use resvg::{
     Document,
     FitTo,
     Options,
+    Rect,
 };
 
 use svgdom::{
@@ -49,7 +50,9 @@ enum Error {
 }
 
 fn main() {
-    #[cfg(all(not(feature = "cairo-backend"), not(feature = "qt-backend")))]
+    #[cfg(all(not(feature = "cairo-backend"),
+              not(feature = "qt-backend"),
+              not(feature = "pdf-backend")))]
     {
         eprintln!("Error: rendersvg has been build without any backend.");
         return;
@@ -103,11 +106,19 @@ fn process() -> Result<(), Error> {
         return Ok(());
     }
 
+    let export_id = args.value_of("export-id");
+    let export_id_only = args.is_present("export-id-only");
+    let export_area = args.value_of("export-area").map(|s| parse_area(s).unwrap());
+
     match args.value_of("backend").unwrap() {
         "cairo" => {
             #[cfg(feature = "cairo-backend")]
             {
-                let img = resvg::render_cairo::render_to_image(&doc, &opt)?;
+                let img = match (export_id, export_area) {
+                    (Some(id), _) => resvg::render_cairo::render_node_to_image(&doc, id, export_id_only, &opt)?,
+                    (_, Some(area)) => resvg::render_cairo::render_area_to_image(&doc, area, &opt)?,
+                    _ => resvg::render_cairo::render_to_image(&doc, &opt)?,
+                };
                 let mut buffer = fs::File::create(out_file)?;
                 img.write_to_png(&mut buffer)?;
             }
@@ -115,10 +126,26 @@ fn process() -> Result<(), Error> {
         "qt" => {
             #[cfg(feature = "qt-backend")]
             {
-                let img = resvg::render_qt::render_to_image(&doc, &opt)?;
+                let img = match (export_id, export_area) {
+                    (Some(id), _) => resvg::render_qt::render_node_to_image(&doc, id, export_id_only, &opt)?,
+                    (_, Some(area)) => resvg::render_qt::render_area_to_image(&doc, area, &opt)?,
+                    _ => resvg::render_qt::render_to_image(&doc, &opt)?,
+                };
                 img.save(out_file);
             }
         }
+        "pdf" => {
+            #[cfg(feature = "pdf-backend")]
+            {
+                let data = match (export_id, export_area) {
+                    (Some(id), _) => resvg::render_pdf::render_node_to_pdf(&doc, id, export_id_only, &opt)?,
+                    (_, Some(area)) => resvg::render_pdf::render_area_to_pdf(&doc, area, &opt)?,
+                    _ => resvg::render_pdf::render_to_pdf(&doc, &opt)?,
+                };
+                let mut f = fs::File::create(out_file)?;
+                f.write_all(&data)?;
+            }
+        }
         _ => unreachable!(),
     }
 
@@ -137,7 +164,7 @@ fn prepare_app<'a, 'b>() -> App<'a, 'b> {
             .help("Output file")
             .required(true)
             .index(2)
-            .validator(is_png))
+            .validator(is_output))
         .arg(Arg::with_name("dpi")
             .long("dpi")
             .help("Sets the resolution [72..4000]")
@@ -170,12 +197,32 @@ fn prepare_app<'a, 'b>() -> App<'a, 'b> {
             .value_name("COLOR")
             .help("Sets the background color")
             .validator(is_color))
+        .arg(Arg::with_name("threads")
+            .long("threads")
+            .value_name("NUM")
+            .help("Sets the number of rendering threads [1..64]")
+            .default_value("1")
+            .validator(is_threads))
         .arg(Arg::with_name("backend")
             .long("backend")
             .help("Sets the rendering backend")
             .takes_value(true)
             .default_value(default_backend())
             .possible_values(&backends()))
+        .arg(Arg::with_name("export-id")
+            .long("export-id")
+            .help("Renders only the element with the selected ID")
+            .value_name("ID"))
+        .arg(Arg::with_name("export-id-only")
+            .long("export-id-only")
+            .help("Hides all other elements when used with --export-id")
+            .requires("export-id"))
+        .arg(Arg::with_name("export-area")
+            .long("export-area")
+            .help("Renders only the selected area in the user coordinates")
+            .value_name("X0:Y0:X1:Y1")
+            .conflicts_with("export-id")
+            .validator(is_area))
         .arg(Arg::with_name("dump-svg")
             .long("dump-svg")
             .help("Saves a preprocessed SVG to the selected file")
@@ -198,6 +245,11 @@ fn backends() -> Vec<&'static str> {
         list.push("qt");
     }
 
+    #[cfg(feature = "pdf-backend")]
+    {
+        list.push("pdf");
+    }
+
     list
 }
 
@@ -213,6 +265,11 @@ fn default_backend() -> &'static str {
         return "qt"
     }
 
+    #[cfg(feature = "pdf-backend")]
+    {
+        return "pdf"
+    }
+
     unreachable!();
 }
 
@@ -225,11 +282,12 @@ fn is_svg(val: String) -> Result<(), String> {
     }
 }
 
-fn is_png(val: String) -> Result<(), String> {
-    if val.ends_with(".png") || val.ends_with(".PNG") {
+fn is_output(val: String) -> Result<(), String> {
+    let val = val.to_lowercase();
+    if val.ends_with(".png") || val.ends_with(".pdf") {
         Ok(())
     } else {
-        Err(String::from("The output file format must be PNG."))
+        Err(String::from("The output file format must be PNG or PDF."))
     }
 }
 
@@ -272,6 +330,19 @@ fn is_zoom(val: String) -> Result<(), String> {
     }
 }
 
+fn is_threads(val: String) -> Result<(), String> {
+    let n = match val.parse::<usize>() {
+        Ok(v) => v,
+        Err(e) => return Err(format!("{}", e)),
+    };
+
+    if n >= 1 && n <= 64 {
+        Ok(())
+    } else {
+        Err(String::from("Invalid number of threads."))
+    }
+}
+
 fn is_color(val: String) -> Result<(), String> {
     match svgdom::types::Color::from_str(&val) {
         Ok(_) => Ok(()),
@@ -279,6 +350,27 @@ fn is_color(val: String) -> Result<(), String> {
     }
 }
 
+fn is_area(val: String) -> Result<(), String> {
+    match parse_area(&val) {
+        Some(_) => Ok(()),
+        None => Err(String::from("Invalid area.")),
+    }
+}
+
+fn parse_area(val: &str) -> Option<Rect> {
+    let list: Vec<f64> = val.split(':').filter_map(|n| n.parse().ok()).collect();
+    if list.len() != 4 {
+        return None;
+    }
+
+    let (x0, y0, x1, y1) = (list[0], list[1], list[2], list[3]);
+    if x1 > x0 && y1 > y0 {
+        Some(Rect::new(x0, y0, x1 - x0, y1 - y0))
+    } else {
+        None
+    }
+}
+
 fn fill_options(args: &ArgMatches) -> Options {
     let mut fit_to = FitTo::Original;
     if args.is_present("width") {
@@ -300,6 +392,8 @@ fn fill_options(args: &ArgMatches) -> Options {
         dpi: value_t!(args.value_of("dpi"), u16).unwrap() as f64,
         fit_to,
         background,
+        threads: value_t!(args.value_of("threads"), usize).unwrap(),
+        .. Options::default()
     }
 }
 